@@ -0,0 +1,481 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::cost_model::CostModel;
+use crate::error::{Error, TaskError};
+use crate::graph::{NodeResult, TaskClass, TaskRun, FrozenGraph};
+use crate::report::NodeStatus;
+use crate::run_id::RunId;
+use crate::scratch::ScratchDirs;
+use crate::metrics::SchedulerMetrics;
+use crate::observer::Observer;
+use crate::scheduler::{DispatchStrategy, EachHooks, FailurePolicy, Scheduler, Watchdog};
+use crate::spawner::{Spawner, ThreadSpawner};
+
+// Every option of a `Scheduler`, all of them usable together, e.g.
+// `SchedulerBuilder::new(frozen).max_concurrency(8).build()`.
+pub struct SchedulerBuilder<C> {
+    scheduler: Scheduler<C>,
+    class_limits: Vec<(TaskClass, usize)>,
+    strict_classes: bool,
+    prestart_workers: bool,
+}
+
+impl<C> SchedulerBuilder<C> {
+    pub fn new(frozen: FrozenGraph<C>) -> SchedulerBuilder<C> {
+        let metrics = SchedulerMetrics::new(&frozen);
+        return SchedulerBuilder{
+            scheduler: Scheduler{
+                frozen: Arc::new(frozen),
+                threads: ThreadSpawner::default(),
+                spawner: None,
+                metrics: Arc::new(metrics),
+                failure_policy: FailurePolicy::FailFast,
+                max_failures: 0,
+                max_concurrency: 0,
+                max_total_weight: 0,
+                slow_task_factor: 0.0,
+                class_limits: vec![],
+                node_classes: vec![],
+                each: EachHooks{
+                    before: None,
+                    after: None,
+                    check: None,
+                },
+                watchdog: None,
+                observers: vec![],
+                dispatch_strategy: DispatchStrategy::Fifo,
+                cost_model: None,
+                abandon_in_flight: false,
+                on_abandoned: None,
+                scratch: None,
+                deadline: None,
+                skip_hopeless: false,
+                panics_as_errors: false,
+                gate_poll_interval: Duration::from_millis(10),
+                gate_timeout: None,
+                clock: Arc::new(SystemClock),
+                max_ready_queue: 0,
+            },
+            class_limits: vec![],
+            strict_classes: false,
+            prestart_workers: false,
+        };
+    }
+
+    // panics where `try_build` fails, which is only ever in strict mode
+    pub fn build(self) -> Scheduler<C> {
+        return self.try_build().unwrap_or_else(|err| panic!("{}", err));
+    }
+
+    pub fn try_build(mut self) -> Result<Scheduler<C>, Error> {
+        if self.strict_classes {
+            for node in self.scheduler.frozen.graph.nodes.iter() {
+                let Some(class) = &node.class else {
+                    continue;
+                };
+                if !self.class_limits.iter().any(|(c, _)| c == class) {
+                    return Err(Error::UnsatisfiableNode{
+                        name: node.name.to_string(),
+                        reason: format!("no limit configured for its class {:?}", class),
+                    });
+                }
+            }
+        }
+        let scheduler = &mut self.scheduler;
+        if !self.class_limits.is_empty() {
+            scheduler.class_limits = self.class_limits.iter().map(|(_, limit)| *limit).collect();
+            scheduler.node_classes = scheduler.frozen.graph.nodes.iter()
+                .map(|node| node.class.as_ref().and_then(|class| self.class_limits.iter().position(|(c, _)| c == class)))
+                .collect();
+        }
+        if self.prestart_workers {
+            self.scheduler.warm_up();
+        }
+        return Ok(self.scheduler);
+    }
+
+    // `Scheduler::warm_up` right in `build`, so that not even the first run
+    // waits for threads to be started.
+    pub fn prestart_workers(mut self, prestart: bool) -> SchedulerBuilder<C> {
+        self.prestart_workers = prestart;
+        return self;
+    }
+
+    // Among the nodes ready at the same time, dispatch the one added to the
+    // graph first, instead of the one which became ready first. Together
+    // with `max_concurrency(1)` every run executes the nodes in the very
+    // same order; with more workers only the dispatch order is fixed, tasks
+    // still finish in whatever order they happen to.
+    pub fn deterministic_dispatch(mut self, deterministic: bool) -> SchedulerBuilder<C> {
+        self.scheduler.dispatch_strategy = match deterministic {
+            true => DispatchStrategy::InsertionOrder,
+            false => DispatchStrategy::Fifo,
+        };
+        return self;
+    }
+
+    pub fn dispatch_strategy(mut self, strategy: DispatchStrategy) -> SchedulerBuilder<C> {
+        self.scheduler.dispatch_strategy = strategy;
+        return self;
+    }
+
+    // Records how long every successful node took into `model`, which
+    // `DispatchStrategy::CriticalPath` then prefers over the costs set on
+    // the graph. The model may be shared by several schedulers.
+    pub fn cost_model(mut self, model: Arc<Mutex<CostModel>>) -> SchedulerBuilder<C> {
+        self.scheduler.cost_model = Some(model);
+        return self;
+    }
+
+    // Runs the tasks through `spawner` instead of one std thread per task.
+    pub fn spawner<S>(mut self, spawner: S) -> SchedulerBuilder<C>
+        where
+            S: Spawner + 'static
+    {
+        self.scheduler.spawner = Some(Arc::new(spawner));
+        return self;
+    }
+
+    // May be called several times, every observer gets every event.
+    pub fn observer<O>(mut self, observer: O) -> SchedulerBuilder<C>
+        where
+            O: Observer + 'static
+    {
+        self.scheduler.observers.push(Arc::new(observer));
+        return self;
+    }
+
+    // Name every task thread after its node (default). Names are cut to
+    // what the platform supports, e.g. 15 bytes on Linux. Both this and
+    // `stack_size` only apply to the threads spawned by the scheduler
+    // itself, not to a custom spawner.
+    pub fn name_threads(mut self, name_threads: bool) -> SchedulerBuilder<C> {
+        self.scheduler.threads.name_threads = name_threads;
+        return self;
+    }
+
+    pub fn stack_size(mut self, size: usize) -> SchedulerBuilder<C> {
+        self.scheduler.threads.stack_size = Some(size);
+        return self;
+    }
+
+    // Gives every attempt of a task its own directory through
+    // `TaskCtx::scratch_dir`, under `std::env::temp_dir()` unless set with
+    // `scratch_base`. It is removed once the node finished, whether it
+    // succeeded, failed or panicked.
+    pub fn scratch_dirs(mut self, enabled: bool) -> SchedulerBuilder<C> {
+        self.scheduler.scratch = match enabled {
+            true => Some(self.scheduler.scratch.take().unwrap_or_default()),
+            false => None,
+        };
+        return self;
+    }
+
+    // also turns scratch directories on
+    pub fn scratch_base<P: Into<PathBuf>>(mut self, base: P) -> SchedulerBuilder<C> {
+        self.scheduler.scratch.get_or_insert_with(ScratchDirs::default).base = base.into();
+        return self;
+    }
+
+    // Leaves the scratch directories of failed and panicked nodes in place
+    // for debugging, they are never removed afterwards. Also turns scratch
+    // directories on.
+    pub fn keep_scratch_on_failure(mut self, keep: bool) -> SchedulerBuilder<C> {
+        self.scheduler.scratch.get_or_insert_with(ScratchDirs::default).keep_on_failure = keep;
+        return self;
+    }
+
+    pub fn failure_policy(mut self, policy: FailurePolicy) -> SchedulerBuilder<C> {
+        self.scheduler.failure_policy = policy;
+        return self;
+    }
+
+    // With `ContinueOnError`, stop dispatching new nodes once `n` nodes have
+    // failed; the ones still running are waited for. 0 means no limit.
+    pub fn max_failures(mut self, n: usize) -> SchedulerBuilder<C> {
+        self.scheduler.max_failures = n;
+        return self;
+    }
+
+    // Upper bound on the number of nodes running at the same time, inline
+    // ones included. 0 means no limit.
+    pub fn max_concurrency(mut self, n: usize) -> SchedulerBuilder<C> {
+        self.scheduler.max_concurrency = n;
+        return self;
+    }
+
+    // Sends `Event::QueueSaturated` whenever more than `n` nodes of a run
+    // are ready and waiting to be dispatched, for monitoring; the graph is
+    // fixed once frozen, so nothing can be held back. 0 means no bound.
+    pub fn max_ready_queue(mut self, n: usize) -> SchedulerBuilder<C> {
+        self.scheduler.max_ready_queue = n;
+        return self;
+    }
+
+    // Upper bound on the summed `Graph::set_weight` of the nodes running at
+    // the same time: a ready node waits until enough weight is released. A
+    // node heavier than the whole budget still runs, but only while no other
+    // weighted node does. 0 means no limit.
+    pub fn max_total_weight(mut self, weight: u64) -> SchedulerBuilder<C> {
+        self.scheduler.max_total_weight = weight;
+        return self;
+    }
+
+    // Flags successful nodes taking longer than `factor` times their cost,
+    // see `Graph::set_cost`, as `over_budget` in their status, with a
+    // `NodeSlow` event before they finish. Nodes without a cost are never
+    // flagged. Nothing fails because of it; 0 turns it off.
+    pub fn slow_task_factor(mut self, factor: f64) -> SchedulerBuilder<C> {
+        assert!(factor >= 0.0, "the slow task factor can not be negative");
+        self.scheduler.slow_task_factor = factor;
+        return self;
+    }
+
+    // Where runs take the time from: timestamps and durations of nodes,
+    // deadlines, stuck warnings and heartbeats, see `TaskCtx::clock`. With
+    // a `ManualClock` the stuck warnings are checked on its time, waiting
+    // in between still takes real time.
+    pub fn clock(mut self, clock: Arc<dyn Clock>) -> SchedulerBuilder<C> {
+        self.scheduler.clock = clock;
+        return self;
+    }
+
+    // Stops dispatching once a run has taken `deadline`, the nodes running
+    // are let finish; the run then fails with `DeadlineExceeded`, unless
+    // nothing was left to run.
+    pub fn deadline(mut self, deadline: Duration) -> SchedulerBuilder<C> {
+        self.scheduler.deadline = Some(deadline);
+        return self;
+    }
+
+    // With a deadline, a ready node is not started if its estimate, see
+    // `DispatchStrategy::CriticalPath`, plus the longest path of estimates
+    // ahead of it is more than the time left. It and everything depending
+    // on it are not run, the others still are, and the run fails with
+    // `DeadlineExceeded`. Nodes without an estimate always run.
+    pub fn skip_hopeless_nodes(mut self, skip: bool) -> SchedulerBuilder<C> {
+        self.scheduler.skip_hopeless = skip;
+        return self;
+    }
+
+    // How often the gates of nodes otherwise ready are asked whether they
+    // are open, see `Graph::set_gate`; 10 ms by default.
+    pub fn gate_poll_interval(mut self, interval: Duration) -> SchedulerBuilder<C> {
+        self.scheduler.gate_poll_interval = interval;
+        return self;
+    }
+
+    // A node whose gate stays closed for longer than `timeout` once the
+    // node is ready fails with `RuntimeTimedOut`, without being started.
+    pub fn gate_timeout(mut self, timeout: Duration) -> SchedulerBuilder<C> {
+        self.scheduler.gate_timeout = Some(timeout);
+        return self;
+    }
+
+    // A node which panicked fails with `RuntimeFailed` and a
+    // `PanickedTaskError`, like one returning an error, instead of with
+    // `RuntimePanicked`. Fallbacks and hooks see panics either way; the
+    // report still has the node as panicked.
+    pub fn panics_as_errors(mut self, panics_as_errors: bool) -> SchedulerBuilder<C> {
+        self.scheduler.panics_as_errors = panics_as_errors;
+        return self;
+    }
+
+    // Every class set on a node must have been given a limit through
+    // `limit_class`, so that a typo in a class name does not silently run
+    // its nodes unlimited; checked by `try_build`.
+    pub fn strict_classes(mut self, strict: bool) -> SchedulerBuilder<C> {
+        self.strict_classes = strict;
+        return self;
+    }
+
+    // At most `n` nodes of `class` run at the same time; a node waiting for
+    // its class does not hold back ready nodes of other classes. Classes
+    // without a limit, and nodes without a class, are not limited. Setting
+    // a class again replaces its limit.
+    pub fn limit_class(mut self, class: TaskClass, n: usize) -> SchedulerBuilder<C> {
+        assert!(n > 0, "class limits must be at least 1");
+        match self.class_limits.iter_mut().find(|(c, _)| *c == class) {
+            Some((_, limit)) => *limit = n,
+            None => self.class_limits.push((class, n)),
+        }
+        return self;
+    }
+
+    // When a fail-fast run stops, return right away instead of waiting for
+    // the nodes still running, which finish in the background. Only runs
+    // whose tasks own their context can do so, that is `run_arc` and
+    // `spawn_run` with `C: 'static` behind an `Arc`; tasks borrowing the
+    // context of `run` are always waited for.
+    pub fn abandon_in_flight_on_error(mut self, abandon: bool) -> SchedulerBuilder<C> {
+        self.scheduler.abandon_in_flight = abandon;
+        return self;
+    }
+
+    // Called with the name and status of every node finishing after its run
+    // abandoned it, on the thread which ran it.
+    pub fn on_abandoned<F>(mut self, f: F) -> SchedulerBuilder<C>
+        where
+            F: Fn(&str, NodeStatus) + Send + Sync + 'static
+    {
+        self.scheduler.on_abandoned = Some(Arc::new(f));
+        return self;
+    }
+
+    // Called with the node name right before every task, on the thread that
+    // runs it. These only observe: a task is never skipped because of them.
+    // When the hook panics the task, `post_task_check` and `on_after_each`
+    // still run, the latter with the result of the task, and the node then
+    // panics, unless the task panicked itself.
+    pub fn on_before_each<F>(mut self, f: F) -> SchedulerBuilder<C>
+        where
+            F: Fn(&str, &C) + Send + Sync + 'static
+    {
+        self.scheduler.each.before = Some(Arc::new(f));
+        return self;
+    }
+
+    // Called right after every task with its final result, after the hooks
+    // and fallback of the node itself.
+    pub fn on_after_each<F>(mut self, f: F) -> SchedulerBuilder<C>
+        where
+            F: Fn(&str, &C, &NodeResult) + Send + Sync + 'static
+    {
+        self.scheduler.each.after = Some(Arc::new(f));
+        return self;
+    }
+
+    // Called after every task on the thread that ran it, before
+    // `on_after_each`, e.g. to find mutexes of the context a task left
+    // poisoned. When it fails after a node which succeeded, the node fails
+    // with a `PostTaskCheckError`; a node which failed keeps its own error.
+    pub fn post_task_check<F>(mut self, f: F) -> SchedulerBuilder<C>
+        where
+            F: Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static
+    {
+        self.scheduler.each.check = Some(Arc::new(f));
+        return self;
+    }
+
+    // Calls `f` with the node name and how long it has been running for
+    // every node still running after `after`, and again every further
+    // `after`, unless the node sent a heartbeat through `TaskCtx` within
+    // the last `after`. Nothing is interrupted, this only reports. `f`
+    // runs on the scheduling thread, which executes inline nodes as well:
+    // while one of them runs there are no warnings.
+    pub fn stuck_warning_after<F>(mut self, after: Duration, f: F) -> SchedulerBuilder<C>
+        where
+            F: Fn(&str, Duration) + Send + Sync + 'static
+    {
+        assert!(!after.is_zero(), "stuck warnings need a non-zero interval");
+        self.scheduler.watchdog = Some(Watchdog{
+            after: after,
+            callback: Arc::new(f),
+        });
+        return self;
+    }
+}
+
+// Per-run overrides for `Scheduler::run_with`, the rest is taken from the
+// scheduler, e.g. `RunOptions{targets: Some(vec![..]), ..RunOptions::default()}`.
+pub struct RunOptions<C> {
+    // only run these nodes and what they depend on
+    pub targets: Option<Vec<String>>,
+    // nodes not run at all but taken as done, their children still run
+    pub skip: Vec<String>,
+    pub failure_policy: Option<FailurePolicy>,
+    pub max_failures: Option<usize>,
+    pub max_concurrency: Option<usize>,
+    // the id of the run, instead of a generated one
+    pub run_id: Option<RunId>,
+    // by node name, what `TaskCtx::param` gives, over `Graph::set_param`;
+    // every name must be a node of the graph
+    pub node_params: HashMap<String, HashMap<String, String>>,
+    // the share of a `ThreadPoolSpawner` the run gets next to other runs,
+    // in proportion to `priority + 1`
+    pub priority: u8,
+    // by node name, the task run instead of that of the graph, hooks and
+    // fallback of the node still apply; every name must be a node of the
+    // graph
+    pub task_overrides: HashMap<String, Arc<dyn TaskRun<C>>>,
+}
+
+impl<C> Default for RunOptions<C> {
+    fn default() -> RunOptions<C> {
+        return RunOptions{
+            targets: None,
+            skip: vec![],
+            failure_policy: None,
+            max_failures: None,
+            max_concurrency: None,
+            run_id: None,
+            node_params: HashMap::new(),
+            priority: 0,
+            task_overrides: HashMap::new(),
+        };
+    }
+}
+
+impl<C> Clone for RunOptions<C> {
+    fn clone(&self) -> RunOptions<C> {
+        return RunOptions{
+            targets: self.targets.clone(),
+            skip: self.skip.clone(),
+            failure_policy: self.failure_policy,
+            max_failures: self.max_failures,
+            max_concurrency: self.max_concurrency,
+            run_id: self.run_id.clone(),
+            node_params: self.node_params.clone(),
+            priority: self.priority,
+            task_overrides: self.task_overrides.clone(),
+        };
+    }
+}
+
+impl<C> std::fmt::Debug for RunOptions<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut overridden: Vec<&String> = self.task_overrides.keys().collect();
+        overridden.sort();
+        f.debug_struct("RunOptions")
+            .field("targets", &self.targets)
+            .field("skip", &self.skip)
+            .field("failure_policy", &self.failure_policy)
+            .field("max_failures", &self.max_failures)
+            .field("max_concurrency", &self.max_concurrency)
+            .field("run_id", &self.run_id)
+            .field("node_params", &self.node_params)
+            .field("priority", &self.priority)
+            .field("task_overrides", &overridden)
+            .finish()
+    }
+}
+
+impl<C> RunOptions<C> {
+    pub fn run_id<I: Into<RunId>>(mut self, run_id: I) -> RunOptions<C> {
+        self.run_id = Some(run_id.into());
+        return self;
+    }
+
+    pub fn node_params(mut self, params: HashMap<String, HashMap<String, String>>) -> RunOptions<C> {
+        self.node_params = params;
+        return self;
+    }
+
+    pub fn priority(mut self, priority: u8) -> RunOptions<C> {
+        self.priority = priority;
+        return self;
+    }
+
+    // e.g. a test double for a node too expensive to run in tests
+    pub fn override_task<T>(mut self, name: &str, task: T) -> RunOptions<C>
+        where
+            T: TaskRun<C> + 'static
+    {
+        self.task_overrides.insert(name.to_string(), Arc::new(task));
+        return self;
+    }
+}
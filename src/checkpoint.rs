@@ -0,0 +1,97 @@
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+// Where `Scheduler::run_resumable` remembers the nodes that succeeded, so
+// that a run dying half way may be started again without redoing them.
+// `mark_done` is called from the scheduling loop after every successful
+// node, in completion order.
+pub trait Checkpoint: Send + Sync {
+    fn mark_done(&self, run_id: &str, node: &str) -> io::Result<()>;
+    fn load_done(&self, run_id: &str) -> io::Result<HashSet<String>>;
+}
+
+// One line `<run id>\t<node>` per finished node, appended to a single file
+// shared by all runs. A missing file has nothing done yet; a line cut off
+// by a crash is ignored, as it was never acknowledged.
+pub struct FileCheckpoint {
+    path: PathBuf,
+    sync: bool,
+}
+
+impl FileCheckpoint {
+    pub fn new<P: AsRef<Path>>(path: P) -> FileCheckpoint {
+        return FileCheckpoint{
+            path: path.as_ref().to_path_buf(),
+            sync: false,
+        };
+    }
+
+    // fsync after every line, so that a node is never run twice even when
+    // the whole machine goes down
+    pub fn sync(mut self, sync: bool) -> FileCheckpoint {
+        self.sync = sync;
+        return self;
+    }
+
+    pub fn path(&self) -> &Path {
+        return &self.path;
+    }
+}
+
+impl Checkpoint for FileCheckpoint {
+    fn mark_done(&self, run_id: &str, node: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new().create(true).truncate(false).read(true).write(true).open(&self.path)?;
+        let len = file.seek(SeekFrom::End(0))?;
+        if len > 0 {
+            let mut last = [0u8];
+            file.seek(SeekFrom::End(-1))?;
+            file.read_exact(&mut last)?;
+            if last[0] != b'\n' {
+                // drop the line cut off before, nobody reads it anyway
+                let mut content = vec![];
+                file.seek(SeekFrom::Start(0))?;
+                file.read_to_end(&mut content)?;
+                let keep = content.iter().rposition(|c| *c == b'\n').map_or(0, |i| i + 1);
+                file.set_len(keep as u64)?;
+                file.seek(SeekFrom::Start(keep as u64))?;
+            }
+        }
+        let line = format!("{}\t{}\n", run_id, node);
+        file.write_all(line.as_bytes())?;
+        if self.sync {
+            file.sync_data()?;
+        }
+        return Ok(());
+    }
+
+    fn load_done(&self, run_id: &str) -> io::Result<HashSet<String>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(HashSet::new()),
+            Err(err) => return Err(err),
+        };
+        let mut done = HashSet::new();
+        let mut reader = BufReader::new(file);
+        let mut line = vec![];
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                break;
+            }
+            let Some(line) = line.strip_suffix(b"\n") else {
+                continue;
+            };
+            let Ok(line) = std::str::from_utf8(line) else {
+                continue;
+            };
+            if let Some((id, node)) = line.split_once('\t') {
+                if id == run_id {
+                    done.insert(node.to_string());
+                }
+            }
+        }
+        return Ok(done);
+    }
+}
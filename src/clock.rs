@@ -0,0 +1,70 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::time::Instant;
+
+// Where the scheduler and the task wrappers take the time from, so that
+// deadlines, timeouts and backoff can be tested without waiting for them.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+
+    fn sleep(&self, duration: Duration);
+}
+
+// The real time, and the default everywhere. Sleeping blocks the thread,
+// which wasm32-unknown-unknown does not support.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        return Instant::now();
+    }
+
+    fn sleep(&self, duration: Duration) {
+        std::thread::sleep(duration);
+    }
+}
+
+// A clock which only moves when told to: `advance` moves it forward, and
+// so does `sleep`, which returns right away. Shared as `Arc<ManualClock>`,
+// every thread sees the same time.
+#[derive(Debug)]
+pub struct ManualClock {
+    start: Instant,
+    elapsed: Mutex<Duration>,
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        return ManualClock::new();
+    }
+}
+
+impl ManualClock {
+    pub fn new() -> ManualClock {
+        return ManualClock{
+            start: Instant::now(),
+            elapsed: Mutex::new(Duration::ZERO),
+        };
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.elapsed.lock().unwrap_or_else(|err| err.into_inner()) += duration;
+    }
+
+    // how far the clock was moved since it was created
+    pub fn elapsed(&self) -> Duration {
+        return *self.elapsed.lock().unwrap_or_else(|err| err.into_inner());
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        return self.start + self.elapsed();
+    }
+
+    fn sleep(&self, duration: Duration) {
+        self.advance(duration);
+    }
+}
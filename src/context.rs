@@ -0,0 +1,131 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::TaskError;
+use crate::graph::TaskRun;
+use crate::metrics::NodeProgress;
+use crate::scratch::Scratch;
+
+// What tasks added through `Graph::add_node_with_ctx` get instead of the
+// bare context: the context itself, reachable through deref, and a handle
+// to report liveness and progress, which is read back through
+// `SchedulerMetrics`. Both only store into atomics.
+pub struct TaskCtx<'a, C> {
+    ctx: &'a C,
+    name: &'a str,
+    progress: &'a NodeProgress,
+    scratch: Option<&'a Scratch>,
+    output: &'a Mutex<Option<Output>>,
+    clock: &'a dyn Clock,
+    params: &'a HashMap<String, String>,
+    run_scratch: Option<&'a (dyn Any + Send + Sync)>,
+}
+
+pub(crate) type Output = Box<dyn Any + Send>;
+
+// made by `Scheduler::run_with_scratch` for a single run
+pub(crate) type RunScratch = Arc<dyn Any + Send + Sync>;
+
+impl<'a, C> TaskCtx<'a, C> {
+    pub(crate) fn new(ctx: &'a C, name: &'a str, progress: &'a NodeProgress, scratch: Option<&'a Scratch>, output: &'a Mutex<Option<Output>>, clock: &'a dyn Clock, params: &'a HashMap<String, String>) -> TaskCtx<'a, C> {
+        return TaskCtx{
+            ctx: ctx,
+            name: name,
+            progress: progress,
+            scratch: scratch,
+            output: output,
+            clock: clock,
+            params: params,
+            run_scratch: None,
+        };
+    }
+
+    pub(crate) fn with_run_scratch(mut self, run_scratch: Option<&'a (dyn Any + Send + Sync)>) -> TaskCtx<'a, C> {
+        self.run_scratch = run_scratch;
+        return self;
+    }
+
+    pub fn ctx(&self) -> &'a C {
+        return self.ctx;
+    }
+
+    // name of the node being run
+    pub fn name(&self) -> &'a str {
+        return self.name;
+    }
+
+    // set for the node by `RunOptions::node_params`, or else by
+    // `Graph::set_param`
+    pub fn param(&self, key: &str) -> Option<&'a str> {
+        return self.params.get(key).map(String::as_str);
+    }
+
+    // what `Scheduler::run_with_scratch` made for this run, if it is an `S`
+    pub fn run_scratch<S: Any>(&self) -> Option<&'a S> {
+        return self.run_scratch.and_then(|run_scratch| run_scratch.downcast_ref());
+    }
+
+    // the one of the scheduler, see `SchedulerBuilder::clock`
+    pub fn clock(&self) -> &'a dyn Clock {
+        return self.clock;
+    }
+
+    pub fn heartbeat(&self) {
+        self.progress.heartbeat(self.clock.now());
+    }
+
+    // also counts as a heartbeat, `progress` is clamped to 0.0..=1.0
+    pub fn set_progress(&self, progress: f32) {
+        self.progress.set_progress(progress);
+        self.progress.heartbeat(self.clock.now());
+    }
+
+    // What `Scheduler::run_collect` hands back for this node, if it is a
+    // leaf; a later call replaces an earlier one. Otherwise it is dropped
+    // once the node finished.
+    pub fn set_output<T: Any + Send>(&self, value: T) {
+        *self.output.lock().unwrap_or_else(|err| err.into_inner()) = Some(Box::new(value));
+    }
+
+    // A directory of this attempt alone, created on the first call and
+    // removed with everything in it once the node finished. Panics unless
+    // the scheduler was built with `SchedulerBuilder::scratch_dirs`, or if
+    // the directory can not be created.
+    pub fn scratch_dir(&self) -> &'a Path {
+        return match self.scratch {
+            Some(scratch) => scratch.path(),
+            None => panic!("scratch directories are not enabled, see SchedulerBuilder::scratch_dirs"),
+        };
+    }
+}
+
+impl<C> Deref for TaskCtx<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        return self.ctx;
+    }
+}
+
+pub(crate) struct WithCtx<F>(pub F);
+
+impl<C, F> TaskRun<C> for WithCtx<F>
+    where
+        F: Fn(&TaskCtx<'_, C>) -> Result<(), TaskError> + Send + Sync
+{
+    // run outside of a scheduler, nothing is reported
+    fn run(&self, ctx: &C) -> Result<(), TaskError> {
+        let progress = NodeProgress::default();
+        let output = Mutex::new(None);
+        let params = HashMap::new();
+        return (self.0)(&TaskCtx::new(ctx, "", &progress, None, &output, &SystemClock, &params));
+    }
+
+    fn run_with_ctx(&self, ctx: &TaskCtx<'_, C>) -> Result<(), TaskError> {
+        return (self.0)(ctx);
+    }
+}
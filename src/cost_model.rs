@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+// How long nodes took in earlier runs, by node name, as an exponentially
+// weighted moving average: every new duration `d` moves the estimate `e` to
+// `alpha * d + (1 - alpha) * e`, the first one is taken as is. A scheduler
+// given one through `SchedulerBuilder::cost_model` records every node that
+// succeeded without its fallback, and `costs` is what `FrozenGraph::simulate`
+// takes.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CostModel {
+    alpha: f64,
+    costs: HashMap<String, Duration>,
+}
+
+impl Default for CostModel {
+    fn default() -> CostModel {
+        return CostModel::new();
+    }
+}
+
+impl CostModel {
+    // alpha 0.5
+    pub fn new() -> CostModel {
+        return CostModel::with_alpha(0.5);
+    }
+
+    // `alpha` in 0.0..=1.0, the higher the faster old durations are forgotten
+    pub fn with_alpha(alpha: f64) -> CostModel {
+        assert!((0.0..=1.0).contains(&alpha), "alpha must be within 0.0..=1.0");
+        return CostModel{
+            alpha: alpha,
+            costs: HashMap::new(),
+        };
+    }
+
+    pub fn alpha(&self) -> f64 {
+        return self.alpha;
+    }
+
+    pub fn record(&mut self, name: &str, duration: Duration) {
+        match self.costs.get_mut(name) {
+            Some(cost) => {
+                let secs = self.alpha * duration.as_secs_f64() + (1.0 - self.alpha) * cost.as_secs_f64();
+                *cost = Duration::from_secs_f64(secs);
+            },
+            None => {
+                self.costs.insert(name.to_string(), duration);
+            },
+        }
+    }
+
+    // None for nodes never recorded
+    pub fn estimate(&self, name: &str) -> Option<Duration> {
+        return self.costs.get(name).copied();
+    }
+
+    pub fn costs(&self) -> &HashMap<String, Duration> {
+        return &self.costs;
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string(self).expect("a cost model is always serializable");
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<CostModel, serde_json::Error> {
+        return serde_json::from_str(json);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::error::Error::{self, *};
+use crate::graph::{Graph, Task};
+
+// A tiny text format for edges: "A -> B -> C; A -> D". Statements are
+// separated by `;` or newlines, every `->` adds an edge between the names
+// around it, and whitespace around names is ignored.
+
+impl<C> Graph<C> {
+    // The first error is returned: `InvalidDsl` with the offending token and
+    // its byte offset for malformed input, otherwise whatever `add_edge`
+    // failed with.
+    pub fn add_edges_dsl(&mut self, dsl: &str) -> Result<(), Error> {
+        for chain in parse(dsl)? {
+            for pair in chain.windows(2) {
+                self.add_edge(pair[0], pair[1])?;
+            }
+        }
+        return Ok(());
+    }
+}
+
+impl<C: 'static> Graph<C> {
+    // Same as `add_edges_dsl` on an empty graph, creating every node on its
+    // first mention with the task made by `factory`.
+    pub fn from_dsl<F>(dsl: &str, factory: F) -> Result<Graph<C>, Error>
+        where
+            F: Fn(&str) -> Task<C>
+    {
+        let chains = parse(dsl)?;
+        let mut graph = Graph::new();
+        for name in chains.iter().flatten() {
+            if !graph.nodes_indices.contains_key(*name) {
+                graph.add_node(name, factory(name))?;
+            }
+        }
+        for chain in chains {
+            for pair in chain.windows(2) {
+                graph.add_edge(pair[0], pair[1])?;
+            }
+        }
+        return Ok(graph);
+    }
+}
+
+fn parse(dsl: &str) -> Result<Vec<Vec<&str>>, Error> {
+    let mut chains = vec![];
+    let mut offset = 0;
+    for statement in dsl.split([';', '\n']) {
+        let begin = offset;
+        offset += statement.len() + 1;
+        if statement.trim().is_empty() {
+            continue;
+        }
+        let mut chain = vec![];
+        let mut position = begin;
+        let mut segments = statement.split("->").peekable();
+        while let Some(segment) = segments.next() {
+            let name = segment.trim();
+            let arrow = position + segment.len();
+            if name.is_empty() {
+                // an arrow where a name should be, or a dangling one
+                let position = if segments.peek().is_some() { arrow } else { position - 2 };
+                return Err(InvalidDsl{
+                    token: String::from("->"),
+                    position: position,
+                });
+            }
+            chain.push(name);
+            position = arrow + 2;
+        }
+        chains.push(chain);
+    }
+    return Ok(chains);
+}
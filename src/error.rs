@@ -1,23 +1,65 @@
+use std::time::Duration;
+
+use crate::graph::LimitKind;
+use crate::panics::PanicInfoCaptured;
+use crate::run_id::RunId;
+
 pub type TaskError = Box<dyn std::error::Error + Send>;
 pub type PanicError = Box<dyn std::any::Any + Send>;
 
+// task errors raised by the crate itself only carry a message
+pub(crate) fn task_error(reason: String) -> TaskError {
+    let err: Box<dyn std::error::Error + Send + Sync> = reason.into();
+    return err;
+}
+
 #[derive(Debug)]
 pub enum Error {
-    InvalidNode{name: String},
+    InvalidNode{name: String, reason: String},
     DuplicatedNode{name: String},
     NodeNotFound{name: String},
     InvalidEdge{from_node: String, to_node: String},
     DuplicatedEdge{from_node: String, to_node: String},
-    CyclicGraphFound{ring: String},
-    RuntimeFailed{node: String, err: TaskError},
-    RuntimePanicked{node: String, err: PanicError},
+    // `ring` are the nodes on or behind a cycle; removing the edges in
+    // `feedback_edges` would break every cycle, see `Graph::feedback_edges`,
+    // unless the graph was too large to look for them
+    CyclicGraphFound{ring: String, feedback_edges: Vec<(String, String)>},
+    DisconnectedGraph{components: Vec<Vec<String>>},
+    MultipleRoots{roots: Vec<String>},
+    EmptyGraph,
+    LimitExceeded{kind: LimitKind, limit: usize},
+    UnsatisfiableNode{name: String, reason: String},
+    InvalidDsl{token: String, position: usize},
+    // every problem a `LenientGraph` ran into
+    BuildFailed{diagnostics: Vec<Error>},
+    // `in_flight` are the other nodes which were still running, or whose
+    // results were not looked at, when the run stopped at `node`. `index`
+    // is that of `node` in the frozen graph, see `FrozenGraph::index_of`.
+    RuntimeFailed{node: String, index: usize, err: TaskError, in_flight: Vec<String>, run_id: RunId},
+    RuntimePanicked{node: String, index: usize, err: PanicError, info: Option<Box<PanicInfoCaptured>>, in_flight: Vec<String>, run_id: RunId},
+    // the task of `node` failed with a `TimeoutError`, see `with_timeout`
+    RuntimeTimedOut{node: String, index: usize, limit: std::time::Duration, elapsed: std::time::Duration, in_flight: Vec<String>, run_id: RunId},
+    SpawnFailed{node: String, index: usize, err: SpawnError},
+    MultipleFailures{failures: Vec<Error>, aborted: bool},
+    CheckpointFailed{run_id: String, err: std::io::Error},
+    // the run was cancelled before every node ran, see `RunHandle::cancel`,
+    // `pending` are the nodes which did not complete
+    Cancelled{completed: Vec<String>, pending: Vec<String>},
+    Drained{completed: Vec<String>, not_run: Vec<String>},
+    // `SchedulerBuilder::deadline` passed, or nodes were skipped for it,
+    // before every node ran
+    DeadlineExceeded{deadline: Duration, completed: Vec<String>, not_run: Vec<String>},
+    // a leaf collected by `Scheduler::run_collect` gave no output, or one
+    // of another type than `expected`
+    MissingOutput{node: String, index: usize},
+    OutputTypeMismatch{node: String, index: usize, expected: &'static str},
 }
 
 impl std::fmt::Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::InvalidNode{name} => {
-                write!(f, "invalid node: {}", name)
+            Self::InvalidNode{name, reason} => {
+                write!(f, "invalid node {:?}: {}", name, reason)
             },
             Self::DuplicatedNode{name} => {
                 write!(f, "duplicated node: {}", name)
@@ -31,18 +73,91 @@ impl std::fmt::Display for Error {
             Self::DuplicatedEdge{from_node, to_node} => {
                 write!(f, "duplicated edge: {} -> {}", from_node, to_node)
             },
-            Self::CyclicGraphFound{ring} => {
-                write!(f, "found ring in graph: {}", ring)
+            Self::CyclicGraphFound{ring, feedback_edges} => {
+                write!(f, "found ring in graph: {}", ring)?;
+                for (i, (from_node, to_node)) in feedback_edges.iter().enumerate() {
+                    write!(f, "{} {} -> {}", if i == 0 { ", try removing" } else { "," }, from_node, to_node)?;
+                }
+                Ok(())
+            },
+            Self::DisconnectedGraph{components} => {
+                write!(f, "graph is not connected:")?;
+                for (i, component) in components.iter().enumerate() {
+                    write!(f, "{} [{}]", if i == 0 { "" } else { "," }, component.join(", "))?;
+                }
+                Ok(())
+            },
+            Self::MultipleRoots{roots} => {
+                write!(f, "graph has more than one root: [{}]", roots.join(", "))
+            },
+            Self::EmptyGraph => {
+                write!(f, "graph has no nodes")
+            },
+            Self::LimitExceeded{kind, limit} => {
+                let what = match kind {
+                    LimitKind::Nodes => "nodes",
+                    LimitKind::Edges => "edges",
+                    LimitKind::FanOut => "children of a node",
+                };
+                write!(f, "more than {} {}", limit, what)
             },
-            Self::RuntimeFailed{node, err} => {
+            Self::UnsatisfiableNode{name, reason} => {
+                write!(f, "node {} can never run: {}", name, reason)
+            },
+            Self::InvalidDsl{token, position} => {
+                write!(f, "invalid token in dsl at {}: {}", position, token)
+            },
+            Self::BuildFailed{diagnostics} => {
+                write!(f, "{} problems building the graph", diagnostics.len())?;
+                for (i, err) in diagnostics.iter().enumerate() {
+                    write!(f, "{} {}", if i == 0 { ":" } else { ";" }, err)?;
+                }
+                Ok(())
+            },
+            Self::RuntimeFailed{node, err, ..} => {
                 write!(f, "run {} failed: {}", node, err)
             },
-            Self::RuntimePanicked{node, err} => {
-                if let Some(s) = err.downcast_ref::<String>() {
-                    write!(f, "run {} panic: {}", node, s)
+            Self::RuntimePanicked{node, err, info, ..} => {
+                if let Some(s) = panic_message(err) {
+                    write!(f, "run {} panic: {}", node, s)?;
                 } else {
-                    write!(f, "run {} panic occurred", node)
+                    write!(f, "run {} panic occurred", node)?;
+                }
+                if let Some(info) = info {
+                    write!(f, " at {}", info)?;
+                }
+                Ok(())
+            },
+            Self::RuntimeTimedOut{node, limit, elapsed, ..} => {
+                write!(f, "run {} timed out: exceeded {:?} (ran {:?})", node, limit, elapsed)
+            },
+            Self::SpawnFailed{node, err, ..} => {
+                write!(f, "spawn {} failed: {}", node, err)
+            },
+            Self::MultipleFailures{failures, aborted} => {
+                write!(f, "{} nodes failed", failures.len())?;
+                if *aborted {
+                    write!(f, ", run aborted")?;
                 }
+                write!(f, ":{}", self.failures())
+            },
+            Self::CheckpointFailed{run_id, err} => {
+                write!(f, "checkpoint of run {} failed: {}", run_id, err)
+            },
+            Self::Cancelled{completed, pending} => {
+                write!(f, "run cancelled after {} nodes, {} pending", completed.len(), pending.len())
+            },
+            Self::Drained{completed, not_run} => {
+                write!(f, "run drained after {} nodes, {} not run", completed.len(), not_run.len())
+            },
+            Self::DeadlineExceeded{deadline, not_run, ..} => {
+                write!(f, "run missed its deadline of {:?}, {} nodes not run", deadline, not_run.len())
+            },
+            Self::MissingOutput{node, ..} => {
+                write!(f, "node {} has no output", node)
+            },
+            Self::OutputTypeMismatch{node, expected, ..} => {
+                write!(f, "output of node {} is not a {}", node, expected)
             },
         }
     }
@@ -51,3 +166,339 @@ impl std::fmt::Display for Error {
 impl std::error::Error for Error {
 
 }
+
+impl Error {
+    // the node a run failed at
+    pub fn node_name(&self) -> Option<&str> {
+        return match self {
+            Self::RuntimeFailed{node, ..}
+            | Self::RuntimePanicked{node, ..}
+            | Self::RuntimeTimedOut{node, ..}
+            | Self::SpawnFailed{node, ..}
+            | Self::MissingOutput{node, ..}
+            | Self::OutputTypeMismatch{node, ..} => Some(node),
+            _ => None,
+        };
+    }
+
+    // the index of `node_name` in the frozen graph
+    pub fn node_index(&self) -> Option<usize> {
+        return match self {
+            Self::RuntimeFailed{index, ..}
+            | Self::RuntimePanicked{index, ..}
+            | Self::RuntimeTimedOut{index, ..}
+            | Self::SpawnFailed{index, ..}
+            | Self::MissingOutput{index, ..}
+            | Self::OutputTypeMismatch{index, ..} => Some(*index),
+            _ => None,
+        };
+    }
+
+    // the payload of a panic, if it was a string
+    pub fn panic_message(&self) -> Option<String> {
+        return match self {
+            Self::RuntimePanicked{err, ..} => panic_message(err),
+            _ => None,
+        };
+    }
+
+    pub fn in_flight(&self) -> &[String] {
+        return match self {
+            Self::RuntimeFailed{in_flight, ..}
+            | Self::RuntimePanicked{in_flight, ..}
+            | Self::RuntimeTimedOut{in_flight, ..} => in_flight,
+            _ => &[],
+        };
+    }
+
+    // the run a node failed in
+    pub fn run_id(&self) -> Option<&RunId> {
+        return match self {
+            Self::RuntimeFailed{run_id, ..}
+            | Self::RuntimePanicked{run_id, ..}
+            | Self::RuntimeTimedOut{run_id, ..} => Some(run_id),
+            _ => None,
+        };
+    }
+
+    pub(crate) fn with_in_flight(mut self, names: Vec<String>) -> Error {
+        match &mut self {
+            Self::RuntimeFailed{in_flight, ..}
+            | Self::RuntimePanicked{in_flight, ..}
+            | Self::RuntimeTimedOut{in_flight, ..} => *in_flight = names,
+            _ => (),
+        }
+        return self;
+    }
+
+    // The failures of a `MultipleFailures`, in the order the nodes finished
+    // in; any other error is a list of itself.
+    pub fn failures(&self) -> Failures<'_> {
+        let errors = match self {
+            Self::MultipleFailures{failures, ..} => &failures[..],
+            _ => std::slice::from_ref(self),
+        };
+        return Failures{errors: errors, max_listed: MAX_LISTED_FAILURES};
+    }
+
+    pub fn task_error(&self) -> Option<&(dyn std::error::Error + Send + 'static)> {
+        return match self {
+            Self::RuntimeFailed{err, ..} => Some(&**err),
+            _ => None,
+        };
+    }
+
+    // errors from running a graph, as opposed to building one
+    pub fn is_runtime(&self) -> bool {
+        return matches!(self, Self::RuntimeFailed{..}
+            | Self::RuntimePanicked{..}
+            | Self::RuntimeTimedOut{..}
+            | Self::SpawnFailed{..}
+            | Self::MultipleFailures{..}
+            | Self::CheckpointFailed{..}
+            | Self::Cancelled{..}
+            | Self::Drained{..}
+            | Self::DeadlineExceeded{..}
+            | Self::MissingOutput{..}
+            | Self::OutputTypeMismatch{..});
+    }
+
+    pub fn is_build(&self) -> bool {
+        return matches!(self, Self::InvalidNode{..}
+            | Self::DuplicatedNode{..}
+            | Self::NodeNotFound{..}
+            | Self::InvalidEdge{..}
+            | Self::DuplicatedEdge{..}
+            | Self::CyclicGraphFound{..}
+            | Self::DisconnectedGraph{..}
+            | Self::MultipleRoots{..}
+            | Self::EmptyGraph
+            | Self::LimitExceeded{..}
+            | Self::UnsatisfiableNode{..}
+            | Self::InvalidDsl{..}
+            | Self::BuildFailed{..});
+    }
+}
+
+pub(crate) fn panic_message(err: &PanicError) -> Option<String> {
+    if let Some(s) = err.downcast_ref::<String>() {
+        return Some(s.clone());
+    }
+    if let Some(s) = err.downcast_ref::<&'static str>() {
+        return Some(s.to_string());
+    }
+    return None;
+}
+
+#[derive(Debug)]
+pub struct SpawnError {
+    reason: String,
+}
+
+impl SpawnError {
+    pub fn new(reason: &str) -> SpawnError {
+        SpawnError{
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl From<std::io::Error> for SpawnError {
+    fn from(err: std::io::Error) -> SpawnError {
+        SpawnError{
+            reason: err.to_string(),
+        }
+    }
+}
+
+impl std::fmt::Display for SpawnError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.reason)
+    }
+}
+
+impl std::error::Error for SpawnError {
+
+}
+
+// What a panicking task fails with under `SchedulerBuilder::panics_as_errors`,
+// reached via `err.task_error()` and then `downcast_ref::<PanickedTaskError>()`.
+pub struct PanickedTaskError {
+    // the payload, if it was a string
+    pub message: Option<String>,
+    pub payload: PanicError,
+    pub info: Option<Box<PanicInfoCaptured>>,
+}
+
+impl PanickedTaskError {
+    pub fn new(payload: PanicError, info: Option<Box<PanicInfoCaptured>>) -> PanickedTaskError {
+        PanickedTaskError{
+            message: panic_message(&payload),
+            payload: payload,
+            info: info,
+        }
+    }
+}
+
+impl std::fmt::Debug for PanickedTaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PanickedTaskError")
+            .field("message", &self.message)
+            .field("info", &self.info)
+            .finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Display for PanickedTaskError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.message {
+            Some(s) => write!(f, "task panicked: {}", s)?,
+            None => write!(f, "task panicked")?,
+        }
+        if let Some(info) = &self.info {
+            write!(f, " at {}", info)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PanickedTaskError {
+
+}
+
+// how many failures `Failures` displays unless told otherwise
+const MAX_LISTED_FAILURES: usize = 10;
+
+// A view of the failures of an error, see `Error::failures`. Iterating
+// gives the failed node, "" for errors not tied to a node, along with the
+// error of its task, which can be downcast, or the error itself if there
+// is none. Displayed, it is a bulleted list of the first `max_listed`.
+#[derive(Clone, Copy, Debug)]
+pub struct Failures<'a> {
+    errors: &'a [Error],
+    max_listed: usize,
+}
+
+impl<'a> Failures<'a> {
+    pub fn iter(&self) -> impl Iterator<Item = (&'a str, &'a (dyn std::error::Error + 'static))> + 'a {
+        return self.errors.iter().map(|err| {
+            let source: &(dyn std::error::Error + 'static) = match err.task_error() {
+                Some(task_err) => task_err,
+                None => err,
+            };
+            return (err.node_name().unwrap_or(""), source);
+        });
+    }
+
+    pub fn first(&self) -> Option<(&'a str, &'a (dyn std::error::Error + 'static))> {
+        return self.iter().next();
+    }
+
+    pub fn contains_node(&self, name: &str) -> bool {
+        return self.errors.iter().any(|err| err.node_name() == Some(name));
+    }
+
+    pub fn errors(&self) -> &'a [Error] {
+        return self.errors;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.errors.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.errors.is_empty();
+    }
+
+    pub fn max_listed(mut self, max_listed: usize) -> Failures<'a> {
+        self.max_listed = max_listed;
+        return self;
+    }
+}
+
+impl<'a> IntoIterator for Failures<'a> {
+    type Item = (&'a str, &'a (dyn std::error::Error + 'static));
+    type IntoIter = Box<dyn Iterator<Item = Self::Item> + 'a>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return Box::new(self.iter());
+    }
+}
+
+impl std::fmt::Display for Failures<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for err in self.errors.iter().take(self.max_listed) {
+            write!(f, "\n  - {}", err)?;
+        }
+        if self.errors.len() > self.max_listed {
+            write!(f, "\n  …and {} more", self.errors.len() - self.max_listed)?;
+        }
+        Ok(())
+    }
+}
+
+// What a node fails with when `SchedulerBuilder::post_task_check` fails
+// after it, `err` being the error of the check.
+#[derive(Debug)]
+pub struct PostTaskCheckError {
+    pub node: String,
+    pub err: TaskError,
+}
+
+impl std::fmt::Display for PostTaskCheckError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "post-task check failed after {}: {}", self.node, self.err)
+    }
+}
+
+impl std::error::Error for PostTaskCheckError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return Some(&*self.err);
+    }
+}
+
+#[derive(Debug)]
+pub struct TimeoutError {
+    pub limit: std::time::Duration,
+    pub elapsed: std::time::Duration,
+}
+
+impl TimeoutError {
+    pub fn new(limit: std::time::Duration, elapsed: std::time::Duration) -> TimeoutError {
+        TimeoutError{
+            limit: limit,
+            elapsed: elapsed,
+        }
+    }
+}
+
+impl std::fmt::Display for TimeoutError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "exceeded {:?} (ran {:?})", self.limit, self.elapsed)
+    }
+}
+
+impl std::error::Error for TimeoutError {
+
+}
+
+// What a task added through `Graph::add_node_anyhow` fails with. The
+// original error is reached via `err.downcast_ref::<AnyhowError>()` and
+// then anyhow's own downcasting on `.0`.
+#[cfg(feature = "anyhow")]
+#[derive(Debug)]
+pub struct AnyhowError(pub anyhow::Error);
+
+#[cfg(feature = "anyhow")]
+impl std::fmt::Display for AnyhowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(feature = "anyhow")]
+impl std::error::Error for AnyhowError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        return self.0.source();
+    }
+}
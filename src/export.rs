@@ -0,0 +1,108 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::graph::{FrozenGraph, Graph};
+use crate::report::{NodeReport, NodeStatus, RunReport};
+
+impl<C> Graph<C> {
+    // A minimal GraphML document of the graph. Node ids are `n<index>`, the
+    // node name and its settings are data attributes.
+    pub fn to_graphml(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n");
+        out.push_str("  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n");
+        out.push_str("  <key id=\"inline\" for=\"node\" attr.name=\"inline\" attr.type=\"boolean\"/>\n");
+        out.push_str("  <graph id=\"G\" edgedefault=\"directed\">\n");
+        for node in self.nodes.iter() {
+            let _ = writeln!(out, "    <node id=\"n{}\">", node.index);
+            let _ = writeln!(out, "      <data key=\"name\">{}</data>", xml_escape(&node.name));
+            let _ = writeln!(out, "      <data key=\"inline\">{}</data>", node.inline);
+            out.push_str("    </node>\n");
+        }
+        for node in self.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                let _ = writeln!(out, "    <edge source=\"n{}\" target=\"n{}\"/>", node.index, child_index);
+            }
+        }
+        out.push_str("  </graph>\n");
+        out.push_str("</graphml>\n");
+        return out;
+    }
+}
+
+impl RunReport {
+    // The graph in DOT, every node filled by how it did in this run: green
+    // succeeded, red failed or panicked, yellow skipped, grey not run, which
+    // is also what nodes the report does not know get. Nodes which ran are
+    // labeled with their duration.
+    pub fn to_dot<C>(&self, frozen: &FrozenGraph<C>) -> String {
+        let reports: HashMap<&str, &NodeReport> = self.nodes().iter()
+            .map(|report| (report.name.as_str(), report))
+            .collect();
+        let mut out = String::new();
+        let _ = writeln!(out, "digraph {} {{", dot_quote(self.run_id().as_str()));
+        out.push_str("    node [style=filled];\n");
+        for node in frozen.graph.nodes.iter() {
+            let report = reports.get(&*node.name);
+            let status = report.map_or(&NodeStatus::NotRun, |report| &report.status);
+            let duration = report.and_then(|report| Some(report.finished_at?.saturating_sub(report.started_at?)));
+            let (color, mut tooltip) = match status {
+                NodeStatus::Succeeded{over_budget, ..} => ("green", format!("succeeded{}", if *over_budget { ", over budget" } else { "" })),
+                NodeStatus::Failed => ("red", String::from("failed")),
+                NodeStatus::Panicked => ("red", String::from("panicked")),
+                NodeStatus::Skipped{because_of, ..} => ("yellow", format!("skipped because of {}", because_of)),
+                NodeStatus::SkippedForDeadline{estimate, remaining} => ("yellow", format!("skipped, {:?} needed with {:?} left", estimate, remaining)),
+                NodeStatus::NotRun => ("grey", String::from("not run")),
+            };
+            let mut label = node.name.to_string();
+            if let Some(duration) = duration {
+                let _ = write!(label, "\n{:?}", duration);
+                let _ = write!(tooltip, " in {:?}", duration);
+            }
+            if report.is_some_and(|report| report.fallback_used) {
+                tooltip.push_str(", fallback used");
+            }
+            let _ = writeln!(out, "    n{} [label={}, fillcolor={}, tooltip={}];",
+                node.index, dot_quote(&label), color, dot_quote(&tooltip));
+        }
+        for node in frozen.graph.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                let _ = writeln!(out, "    n{} -> n{};", node.index, child_index);
+            }
+        }
+        out.push_str("}\n");
+        return out;
+    }
+}
+
+// a quoted DOT string, line breaks become centered line breaks
+fn dot_quote(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    return quoted;
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    return escaped;
+}
@@ -0,0 +1,15 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+// An outside condition a node waits for besides its parents, see
+// `Graph::set_gate`. `is_open` is polled by the scheduling loop, so it
+// should return right away; once open, a gate is not asked again.
+pub trait Gate: Send + Sync {
+    fn is_open(&self) -> bool;
+}
+
+// e.g. `Arc<AtomicBool>` set by another thread
+impl Gate for AtomicBool {
+    fn is_open(&self) -> bool {
+        return self.load(Ordering::Acquire);
+    }
+}
@@ -1,35 +1,192 @@
+use std::any::Any;
+use std::borrow::Cow;
 use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+use crate::context::{TaskCtx, WithCtx};
 use crate::error::Error::{self, *};
-use crate::error::TaskError;
+use crate::error::{task_error, TaskError};
+use crate::gate::Gate;
 
 pub type Task<C> = Box<dyn Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static>;
+pub type NodeResult = Result<(), TaskError>;
 
+// What a node runs. Closures get this through the blanket impl below, types
+// carrying configuration or helpers can implement it themselves.
+pub trait TaskRun<C>: Send + Sync {
+    fn run(&self, ctx: &C) -> Result<(), TaskError>;
+
+    // what the scheduler calls, tasks wanting to report progress override
+    // this instead
+    fn run_with_ctx(&self, ctx: &TaskCtx<'_, C>) -> Result<(), TaskError> {
+        return self.run(ctx.ctx());
+    }
+
+    fn name_hint(&self) -> Option<&str> {
+        return None;
+    }
+}
+
+impl<C, F> TaskRun<C> for F
+    where
+        F: Fn(&C) -> Result<(), TaskError> + Send + Sync
+{
+    fn run(&self, ctx: &C) -> Result<(), TaskError> {
+        return self(ctx);
+    }
+}
+
+pub(crate) type AfterHook<C> = Box<dyn Fn(&C, &NodeResult) + Send + Sync + 'static>;
+
+pub(crate) struct Hooks<C> {
+    pub before: Task<C>,
+    pub after: AfterHook<C>,
+}
+
+// Tasks, fallbacks and hooks are shared, so that graphs derived from this
+// one, like `Graph::reversed`, run the very same tasks.
 pub(crate) struct Node<C> {
     pub index: usize,
-    pub name: String,
-    pub task: Task<C>,
+    pub name: Arc<str>,
+    pub task: Arc<dyn TaskRun<C>>,
     pub parent_count: usize,
     pub childrens: Vec<usize>,
     pub childrens_set: HashSet<usize>,
+    pub inline: bool,
+    pub fallback: Option<Arc<dyn TaskRun<C>>>,
+    pub hooks: Option<Arc<Hooks<C>>>,
+    pub cost: Option<Duration>,
+    pub weight: u64,
+    pub class: Option<TaskClass>,
+    // added by `Graph::add_barrier`, it has no task of its own
+    pub barrier: bool,
+    pub params: HashMap<String, String>,
+    pub gate: Option<Arc<dyn Gate>>,
+    pub pinned: bool,
+    pub panic_policy: PanicPolicy,
 }
 
 impl<C> Node<C> {
-    fn new(index: usize, name: String, task: Task<C>) -> Node<C> {
+    fn new(index: usize, name: Arc<str>, task: Arc<dyn TaskRun<C>>, capacity: usize, bulk: bool) -> Node<C> {
         Node{
             index: index,
             name: name,
             task: task,
             parent_count: 0,
-            childrens: vec![],
-            childrens_set: HashSet::new(),
+            childrens: Vec::with_capacity(capacity),
+            childrens_set: if bulk { HashSet::new() } else { HashSet::with_capacity(capacity) },
+            inline: false,
+            fallback: None,
+            hooks: None,
+            cost: None,
+            weight: 0,
+            class: None,
+            barrier: false,
+            params: HashMap::new(),
+            gate: None,
+            pinned: false,
+            panic_policy: PanicPolicy::Capture,
         }
     }
 }
 
+// Extra checks done by `Graph::froze_with`; all of them are off by default,
+// which is what `Graph::froze` uses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct FrozeOptions {
+    // all nodes must be connected, ignoring edge directions
+    pub require_connected: bool,
+    // exactly one node may have no parents
+    pub require_single_root: bool,
+    // a graph without nodes is fine otherwise, running it does nothing
+    pub reject_empty: bool,
+    // Not a check: a node with a single child which has no other parent
+    // is run together with that child, one right after the other on the
+    // same worker, and so on down the chain, saving a handoff to the
+    // scheduling thread per node. Every node still has its own result in
+    // reports, events and errors. Inline, pinned, gated, classed and
+    // weighted nodes are never part of a chain, sequential runs do not
+    // fuse. A chain counts as a single node against
+    // `SchedulerBuilder::max_concurrency` while it runs. A node of a chain is not started past the deadline, or once
+    // hopeless, see `SchedulerBuilder::skip_hopeless_nodes`, but handed
+    // back to the scheduler to be checked like any other.
+    pub fuse_linear_chains: bool,
+}
+
+impl FrozeOptions {
+    pub fn fuse_linear_chains(mut self, fuse: bool) -> FrozeOptions {
+        self.fuse_linear_chains = fuse;
+        return self;
+    }
+}
+
+// Which of the limits of `Graph::max_nodes`, `Graph::max_edges` and
+// `Graph::max_fan_out` was hit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LimitKind {
+    Nodes,
+    Edges,
+    FanOut,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct GraphLimits {
+    pub max_nodes: usize,
+    pub max_edges: usize,
+    pub max_fan_out: usize,
+}
+
+// What `Graph::add_edge` does with an edge which is already there.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DuplicateEdgePolicy {
+    #[default]
+    Error,
+    Ignore,
+}
+
+// What a panic in the task of a node does, see `Graph::set_panic_policy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PanicPolicy {
+    #[default]
+    Capture,
+    Abort,
+}
+
+// What kind of work a node does, for `SchedulerBuilder::limit_class`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TaskClass {
+    Io,
+    Cpu,
+    Custom(String),
+}
+
+// larger cyclic graphs are reported without looking for feedback edges
+const FEEDBACK_EDGES_LIMIT: usize = 10_000;
+
 pub struct Graph<C> {
     pub(crate) nodes: Vec<Node<C>>,
-    pub(crate) nodes_indices: HashMap<String, usize>,
+    pub(crate) nodes_indices: HashMap<Arc<str>, usize>,
+    pub(crate) default_cost: Duration,
+    pub(crate) duplicate_edge_policy: DuplicateEdgePolicy,
+    // children reserved for every new node
+    pub(crate) childrens_hint: usize,
+    // see `Graph::begin_bulk`
+    pub(crate) bulk: bool,
+    // every edge, duplicates included while in bulk mode
+    pub(crate) n_edge: usize,
+    pub(crate) limits: GraphLimits,
+    pub(crate) name_validator: Option<NameValidator>,
+    // see `Graph::normalize_names`
+    pub(crate) normalize_names: bool,
+}
+
+pub(crate) type NameValidator = Arc<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
+impl<C> Default for Graph<C> {
+    fn default() -> Graph<C> {
+        return Graph::new();
+    }
 }
 
 impl<C> Graph<C> {
@@ -37,6 +194,84 @@ impl<C> Graph<C> {
         Graph{
             nodes: vec![],
             nodes_indices: HashMap::new(),
+            default_cost: Duration::ZERO,
+            duplicate_edge_policy: DuplicateEdgePolicy::Error,
+            childrens_hint: 0,
+            bulk: false,
+            n_edge: 0,
+            limits: GraphLimits{
+                max_nodes: usize::MAX,
+                max_edges: usize::MAX,
+                max_fan_out: usize::MAX,
+            },
+            name_validator: None,
+            normalize_names: false,
+        }
+    }
+
+    // `edges_hint` is spread evenly over the nodes, each of them reserves
+    // room for that many children
+    pub fn with_capacity(nodes: usize, edges_hint: usize) -> Graph<C> {
+        let mut graph = Graph::new();
+        graph.nodes.reserve(nodes);
+        graph.nodes_indices.reserve(nodes);
+        if nodes > 0 {
+            graph.childrens_hint = edges_hint.div_ceil(nodes);
+        }
+        return graph;
+    }
+
+    // room for `additional` more children of `name`
+    pub fn reserve_edges(&mut self, name: &str, additional: usize) -> Result<(), Error> {
+        let bulk = self.bulk;
+        let node = self.node_mut(name)?;
+        node.childrens.reserve(additional);
+        if !bulk {
+            node.childrens_set.reserve(additional);
+        }
+        return Ok(());
+    }
+
+    // Until `end_bulk`, edges are only appended: nothing checks whether an
+    // edge is there already, and duplicates are dropped by `end_bulk` or
+    // `froze`, whichever comes first, instead of being an error. Meant for
+    // building very large graphs in one go.
+    pub fn begin_bulk(&mut self) {
+        self.bulk = true;
+    }
+
+    pub fn end_bulk(&mut self) {
+        if !self.bulk {
+            return;
+        }
+        self.bulk = false;
+        self.dedup_childrens();
+        for node in self.nodes.iter_mut() {
+            node.childrens_set = node.childrens.iter().copied().collect();
+        }
+    }
+
+    // keeps the first of equal edges per parent, so children stay in the
+    // order they were added
+    fn dedup_childrens(&mut self) {
+        let n_node = self.nodes.len();
+        let mut seen_by = vec![usize::MAX; n_node];
+        let mut parent_counts = vec![0usize; n_node];
+        let mut n_edge = 0;
+        for node in self.nodes.iter_mut() {
+            node.childrens.retain(|child_index| {
+                if seen_by[*child_index] == node.index {
+                    return false;
+                }
+                seen_by[*child_index] = node.index;
+                parent_counts[*child_index] += 1;
+                return true;
+            });
+            n_edge += node.childrens.len();
+        }
+        self.n_edge = n_edge;
+        for (node, parent_count) in self.nodes.iter_mut().zip(parent_counts) {
+            node.parent_count = parent_count;
         }
     }
 
@@ -44,46 +279,406 @@ impl<C> Graph<C> {
         where
             F: Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static
     {
-        if name == "" {
-            return Err(InvalidNode{name: name.to_string()});
+        return self.add_task(name, task);
+    }
+
+    pub fn add_task<T>(&mut self, name: &str, task: T) -> Result<(), Error>
+        where
+            T: TaskRun<C> + 'static
+    {
+        self.check_name(name)?;
+        let name = self.normalized(name);
+        if self.nodes_indices.contains_key(&*name) {
+            return Err(DuplicatedNode{name: name.into_owned()});
         }
-        if self.nodes_indices.contains_key(name) {
-            return Err(DuplicatedNode{name: name.to_string()});
+        if self.nodes.len() == self.limits.max_nodes {
+            return Err(LimitExceeded{kind: LimitKind::Nodes, limit: self.limits.max_nodes});
         }
         let index = self.nodes.len();
-        let node = Node::new(index, name.to_string(), Box::new(task));
+        let name: Arc<str> = Arc::from(name);
+        let node = Node::new(index, name.clone(), Arc::new(task), self.childrens_hint, self.bulk);
         self.nodes.push(node);
-        self.nodes_indices.insert(name.to_string(), index);
+        self.nodes_indices.insert(name, index);
         return Ok(());
     }
 
+    // Names, which end up in thread names, DOT ids, checkpoint files and
+    // so on, may neither be empty nor contain control characters. On top of
+    // that, `validator` is asked about every name from now on, the `Err` it
+    // returns becoming the reason of `InvalidNode`.
+    pub fn set_name_validator<F>(&mut self, validator: F)
+        where
+            F: Fn(&str) -> Result<(), String> + Send + Sync + 'static
+    {
+        self.name_validator = Some(Arc::new(validator));
+    }
+
+    fn check_name(&self, name: &str) -> Result<(), Error> {
+        let invalid = |reason: String| InvalidNode{name: name.to_string(), reason: reason};
+        if name.is_empty() {
+            return Err(invalid(String::from("empty name")));
+        }
+        if let Some(c) = name.chars().find(|c| c.is_control()) {
+            return Err(invalid(format!("contains the control character {:?}", c)));
+        }
+        if let Some(validator) = &self.name_validator {
+            validator(name).map_err(invalid)?;
+        }
+        return Ok(());
+    }
+
+    // Once set, names are put into Unicode normalization form C wherever
+    // they are given, so that "café" refers to the same node whether its
+    // "é" is one code point or "e" followed by a combining accent. Set it
+    // before adding nodes, those already there are left as they are.
+    #[cfg(feature = "nfc")]
+    pub fn normalize_names(&mut self, normalize: bool) {
+        self.normalize_names = normalize;
+    }
+
+    pub(crate) fn normalized<'n>(&self, name: &'n str) -> Cow<'n, str> {
+        #[cfg(feature = "nfc")]
+        if self.normalize_names {
+            use unicode_normalization::{is_nfc_quick, IsNormalized, UnicodeNormalization};
+            if is_nfc_quick(name.chars()) != IsNormalized::Yes {
+                return Cow::Owned(name.nfc().collect());
+            }
+        }
+        return Cow::Borrowed(name);
+    }
+
+    // For tasks sending heartbeats or reporting progress through `TaskCtx`.
+    pub fn add_node_with_ctx<F>(&mut self, name: &str, task: F) -> Result<(), Error>
+        where
+            F: Fn(&TaskCtx<'_, C>) -> Result<(), TaskError> + Send + Sync + 'static
+    {
+        return self.add_task(name, WithCtx(task));
+    }
+
+    // For tasks using the state `Scheduler::run_with_scratch` makes afresh
+    // for every run. Run any other way, these fail.
+    pub fn add_node_scratch<S, F>(&mut self, name: &str, task: F) -> Result<(), Error>
+        where
+            S: Send + Sync + 'static,
+            F: Fn(&C, &S) -> Result<(), TaskError> + Send + Sync + 'static
+    {
+        return self.add_node_with_ctx(name, move |ctx: &TaskCtx<'_, C>| {
+            return match ctx.run_scratch::<S>() {
+                Some(run_scratch) => task(ctx.ctx(), run_scratch),
+                None => Err(task_error(format!(
+                    "{} needs a run scratch of type {}, see Scheduler::run_with_scratch",
+                    ctx.name(), std::any::type_name::<S>(),
+                ))),
+            };
+        });
+    }
+
+    // The value returned by `task` becomes the output of the node, see
+    // `TaskCtx::set_output`.
+    pub fn add_output_node<T, F>(&mut self, name: &str, task: F) -> Result<(), Error>
+        where
+            T: Any + Send,
+            F: Fn(&C) -> Result<T, TaskError> + Send + Sync + 'static
+    {
+        return self.add_node_with_ctx(name, move |ctx: &TaskCtx<'_, C>| {
+            ctx.set_output(task(ctx.ctx())?);
+            return Ok(());
+        });
+    }
+
+    // Replaces the task if `name` is there already, keeping its edges and
+    // settings, and tells whether it did.
+    pub fn add_or_replace_node<F>(&mut self, name: &str, task: F) -> Result<bool, Error>
+        where
+            F: Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static
+    {
+        if self.nodes_indices.contains_key(&*self.normalized(name)) {
+            self.replace_task(name, task)?;
+            return Ok(true);
+        }
+        self.add_node(name, task)?;
+        return Ok(false);
+    }
+
+    // A node doing nothing which runs after all of `upstream` and before all
+    // of `downstream`, that is n + m edges instead of n * m. It runs inline,
+    // and reports say it is a barrier.
+    pub fn add_barrier(&mut self, name: &str, upstream: &[&str], downstream: &[&str]) -> Result<(), Error> {
+        for other in upstream.iter().chain(downstream.iter()) {
+            self.index_of(other)?;
+        }
+        self.add_node(name, |_: &C| -> Result<(), TaskError> {
+            return Ok(());
+        })?;
+        let node = self.node_mut(name)?;
+        node.inline = true;
+        node.barrier = true;
+        for from_node in upstream {
+            self.add_edge_idempotent(from_node, name)?;
+        }
+        for to_node in downstream {
+            self.add_edge_idempotent(name, to_node)?;
+        }
+        return Ok(());
+    }
+
+    pub fn add_infallible_node<F>(&mut self, name: &str, task: F) -> Result<(), Error>
+        where
+            F: Fn(&C) + Send + Sync + 'static
+    {
+        return self.add_node(name, move |ctx: &C| -> Result<(), TaskError> {
+            task(ctx);
+            return Ok(());
+        });
+    }
+
+    // For tasks consuming what they captured. Such a node can only run once,
+    // every later run of the graph fails at this node.
+    pub fn add_once_node<F>(&mut self, name: &str, task: F) -> Result<(), Error>
+        where
+            F: FnOnce(&C) -> Result<(), TaskError> + Send + 'static
+    {
+        let task = Mutex::new(Some(task));
+        return self.add_node(name, move |ctx: &C| -> Result<(), TaskError> {
+            let task = task.lock().unwrap_or_else(|err| err.into_inner()).take();
+            return match task {
+                Some(task) => task(ctx),
+                None => Err(task_error(String::from("once task already ran"))),
+            };
+        });
+    }
+
+    // Errors are wrapped into `AnyhowError`, keeping the whole chain.
+    #[cfg(feature = "anyhow")]
+    pub fn add_node_anyhow<F>(&mut self, name: &str, task: F) -> Result<(), Error>
+        where
+            F: Fn(&C) -> anyhow::Result<()> + Send + Sync + 'static
+    {
+        return self.add_node(name, move |ctx: &C| -> Result<(), TaskError> {
+            return task(ctx).map_err(|err| -> TaskError {
+                return Box::new(crate::error::AnyhowError(err));
+            });
+        });
+    }
+
+    pub fn set_duplicate_edge_policy(&mut self, policy: DuplicateEdgePolicy) {
+        self.duplicate_edge_policy = policy;
+    }
+
+    // Guards against graphs growing out of hand, e.g. nodes added by
+    // plugins: adding a node or an edge beyond them fails right there with
+    // `LimitExceeded`. Nothing is limited by default; limits below what
+    // the graph already has only stop it from growing further.
+    pub fn max_nodes(&mut self, n: usize) {
+        self.limits.max_nodes = n;
+    }
+
+    pub fn max_edges(&mut self, n: usize) {
+        self.limits.max_edges = n;
+    }
+
+    // most children a single node may have; in bulk mode duplicates count
+    // until `end_bulk` drops them
+    pub fn max_fan_out(&mut self, n: usize) {
+        self.limits.max_fan_out = n;
+    }
+
     pub fn add_edge(&mut self, from_node: &str, to_node: &str) -> Result<(), Error> {
-        if from_node == "" || to_node == "" || from_node == to_node {
+        let added = self.add_edge_idempotent(from_node, to_node)?;
+        if added || self.duplicate_edge_policy == DuplicateEdgePolicy::Ignore {
+            return Ok(());
+        }
+        return Err(DuplicatedEdge{
+            from_node: from_node.to_string(),
+            to_node: to_node.to_string(),
+        });
+    }
+
+    // Same as `add_edge`, except that an edge which is already there is
+    // fine whatever the policy, false is returned then.
+    pub fn add_edge_idempotent(&mut self, from_node: &str, to_node: &str) -> Result<bool, Error> {
+        if from_node.is_empty() || to_node.is_empty() || from_node == to_node {
             return Err(InvalidEdge{
                 from_node: from_node.to_string(),
                 to_node: to_node.to_string(),
             });
         }
-        let parent_index = *match self.nodes_indices.get(from_node) {
-            Some(v) => v,
-            None => return Err(NodeNotFound{name: from_node.to_string()}),
-        };
-        let child_index = *match self.nodes_indices.get(to_node) {
-            Some(v) => v,
-            None => return Err(NodeNotFound{name: to_node.to_string()}),
+        let parent_index = self.index_of(from_node)?;
+        let child_index = self.index_of(to_node)?;
+        if !self.bulk && self.nodes[parent_index].childrens_set.contains(&child_index) {
+            return Ok(false);
+        }
+        if self.nodes[parent_index].childrens.len() >= self.limits.max_fan_out {
+            return Err(LimitExceeded{kind: LimitKind::FanOut, limit: self.limits.max_fan_out});
+        }
+        if self.n_edge >= self.limits.max_edges {
+            return Err(LimitExceeded{kind: LimitKind::Edges, limit: self.limits.max_edges});
+        }
+        if self.bulk {
+            self.nodes[parent_index].childrens.push(child_index);
+            self.nodes[child_index].parent_count += 1;
+            self.n_edge += 1;
+            return Ok(true);
+        }
+        let (parent, child) = Self::pair_mut(&mut self.nodes, parent_index, child_index);
+        Self::add_child(parent, child)?;
+        self.n_edge += 1;
+        return Ok(true);
+    }
+
+    // Edges and settings stay with the node, only the name changes.
+    pub fn rename_node(&mut self, old_name: &str, new_name: &str) -> Result<(), Error> {
+        self.check_name(new_name)?;
+        let index = self.index_of(old_name)?;
+        let new_name = self.normalized(new_name);
+        if *self.nodes[index].name == *new_name {
+            return Ok(());
+        }
+        if self.nodes_indices.contains_key(&*new_name) {
+            return Err(DuplicatedNode{name: new_name.into_owned()});
+        }
+        let name: Arc<str> = Arc::from(new_name);
+        self.nodes_indices.remove(&self.nodes[index].name);
+        self.nodes_indices.insert(name.clone(), index);
+        self.nodes[index].name = name;
+        return Ok(());
+    }
+
+    pub fn replace_task<T>(&mut self, name: &str, task: T) -> Result<(), Error>
+        where
+            T: TaskRun<C> + 'static
+    {
+        self.node_mut(name)?.task = Arc::new(task);
+        return Ok(());
+    }
+
+    // Inline nodes are executed directly on the thread driving the run
+    // instead of on a thread of their own, which only pays off for tasks
+    // that are much cheaper than spawning a thread.
+    pub fn set_inline(&mut self, name: &str, inline: bool) -> Result<(), Error> {
+        self.node_mut(name)?.inline = inline;
+        return Ok(());
+    }
+
+    // Pinned nodes are executed on the thread which called `run`, `run_with`,
+    // `run_arc` and the like, for tasks touching thread-local state, while
+    // the workers go on with the other nodes; with `spawn_run` that is the
+    // thread driving the run. They run one at a time, and nothing else is
+    // dispatched while one runs, so a pinned task waiting for the caller, or
+    // for another node to finish, waits forever.
+    pub fn set_pinned_to_caller(&mut self, name: &str, pinned: bool) -> Result<(), Error> {
+        self.node_mut(name)?.pinned = pinned;
+        return Ok(());
+    }
+
+    // A panic is captured and fails the run like an error does by default.
+    // With `PanicPolicy::Abort` it is raised again on the thread driving the
+    // run once the node is reported, so that `run` and the like panic with
+    // the very same payload, after the workers still running have finished.
+    pub fn set_panic_policy(&mut self, name: &str, policy: PanicPolicy) -> Result<(), Error> {
+        self.node_mut(name)?.panic_policy = policy;
+        return Ok(());
+    }
+
+    // When the task of `name` fails or panics, `fallback` is run with the
+    // same context, and the node only fails if the fallback fails as well.
+    pub fn set_fallback<F>(&mut self, name: &str, fallback: F) -> Result<(), Error>
+        where
+            F: Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static
+    {
+        self.node_mut(name)?.fallback = Some(Arc::new(fallback));
+        return Ok(());
+    }
+
+    // `before` runs right before the task of `name` on the same thread, and
+    // if it fails the node fails without running the task. Once `before`
+    // succeeded, `after` always runs and sees the result of the task; a
+    // panic is passed as an error describing it.
+    pub fn set_hooks<B, A>(&mut self, name: &str, before: B, after: A) -> Result<(), Error>
+        where
+            B: Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static,
+            A: Fn(&C, &NodeResult) + Send + Sync + 'static
+    {
+        self.node_mut(name)?.hooks = Some(Arc::new(Hooks{
+            before: Box::new(before),
+            after: Box::new(after),
+        }));
+        return Ok(());
+    }
+
+    // How long the task of `name` is expected to take; only used for
+    // estimates like `FrozenGraph::critical_path`, never by the scheduler.
+    pub fn set_cost(&mut self, name: &str, cost: Duration) -> Result<(), Error> {
+        self.node_mut(name)?.cost = Some(cost);
+        return Ok(());
+    }
+
+    // What running `name` takes out of `SchedulerBuilder::max_total_weight`,
+    // in whatever unit the budget is given in, e.g. MB of memory. Zero
+    // unless set.
+    pub fn set_weight(&mut self, name: &str, weight: u64) -> Result<(), Error> {
+        self.node_mut(name)?.weight = weight;
+        return Ok(());
+    }
+
+    // What `TaskCtx::param` gives for `key` unless the run sets it, see
+    // `RunOptions::node_params`.
+    pub fn set_param(&mut self, name: &str, key: &str, value: &str) -> Result<(), Error> {
+        self.node_mut(name)?.params.insert(key.to_string(), value.to_string());
+        return Ok(());
+    }
+
+    // The node is only started once `gate` is open as well as all its
+    // parents have finished; other nodes are started meanwhile. See
+    // `SchedulerBuilder::gate_poll_interval` and `gate_timeout`.
+    pub fn set_gate(&mut self, name: &str, gate: Arc<dyn Gate>) -> Result<(), Error> {
+        self.node_mut(name)?.gate = Some(gate);
+        return Ok(());
+    }
+
+    // nodes without a class are never limited by class
+    pub fn set_class(&mut self, name: &str, class: TaskClass) -> Result<(), Error> {
+        self.node_mut(name)?.class = Some(class);
+        return Ok(());
+    }
+
+    // cost of the nodes without one of their own, zero unless set
+    pub fn set_default_cost(&mut self, cost: Duration) {
+        self.default_cost = cost;
+    }
+
+    pub(crate) fn cost_of(&self, index: usize) -> Duration {
+        return self.nodes[index].cost.unwrap_or(self.default_cost);
+    }
+
+    pub(crate) fn node_mut(&mut self, name: &str) -> Result<&mut Node<C>, Error> {
+        let index = self.index_of(name)?;
+        return Ok(&mut self.nodes[index]);
+    }
+
+    pub(crate) fn index_of(&self, name: &str) -> Result<usize, Error> {
+        return match self.nodes_indices.get(&*self.normalized(name)) {
+            Some(index) => Ok(*index),
+            None => Err(NodeNotFound{name: name.to_string()}),
         };
-        assert_ne!(parent_index, child_index);
-        let ptr = self.nodes.as_mut_ptr();
-        let parent = unsafe { ptr.add(parent_index).as_mut().unwrap() };
-        let child = unsafe { ptr.add(child_index).as_mut().unwrap() };
-        return Self::add_child(parent, child);
+    }
+
+    fn pair_mut(nodes: &mut [Node<C>], a: usize, b: usize) -> (&mut Node<C>, &mut Node<C>) {
+        assert_ne!(a, b);
+        if a < b {
+            let (left, right) = nodes.split_at_mut(b);
+            return (&mut left[a], &mut right[0]);
+        } else {
+            let (left, right) = nodes.split_at_mut(a);
+            return (&mut right[0], &mut left[b]);
+        }
     }
 
     fn add_child(parent: &mut Node<C>, child: &mut Node<C>) -> Result<(), Error> {
         if !parent.childrens_set.insert(child.index) {
             return Err(DuplicatedEdge{
-                from_node: parent.name.clone(),
-                to_node: child.name.clone(),
+                from_node: parent.name.to_string(),
+                to_node: child.name.to_string(),
             })
         }
         child.parent_count += 1;
@@ -91,13 +686,116 @@ impl<C> Graph<C> {
         return Ok(());
     }
 
-    pub fn froze(mut self) -> Result<FrozenGraph<C>, Error> {
-        let n_node = self.nodes.len();
-        let root_task = |_: &C| -> Result<(), TaskError> {
-            panic!("in ROOT node");
+    pub(crate) fn remove_child(&mut self, parent_index: usize, child_index: usize) {
+        let parent = &mut self.nodes[parent_index];
+        if self.bulk || parent.childrens_set.remove(&child_index) {
+            let n_child = parent.childrens.len();
+            parent.childrens.retain(|index| *index != child_index);
+            let n_removed = n_child - parent.childrens.len();
+            self.nodes[child_index].parent_count -= n_removed;
+            self.n_edge -= n_removed;
+        }
+    }
+
+    // The same nodes, names and settings, sharing their tasks, with every
+    // edge pointing the other way: what ran last runs first. The children
+    // of a node are its former parents, in the order they were added.
+    pub fn reversed(&self) -> Graph<C> {
+        let mut graph = self.copy_nodes(&(0..self.nodes.len()).collect::<Vec<_>>());
+        for node in self.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                graph.nodes[*child_index].childrens.push(node.index);
+            }
+        }
+        graph.n_edge = self.n_edge;
+        for node in graph.nodes.iter_mut() {
+            node.parent_count = self.nodes[node.index].childrens.len();
+            if !graph.bulk {
+                node.childrens_set = node.childrens.iter().copied().collect();
+            }
+        }
+        return graph;
+    }
+
+    // The nodes passing `keep` and the edges between them, sharing tasks
+    // like `reversed`. Nodes keep their order; those whose parents were all
+    // left out become roots.
+    pub fn subgraph<F>(&self, keep: F) -> Graph<C>
+        where
+            F: Fn(&str) -> bool
+    {
+        let kept: Vec<usize> = (0..self.nodes.len()).filter(|index| keep(&self.nodes[*index].name)).collect();
+        let mut graph = self.copy_nodes(&kept);
+        let mut new_indices: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        for (new_index, index) in kept.iter().enumerate() {
+            new_indices[*index] = Some(new_index);
+        }
+        for (new_index, index) in kept.iter().enumerate() {
+            for child_index in self.nodes[*index].childrens.iter() {
+                if let Some(new_child_index) = new_indices[*child_index] {
+                    graph.nodes[new_index].childrens.push(new_child_index);
+                    graph.nodes[new_child_index].parent_count += 1;
+                    graph.n_edge += 1;
+                }
+            }
+        }
+        if !graph.bulk {
+            for node in graph.nodes.iter_mut() {
+                node.childrens_set = node.childrens.iter().copied().collect();
+            }
+        }
+        return graph;
+    }
+
+    // the nodes at `indices`, renumbered in that order, without any edges
+    fn copy_nodes(&self, indices: &[usize]) -> Graph<C> {
+        let mut nodes: Vec<Node<C>> = Vec::with_capacity(indices.len());
+        for index in indices.iter() {
+            let node = &self.nodes[*index];
+            nodes.push(Node{
+                index: nodes.len(),
+                name: node.name.clone(),
+                task: node.task.clone(),
+                parent_count: 0,
+                childrens: Vec::with_capacity(node.childrens.len()),
+                childrens_set: HashSet::new(),
+                inline: node.inline,
+                fallback: node.fallback.clone(),
+                hooks: node.hooks.clone(),
+                cost: node.cost,
+                weight: node.weight,
+                class: node.class.clone(),
+                barrier: node.barrier,
+                params: node.params.clone(),
+                gate: node.gate.clone(),
+                pinned: node.pinned,
+                panic_policy: node.panic_policy,
+            });
+        }
+        let nodes_indices = nodes.iter().map(|node| (node.name.clone(), node.index)).collect();
+        return Graph{
+            nodes: nodes,
+            nodes_indices: nodes_indices,
+            default_cost: self.default_cost,
+            duplicate_edge_policy: self.duplicate_edge_policy,
+            childrens_hint: self.childrens_hint,
+            bulk: self.bulk,
+            n_edge: 0,
+            limits: self.limits,
+            name_validator: self.name_validator.clone(),
+            normalize_names: self.normalize_names,
         };
-        let mut root = Node::new(n_node, "$ROOT".to_string(), Box::new(root_task));
+    }
+
+    pub fn froze(self) -> Result<FrozenGraph<C>, Error> {
+        return self.froze_with(FrozeOptions::default());
+    }
 
+    pub fn froze_with(mut self, options: FrozeOptions) -> Result<FrozenGraph<C>, Error> {
+        if self.bulk {
+            self.dedup_childrens();
+        }
+        let n_node = self.nodes.len();
         let mut in_degrees: Vec<usize> = self.nodes.iter()
             .map(|node| node.parent_count).collect();
         let mut queue: Vec<usize> = Vec::with_capacity(n_node);
@@ -106,10 +804,9 @@ impl<C> Graph<C> {
         for (index, in_degree) in in_degrees.iter().enumerate() {
             if *in_degree == 0 {
                 queue.push(index);
-                let child = &mut self.nodes[index];
-                Self::add_child(&mut root, child).unwrap();
             }
         }
+        let roots = queue.clone();
         while queue_i < queue.len() {
             let cursor = &self.nodes[queue[queue_i]];
             queue_i += 1;
@@ -131,24 +828,158 @@ impl<C> Graph<C> {
                     ring.push_str(&self.nodes[index].name);
                 }
             }
-            ring.push_str("]");
-            return Err(CyclicGraphFound{ring: ring});
+            ring.push(']');
+            let feedback_edges = match self.edge_count() < FEEDBACK_EDGES_LIMIT {
+                true => self.feedback_edges(),
+                false => vec![],
+            };
+            return Err(CyclicGraphFound{
+                ring: ring,
+                feedback_edges: feedback_edges,
+            });
         }
 
-        return Ok(FrozenGraph::new(self, root));
+        if options.reject_empty && n_node == 0 {
+            return Err(EmptyGraph);
+        }
+        if options.require_connected {
+            let components = self.weakly_connected_components();
+            if components.len() > 1 {
+                return Err(DisconnectedGraph{components: components});
+            }
+        }
+        if options.require_single_root && roots.len() > 1 {
+            let roots = roots.iter().map(|index| self.nodes[*index].name.to_string()).collect();
+            return Err(MultipleRoots{roots: roots});
+        }
+
+        let mut frozen = FrozenGraph::new(self, roots);
+        if options.fuse_linear_chains {
+            frozen.fused_next = frozen.linear_chains();
+        }
+        return Ok(frozen);
     }
 }
 
+// Cheap, tasks are shared and not copied; the copy is changed independently.
+impl<C> Clone for Graph<C> {
+    fn clone(&self) -> Graph<C> {
+        let mut graph = self.copy_nodes(&(0..self.nodes.len()).collect::<Vec<_>>());
+        for (node, other) in graph.nodes.iter_mut().zip(self.nodes.iter()) {
+            node.parent_count = other.parent_count;
+            node.childrens.clone_from(&other.childrens);
+            node.childrens_set.clone_from(&other.childrens_set);
+        }
+        graph.n_edge = self.n_edge;
+        return graph;
+    }
+}
+
+// Besides the graph itself, everything a run needs that never changes is
+// computed once here: children are flattened into one CSR-style array, so
+// the children of node `i` are `childrens[offsets[i]..offsets[i + 1]]`.
 pub struct FrozenGraph<C> {
     pub(crate) graph: Graph<C>,
-    pub(crate) root: Node<C>,
+    // the nodes without parents, in insertion order
+    pub(crate) roots: Vec<usize>,
+    pub(crate) childrens: Vec<u32>,
+    pub(crate) offsets: Vec<u32>,
+    pub(crate) in_degrees: Vec<u32>,
+    // by index, the child run right after the node on the same worker;
+    // empty unless `FrozeOptions::fuse_linear_chains`
+    pub(crate) fused_next: Vec<Option<u32>>,
+}
+
+impl<C> Clone for FrozenGraph<C> {
+    fn clone(&self) -> FrozenGraph<C> {
+        FrozenGraph{
+            graph: self.graph.clone(),
+            roots: self.roots.clone(),
+            childrens: self.childrens.clone(),
+            offsets: self.offsets.clone(),
+            in_degrees: self.in_degrees.clone(),
+            fused_next: self.fused_next.clone(),
+        }
+    }
 }
 
 impl<C> FrozenGraph<C> {
-    fn new(graph: Graph<C>, root: Node<C>) -> FrozenGraph<C> {
+    fn new(graph: Graph<C>, roots: Vec<usize>) -> FrozenGraph<C> {
+        let n_edge: usize = graph.nodes.iter().map(|node| node.childrens.len()).sum();
+        let mut childrens: Vec<u32> = Vec::with_capacity(n_edge);
+        let mut offsets: Vec<u32> = Vec::with_capacity(graph.nodes.len() + 1);
+        offsets.push(0);
+        for node in graph.nodes.iter() {
+            childrens.extend(node.childrens.iter().map(|index| *index as u32));
+            offsets.push(childrens.len() as u32);
+        }
+        let in_degrees = graph.nodes.iter().map(|node| node.parent_count as u32).collect();
         FrozenGraph{
             graph: graph,
-            root: root,
+            roots: roots,
+            childrens: childrens,
+            offsets: offsets,
+            in_degrees: in_degrees,
+            fused_next: vec![],
         }
     }
+
+    fn linear_chains(&self) -> Vec<Option<u32>> {
+        let fusable = |node: &Node<C>| {
+            return !node.inline && !node.pinned && node.class.is_none() && node.gate.is_none() && node.weight == 0;
+        };
+        return self.graph.nodes.iter().map(|node| {
+            let [child_index] = node.childrens[..] else {
+                return None;
+            };
+            let child = &self.graph.nodes[child_index];
+            return (fusable(node) && fusable(child) && child.parent_count == 1).then_some(child_index as u32);
+        }).collect();
+    }
+
+    pub(crate) fn fused_next(&self, index: usize) -> Option<usize> {
+        return self.fused_next.get(index).copied().flatten().map(|index| index as usize);
+    }
+
+    pub(crate) fn childrens_of(&self, index: usize) -> &[u32] {
+        let begin = self.offsets[index] as usize;
+        let end = self.offsets[index + 1] as usize;
+        return &self.childrens[begin..end];
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        return self.graph.index_of(name).is_ok();
+    }
+
+    // Indices are the insertion order of the nodes, they never change for
+    // a frozen graph; runtime errors carry the index of their node.
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        return self.graph.index_of(name).ok();
+    }
+
+    pub fn name_of(&self, index: usize) -> Option<&str> {
+        return self.graph.nodes.get(index).map(|node| &*node.name);
+    }
+
+    // in insertion order, as are all the names below
+    pub fn node_names(&self) -> Vec<&str> {
+        return self.graph.nodes.iter().map(|node| &*node.name).collect();
+    }
+
+    pub fn roots(&self) -> Vec<&str> {
+        return self.roots.iter().map(|index| &*self.graph.nodes[*index].name).collect();
+    }
+
+    pub fn children(&self, name: &str) -> Result<Vec<&str>, Error> {
+        let index = self.graph.index_of(name)?;
+        return Ok(self.childrens_of(index).iter().map(|child_index| &*self.graph.nodes[*child_index as usize].name).collect());
+    }
+
+    pub fn parents(&self, name: &str) -> Result<Vec<&str>, Error> {
+        let index = self.graph.index_of(name)? as u32;
+        return Ok((0..self.graph.nodes.len())
+            .filter(|parent_index| self.childrens_of(*parent_index).contains(&index))
+            .map(|parent_index| &*self.graph.nodes[parent_index].name)
+            .collect());
+    }
 }
@@ -0,0 +1,60 @@
+use crate::error::{Error, TaskError};
+use crate::graph::{FrozeOptions, FrozenGraph, Graph, TaskRun};
+
+// Only what it takes to describe the nodes and the edges between them,
+// the result of `build` can only be queried and run: code holding on to
+// the builder after `build` does not compile, and forgetting to build
+// leaves nothing to hand to a scheduler. Everything else a node may be
+// given, costs, classes, fallbacks and the like, needs a `Graph`, which
+// `GraphBuilder::from` turns into a builder as well.
+pub struct GraphBuilder<C> {
+    graph: Graph<C>,
+}
+
+impl<C> Default for GraphBuilder<C> {
+    fn default() -> GraphBuilder<C> {
+        return GraphBuilder::new();
+    }
+}
+
+impl<C> From<Graph<C>> for GraphBuilder<C> {
+    fn from(graph: Graph<C>) -> GraphBuilder<C> {
+        return GraphBuilder{
+            graph: graph,
+        };
+    }
+}
+
+impl<C> GraphBuilder<C> {
+    pub fn new() -> GraphBuilder<C> {
+        return GraphBuilder{
+            graph: Graph::new(),
+        };
+    }
+
+    pub fn add_node<F>(&mut self, name: &str, task: F) -> Result<(), Error>
+        where
+            F: Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static
+    {
+        return self.graph.add_node(name, task);
+    }
+
+    pub fn add_task<T>(&mut self, name: &str, task: T) -> Result<(), Error>
+        where
+            T: TaskRun<C> + 'static
+    {
+        return self.graph.add_task(name, task);
+    }
+
+    pub fn add_edge(&mut self, from_node: &str, to_node: &str) -> Result<(), Error> {
+        return self.graph.add_edge(from_node, to_node);
+    }
+
+    pub fn build(self) -> Result<FrozenGraph<C>, Error> {
+        return self.graph.froze();
+    }
+
+    pub fn build_with(self, options: FrozeOptions) -> Result<FrozenGraph<C>, Error> {
+        return self.graph.froze_with(options);
+    }
+}
@@ -0,0 +1,66 @@
+use crate::error::{Error, TaskError};
+use crate::graph::{Graph, TaskRun};
+
+// Builds a graph without failing on the first mistake: every problem is
+// kept, and `finish` reports all of them at once as `BuildFailed`. Edges
+// are only added by `finish`, after all nodes, so they may name nodes
+// added later on.
+pub struct LenientGraph<C> {
+    graph: Graph<C>,
+    edges: Vec<(String, String)>,
+    diagnostics: Vec<Error>,
+}
+
+impl<C> Graph<C> {
+    pub fn lenient() -> LenientGraph<C> {
+        return LenientGraph{
+            graph: Graph::new(),
+            edges: vec![],
+            diagnostics: vec![],
+        };
+    }
+}
+
+impl<C> LenientGraph<C> {
+    pub fn add_node<F>(&mut self, name: &str, task: F)
+        where
+            F: Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static
+    {
+        self.add_task(name, task);
+    }
+
+    pub fn add_task<T>(&mut self, name: &str, task: T)
+        where
+            T: TaskRun<C> + 'static
+    {
+        if let Err(err) = self.graph.add_task(name, task) {
+            self.diagnostics.push(err);
+        }
+    }
+
+    pub fn add_edge(&mut self, from_node: &str, to_node: &str) {
+        self.edges.push((from_node.to_string(), to_node.to_string()));
+    }
+
+    // In the order the problems were made, those of edges last; for an edge
+    // whose nodes are both missing, both are reported.
+    pub fn finish(mut self) -> Result<Graph<C>, Error> {
+        for (from_node, to_node) in self.edges.iter() {
+            let missing: Vec<&String> = [from_node, to_node].into_iter()
+                .filter(|name| !name.is_empty() && self.graph.index_of(name).is_err())
+                .collect();
+            // self edges are reported as such, whether the node exists or not
+            if !missing.is_empty() && from_node != to_node {
+                self.diagnostics.extend(missing.into_iter().map(|name| Error::NodeNotFound{name: name.to_string()}));
+                continue;
+            }
+            if let Err(err) = self.graph.add_edge(from_node, to_node) {
+                self.diagnostics.push(err);
+            }
+        }
+        if !self.diagnostics.is_empty() {
+            return Err(Error::BuildFailed{diagnostics: self.diagnostics});
+        }
+        return Ok(self.graph);
+    }
+}
@@ -1,7 +1,63 @@
+#![allow(clippy::needless_return, clippy::redundant_field_names)]
+
+mod builder;
+mod checkpoint;
+mod clock;
+mod cost_model;
+mod context;
+mod dsl;
 mod error;
+mod export;
+mod gate;
 mod graph;
+mod graph_builder;
+mod lenient;
+mod logging;
+mod metrics;
+mod namespace;
+mod observer;
+mod panics;
+#[cfg(feature = "petgraph")]
+mod petgraph_interop;
+mod report;
+mod run_id;
 mod scheduler;
+mod scoped;
+mod scratch;
+#[cfg(feature = "signals")]
+mod signals;
+mod spawner;
+mod time;
+mod topology;
+pub mod task;
+#[cfg(feature = "testing")]
+pub mod testing;
 
-pub use error::{Error, TaskError, PanicError};
-pub use graph::{Task, Graph, FrozenGraph};
-pub use scheduler::Scheduler;
+pub use builder::{SchedulerBuilder, RunOptions};
+pub use checkpoint::{Checkpoint, FileCheckpoint};
+pub use clock::{Clock, SystemClock, ManualClock};
+pub use context::TaskCtx;
+pub use cost_model::CostModel;
+pub use error::{Error, Failures, TaskError, PanicError, PanickedTaskError, PostTaskCheckError, SpawnError, TimeoutError};
+pub use gate::Gate;
+pub use graph::{Task, TaskRun, NodeResult, Graph, TaskClass, DuplicateEdgePolicy, LimitKind, PanicPolicy, FrozeOptions, FrozenGraph};
+pub use graph_builder::GraphBuilder;
+pub use lenient::LenientGraph;
+pub use metrics::SchedulerMetrics;
+pub use namespace::Namespace;
+pub use observer::{Event, Observer};
+pub use panics::PanicInfoCaptured;
+pub use report::{NodeStatus, NodeReport, RunReport, RunSummary};
+pub use run_id::RunId;
+pub use scheduler::{FailurePolicy, DispatchStrategy, Scheduler};
+#[cfg(not(target_arch = "wasm32"))]
+pub use scheduler::{RunHandle, Execution, StepStatus};
+pub use scoped::ScopedGraph;
+pub use spawner::{Job, Spawner, SpawnScope, ThreadSpawner, InlineSpawner};
+#[cfg(not(target_arch = "wasm32"))]
+pub use spawner::ThreadPoolSpawner;
+pub use topology::{TopologyDiff, SimulationResult};
+#[cfg(feature = "rayon")]
+pub use spawner::RayonSpawner;
+#[cfg(feature = "anyhow")]
+pub use error::AnyhowError;
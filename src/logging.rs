@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::run_id::RunId;
+
+// What the scheduler tells the `log` crate about one run. Every message
+// starts with `run <id>:`, so that concurrent runs can be told apart.
+// Without the `log` feature only the id is kept and every other call
+// compiles to nothing.
+pub(crate) struct RunLog {
+    run_id: RunId,
+}
+
+#[cfg_attr(not(feature = "log"), allow(unused_variables))]
+impl RunLog {
+    pub fn start(run_id: RunId) -> RunLog {
+        #[cfg(feature = "log")]
+        log::debug!("run {}: started", run_id);
+        return RunLog{run_id: run_id};
+    }
+
+    pub fn run_id(&self) -> &RunId {
+        return &self.run_id;
+    }
+
+    pub fn node_started(&self, name: &str) {
+        #[cfg(feature = "log")]
+        log::debug!("run {}: node {} started", self.run_id, name);
+    }
+
+    pub fn node_finished(&self, name: &str, duration: Duration, fallback_used: bool) {
+        #[cfg(feature = "log")]
+        {
+            if fallback_used {
+                log::warn!("run {}: node {} failed, its fallback was used", self.run_id, name);
+            }
+            log::debug!("run {}: node {} finished in {:?}", self.run_id, name, duration);
+        }
+    }
+
+    pub fn node_slow(&self, name: &str, duration: Duration, budget: Duration) {
+        #[cfg(feature = "log")]
+        log::warn!("run {}: node {} took {:?}, over its budget of {:?}", self.run_id, name, duration, budget);
+    }
+
+    pub fn node_hopeless(&self, name: &str, estimate: Duration, remaining: Duration) {
+        #[cfg(feature = "log")]
+        log::warn!("run {}: node {} not started, it needs {:?} with {:?} left before the deadline", self.run_id, name, estimate, remaining);
+    }
+
+    pub fn node_failed(&self, err: &Error) {
+        #[cfg(feature = "log")]
+        log::error!("run {}: {}", self.run_id, err);
+    }
+
+    pub fn finish(&self, duration: Duration, err: Option<&Error>) {
+        #[cfg(feature = "log")]
+        match err {
+            None => log::debug!("run {}: succeeded in {:?}", self.run_id, duration),
+            Some(err) => log::debug!("run {}: failed after {:?}: {}", self.run_id, duration, err),
+        }
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::atomic::{Ordering, AtomicU32, AtomicU64};
+use std::time::Duration;
+
+use crate::graph::FrozenGraph;
+use crate::time::Instant;
+
+// Counters and gauges of one scheduler, summed over all of its runs. Every
+// update is a single relaxed atomic operation, so they are always on.
+// Heartbeats and progress are kept per node, for its latest run.
+#[derive(Debug, Default)]
+pub struct SchedulerMetrics {
+    runs: AtomicU64,
+    tasks_started: AtomicU64,
+    tasks_succeeded: AtomicU64,
+    tasks_failed: AtomicU64,
+    tasks_panicked: AtomicU64,
+    tasks_in_flight: AtomicU64,
+    ready_queue_depth: AtomicU64,
+    nodes: Vec<NodeProgress>,
+    nodes_indices: HashMap<Arc<str>, usize>,
+}
+
+impl SchedulerMetrics {
+    pub(crate) fn new<C>(frozen: &FrozenGraph<C>) -> SchedulerMetrics {
+        return SchedulerMetrics{
+            nodes: frozen.graph.nodes.iter().map(|_| NodeProgress::default()).collect(),
+            nodes_indices: frozen.graph.nodes_indices.clone(),
+            ..SchedulerMetrics::default()
+        };
+    }
+
+    pub fn runs(&self) -> u64 {
+        return self.runs.load(Ordering::Relaxed);
+    }
+
+    pub fn tasks_started(&self) -> u64 {
+        return self.tasks_started.load(Ordering::Relaxed);
+    }
+
+    pub fn tasks_succeeded(&self) -> u64 {
+        return self.tasks_succeeded.load(Ordering::Relaxed);
+    }
+
+    pub fn tasks_failed(&self) -> u64 {
+        return self.tasks_failed.load(Ordering::Relaxed);
+    }
+
+    pub fn tasks_panicked(&self) -> u64 {
+        return self.tasks_panicked.load(Ordering::Relaxed);
+    }
+
+    pub fn tasks_in_flight(&self) -> u64 {
+        return self.tasks_in_flight.load(Ordering::Relaxed);
+    }
+
+    // nodes whose parents have all finished but which are not dispatched yet
+    pub fn ready_queue_depth(&self) -> u64 {
+        return self.ready_queue_depth.load(Ordering::Relaxed);
+    }
+
+    // what the node last passed to `TaskCtx::set_progress`, `None` until it
+    // does
+    pub fn progress(&self, node: &str) -> Option<f32> {
+        return self.nodes_indices.get(node).and_then(|index| self.nodes[*index].progress());
+    }
+
+    pub fn last_heartbeat(&self, node: &str) -> Option<Instant> {
+        return self.nodes_indices.get(node).and_then(|index| self.nodes[*index].last_heartbeat());
+    }
+
+    pub(crate) fn node(&self, index: usize) -> &NodeProgress {
+        return &self.nodes[index];
+    }
+
+    pub(crate) fn run_started(&self) {
+        self.runs.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn task_started(&self) {
+        self.tasks_started.fetch_add(1, Ordering::Relaxed);
+        self.tasks_in_flight.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn task_succeeded(&self) {
+        self.tasks_succeeded.fetch_add(1, Ordering::Relaxed);
+        self.tasks_in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn task_failed(&self) {
+        self.tasks_failed.fetch_add(1, Ordering::Relaxed);
+        self.tasks_in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn task_panicked(&self) {
+        self.tasks_panicked.fetch_add(1, Ordering::Relaxed);
+        self.tasks_in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn ready_queue(&self) -> ReadyGauge<'_> {
+        return ReadyGauge{
+            metrics: self,
+            depth: 0,
+        };
+    }
+}
+
+// The share of `ready_queue_depth` owned by one run; whatever is still
+// queued when the run returns early is taken off again on drop.
+pub(crate) struct ReadyGauge<'a> {
+    metrics: &'a SchedulerMetrics,
+    depth: u64,
+}
+
+impl ReadyGauge<'_> {
+    pub fn push(&mut self, n: usize) {
+        self.depth += n as u64;
+        self.metrics.ready_queue_depth.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub fn depth(&self) -> u64 {
+        return self.depth;
+    }
+
+    pub fn pop(&mut self) {
+        self.depth -= 1;
+        self.metrics.ready_queue_depth.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ReadyGauge<'_> {
+    fn drop(&mut self) {
+        self.metrics.ready_queue_depth.fetch_sub(self.depth, Ordering::Relaxed);
+    }
+}
+
+const NO_PROGRESS: u32 = u32::MAX;
+
+// The heartbeat is stored as nanoseconds since `epoch` plus one, so that 0
+// means none.
+#[derive(Debug)]
+pub(crate) struct NodeProgress {
+    epoch: Instant,
+    heartbeat: AtomicU64,
+    progress: AtomicU32,
+}
+
+impl Default for NodeProgress {
+    fn default() -> NodeProgress {
+        return NodeProgress{
+            epoch: Instant::now(),
+            heartbeat: AtomicU64::new(0),
+            progress: AtomicU32::new(NO_PROGRESS),
+        };
+    }
+}
+
+impl NodeProgress {
+    pub fn reset(&self) {
+        self.heartbeat.store(0, Ordering::Relaxed);
+        self.progress.store(NO_PROGRESS, Ordering::Relaxed);
+    }
+
+    pub fn heartbeat(&self, now: Instant) {
+        let nanos = now.saturating_duration_since(self.epoch).as_nanos() as u64;
+        self.heartbeat.store(nanos + 1, Ordering::Relaxed);
+    }
+
+    pub fn set_progress(&self, progress: f32) {
+        let progress = if progress.is_nan() { 0.0 } else { progress.clamp(0.0, 1.0) };
+        self.progress.store(progress.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn last_heartbeat(&self) -> Option<Instant> {
+        return match self.heartbeat.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(self.epoch + Duration::from_nanos(nanos - 1)),
+        };
+    }
+
+    pub fn progress(&self) -> Option<f32> {
+        return match self.progress.load(Ordering::Relaxed) {
+            NO_PROGRESS => None,
+            bits => Some(f32::from_bits(bits)),
+        };
+    }
+}
@@ -0,0 +1,73 @@
+use std::collections::HashSet;
+
+use crate::error::{Error, TaskError};
+use crate::graph::{Graph, TaskRun};
+
+// A part of a graph whose node names all get `prefix/` in front, e.g.
+// "fetch" in namespace "ingest" is the node "ingest/fetch". Names outside
+// of it are only reachable once imported, and then by their full name.
+// The graph stays mutably borrowed while the namespace is alive.
+pub struct Namespace<'a, C> {
+    graph: &'a mut Graph<C>,
+    prefix: String,
+    imports: HashSet<String>,
+}
+
+impl<C> Graph<C> {
+    pub fn namespace(&mut self, name: &str) -> Namespace<'_, C> {
+        return Namespace{
+            graph: self,
+            prefix: format!("{}/", name),
+            imports: HashSet::new(),
+        };
+    }
+}
+
+impl<C> Namespace<'_, C> {
+    // the full name of `name` as seen from this namespace
+    pub fn name(&self, name: &str) -> String {
+        if self.imports.contains(name) {
+            return name.to_string();
+        }
+        return format!("{}{}", self.prefix, name);
+    }
+
+    // `name` is the full name of a node already in the graph
+    pub fn import(&mut self, name: &str) -> Result<(), Error> {
+        self.graph.index_of(name)?;
+        self.imports.insert(name.to_string());
+        return Ok(());
+    }
+
+    pub fn add_node<F>(&mut self, name: &str, task: F) -> Result<(), Error>
+        where
+            F: Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static
+    {
+        return self.add_task(name, task);
+    }
+
+    pub fn add_task<T>(&mut self, name: &str, task: T) -> Result<(), Error>
+        where
+            T: TaskRun<C> + 'static
+    {
+        if name.is_empty() {
+            return Err(Error::InvalidNode{name: self.name(name), reason: String::from("empty name")});
+        }
+        let name = self.name(name);
+        return self.graph.add_task(&name, task);
+    }
+
+    pub fn add_edge(&mut self, from_node: &str, to_node: &str) -> Result<(), Error> {
+        let (from_node, to_node) = (self.name(from_node), self.name(to_node));
+        return self.graph.add_edge(&from_node, &to_node);
+    }
+
+    // nested namespaces join their names, "ingest" then "s3" gives "ingest/s3/"
+    pub fn namespace(&mut self, name: &str) -> Namespace<'_, C> {
+        return Namespace{
+            prefix: format!("{}{}/", self.prefix, name),
+            graph: self.graph,
+            imports: self.imports.clone(),
+        };
+    }
+}
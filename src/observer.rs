@@ -0,0 +1,54 @@
+use std::time::Duration;
+
+use crate::report::NodeStatus;
+use crate::run_id::RunId;
+use crate::time::Instant;
+
+// What observers are told about. Events are sent from the thread driving
+// the run, so the events of one run arrive in order: a node is started
+// when dispatched, and finished once its result got back to the scheduler.
+// Runs of one scheduler may overlap, `run_id` tells which one it was.
+#[derive(Clone, Debug)]
+pub enum Event<'a> {
+    RunStarted{run_id: &'a RunId},
+    // all parents of the node finished `at`, on the clock of the scheduler;
+    // it may still wait for a worker before it is started
+    NodeReady{run_id: &'a RunId, name: &'a str, at: Instant},
+    NodeStarted{run_id: &'a RunId, name: &'a str},
+    NodeFinished{run_id: &'a RunId, name: &'a str, status: NodeStatus, duration: Duration},
+    // right before `NodeFinished` of a node over its budget, see
+    // `SchedulerBuilder::slow_task_factor`
+    NodeSlow{run_id: &'a RunId, name: &'a str, duration: Duration, budget: Duration},
+    // more than `SchedulerBuilder::max_ready_queue` nodes of the run are
+    // ready but not dispatched; sent once until the queue is back in bounds
+    QueueSaturated{run_id: &'a RunId, depth: usize},
+    RunFinished{run_id: &'a RunId, succeeded: bool},
+}
+
+impl Event<'_> {
+    pub fn run_id(&self) -> &RunId {
+        return match self {
+            Event::RunStarted{run_id}
+            | Event::NodeReady{run_id, ..}
+            | Event::NodeStarted{run_id, ..}
+            | Event::NodeFinished{run_id, ..}
+            | Event::NodeSlow{run_id, ..}
+            | Event::QueueSaturated{run_id, ..}
+            | Event::RunFinished{run_id, ..} => run_id,
+        };
+    }
+}
+
+// Observers run on the scheduling thread, a slow one delays every node.
+pub trait Observer: Send + Sync {
+    fn on_event(&self, event: &Event<'_>);
+}
+
+impl<F> Observer for F
+    where
+        F: Fn(&Event<'_>) + Send + Sync
+{
+    fn on_event(&self, event: &Event<'_>) {
+        self(event);
+    }
+}
@@ -0,0 +1,143 @@
+use std::backtrace::Backtrace;
+use std::cell::{Cell, RefCell};
+use std::panic::{self, PanicHookInfo};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use crate::error::PanicError;
+
+// Where a task panicked, as seen by the panic hook. The backtrace is only
+// captured with the `backtrace` feature.
+#[derive(Debug)]
+pub struct PanicInfoCaptured {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+    pub backtrace: Option<Backtrace>,
+}
+
+impl std::fmt::Display for PanicInfoCaptured {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+type Hook = Box<dyn Fn(&PanicHookInfo<'_>) + Send + Sync + 'static>;
+
+// The capturing hook is installed when the first run starts, in front of
+// the hook installed then, which every panic is still passed on to, and
+// taken down again when the last running one returns, so that concurrent
+// runs share one hook. A hook the application installed in the meantime
+// is left in place; runs starting while it is there capture nothing.
+struct HookState {
+    n_run: usize,
+    // the hook replaced by the capturing one, and where the capturing one
+    // lives, to tell whether it is still installed
+    previous: Option<(Arc<Hook>, usize)>,
+}
+
+static HOOK: Mutex<HookState> = Mutex::new(HookState{
+    n_run: 0,
+    previous: None,
+});
+
+thread_local! {
+    static CAPTURED: RefCell<Option<Box<PanicInfoCaptured>>> = const { RefCell::new(None) };
+    // only panics within `catch` are captured
+    static CATCHING: Cell<bool> = const { Cell::new(false) };
+}
+
+pub(crate) struct HookGuard;
+
+impl HookGuard {
+    pub fn install() -> HookGuard {
+        let mut state = HOOK.lock().unwrap_or_else(|err| err.into_inner());
+        state.n_run += 1;
+        if state.previous.is_none() {
+            let previous: Arc<Hook> = Arc::new(panic::take_hook());
+            let chained = previous.clone();
+            let hook: Hook = Box::new(move |info| {
+                if CATCHING.try_with(|cell| cell.get()).unwrap_or(false) {
+                    capture(info);
+                }
+                chained(info);
+            });
+            let address = address_of(&hook);
+            panic::set_hook(hook);
+            state.previous = Some((previous, address));
+        }
+        return HookGuard;
+    }
+}
+
+impl Drop for HookGuard {
+    fn drop(&mut self) {
+        let mut state = HOOK.lock().unwrap_or_else(|err| err.into_inner());
+        state.n_run -= 1;
+        // the hook cannot be replaced while unwinding, it is simply kept
+        // until a later run puts the previous one back
+        if state.n_run > 0 || thread::panicking() {
+            return;
+        }
+        if let Some((previous, address)) = state.previous.take() {
+            let current = panic::take_hook();
+            if address_of(&current) != address {
+                panic::set_hook(current);
+                return;
+            }
+            // dropping the capturing hook leaves the only reference
+            drop(current);
+            match Arc::try_unwrap(previous) {
+                Ok(previous) => panic::set_hook(previous),
+                Err(previous) => panic::set_hook(Box::new(move |info| previous(info))),
+            }
+        }
+    }
+}
+
+fn address_of(hook: &Hook) -> usize {
+    return &**hook as *const _ as *const () as usize;
+}
+
+fn capture(info: &PanicHookInfo<'_>) {
+    let Some(location) = info.location() else {
+        return;
+    };
+    #[cfg(feature = "backtrace")]
+    let backtrace = Some(Backtrace::force_capture());
+    #[cfg(not(feature = "backtrace"))]
+    let backtrace = None;
+    let captured = Box::new(PanicInfoCaptured{
+        file: location.file().to_string(),
+        line: location.line(),
+        column: location.column(),
+        backtrace: backtrace,
+    });
+    let _ = CAPTURED.try_with(|cell| *cell.borrow_mut() = Some(captured));
+}
+
+// `catch_unwind` which also hands back what the hook captured about the
+// panic, if it was installed.
+#[cfg(panic = "unwind")]
+pub(crate) fn catch<R, F>(f: F) -> Result<R, (PanicError, Option<Box<PanicInfoCaptured>>)>
+    where
+        F: FnOnce() -> R
+{
+    CAPTURED.with(|cell| cell.borrow_mut().take());
+    // nested when a task runs a graph of its own
+    let catching = CATCHING.with(|cell| cell.replace(true));
+    let result = panic::catch_unwind(panic::AssertUnwindSafe(f));
+    CATCHING.with(|cell| cell.set(catching));
+    return result.map_err(|err| (err, CAPTURED.with(|cell| cell.borrow_mut().take())));
+}
+
+// Built with `panic = "abort"`, the default on wasm32-unknown-unknown, a
+// panicking task aborts the whole process, or the whole wasm instance,
+// before anything could be caught.
+#[cfg(not(panic = "unwind"))]
+pub(crate) fn catch<R, F>(f: F) -> Result<R, (PanicError, Option<Box<PanicInfoCaptured>>)>
+    where
+        F: FnOnce() -> R
+{
+    return Ok(f());
+}
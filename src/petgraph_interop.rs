@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+
+use petgraph::graph::{DiGraph, NodeIndex};
+
+use crate::error::Error::{self, *};
+use crate::graph::{Graph, Task};
+
+// Node weights are the node names, node `i` of the graph is petgraph's
+// node `i`, and all edges keep their direction.
+impl<C> From<&Graph<C>> for DiGraph<String, ()> {
+    fn from(graph: &Graph<C>) -> DiGraph<String, ()> {
+        let n_edge = graph.nodes.iter().map(|node| node.childrens.len()).sum();
+        let mut g = DiGraph::with_capacity(graph.nodes.len(), n_edge);
+        for node in graph.nodes.iter() {
+            g.add_node(node.name.to_string());
+        }
+        for node in graph.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                g.add_edge(NodeIndex::new(node.index), NodeIndex::new(*child_index), ());
+            }
+        }
+        return g;
+    }
+}
+
+impl<C: 'static> Graph<C> {
+    // Rebuilds a graph from `g`, taking the task of every node from
+    // `registry` by name. A node without a task gives `NodeNotFound`, names
+    // and edges are checked as by `add_node` and `add_edge`.
+    pub fn from_petgraph(g: &DiGraph<String, ()>, mut registry: HashMap<String, Task<C>>) -> Result<Graph<C>, Error> {
+        let mut graph = Graph::new();
+        for name in g.node_weights() {
+            if graph.nodes_indices.contains_key(name.as_str()) {
+                return Err(DuplicatedNode{name: name.clone()});
+            }
+            let task = match registry.remove(name) {
+                Some(task) => task,
+                None => return Err(NodeNotFound{name: name.clone()}),
+            };
+            graph.add_node(name, task)?;
+        }
+        for edge in g.raw_edges() {
+            graph.add_edge(&g[edge.source()], &g[edge.target()])?;
+        }
+        return Ok(graph);
+    }
+}
@@ -0,0 +1,285 @@
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+use std::time::Duration;
+
+use crate::graph::FrozenGraph;
+use crate::run_id::RunId;
+use crate::time::Instant;
+
+// `Skipped` only shows up with `FailurePolicy::ContinueOnError`: the node
+// depends on `because_of`, which failed or panicked, and `path` is one
+// dependency chain from `because_of` down to the skipped node, both ends
+// included. `SkippedForDeadline` nodes were ready but not started with
+// `SchedulerBuilder::skip_hopeless_nodes`, as they were `estimate` away
+// from finishing, themselves and what depends on them, with only
+// `remaining` left.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum NodeStatus {
+    NotRun,
+    // `over_budget` with `SchedulerBuilder::slow_task_factor`, when the node
+    // took longer than the factor times its cost
+    Succeeded{duration: Duration, over_budget: bool},
+    Failed,
+    Panicked,
+    Skipped{because_of: String, path: Vec<String>},
+    SkippedForDeadline{estimate: Duration, remaining: Duration},
+}
+
+// Timestamps are relative to the start of the run, `ready_at` is when all
+// parents of the node had finished. `worker` identifies the
+// slot the node ran in: slots are handed out lowest-free-first when a node
+// is dispatched and freed when its result arrives, so at any time no two
+// running nodes share a slot.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NodeReport {
+    pub name: String,
+    pub status: NodeStatus,
+    pub worker: Option<usize>,
+    pub ready_at: Option<Duration>,
+    pub started_at: Option<Duration>,
+    pub finished_at: Option<Duration>,
+    // the primary task failed and the node's fallback task was run instead
+    pub fallback_used: bool,
+    // added by `Graph::add_barrier`, filter these out to hide them
+    pub barrier: bool,
+    // What a failed or panicked node failed with, displayed, and the type
+    // of that error as far as it is known: the errors of this crate, e.g.
+    // `TimeoutError`, and string panic payloads. The type of a task's own
+    // error is erased once boxed into a `TaskError`. Retries, e.g. of
+    // `task::with_retry`, happen within the task and are not counted here.
+    pub error: Option<String>,
+    pub error_type: Option<String>,
+}
+
+impl NodeReport {
+    // how long the node waited for a worker once it was ready
+    pub fn queue_latency(&self) -> Option<Duration> {
+        return Some(self.started_at?.saturating_sub(self.ready_at?));
+    }
+}
+
+// What a successful run did: `executed` nodes ran, `skipped` ones were left
+// out through `RunOptions` or `Scheduler::run_dirty`, and `cached` ones were
+// taken as done from the checkpoint of `Scheduler::run_resumable`. Barriers
+// are not counted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RunSummary {
+    pub executed: usize,
+    pub skipped: usize,
+    pub cached: usize,
+    pub duration: Duration,
+}
+
+// With the `serde` feature, `to_json` gives the run id, `duration`,
+// `n_worker` and every node report with the field names above; times are
+// relative to the start of the run, durations are `{"secs", "nanos"}`.
+#[derive(Clone, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RunReport {
+    run_id: RunId,
+    nodes: Vec<NodeReport>,
+    duration: Duration,
+    // only needed while recording
+    #[cfg_attr(feature = "serde", serde(skip, default = "Instant::now"))]
+    started: Instant,
+    #[cfg_attr(feature = "serde", serde(skip))]
+    free_workers: BinaryHeap<Reverse<usize>>,
+    n_worker: usize,
+}
+
+impl RunReport {
+    pub(crate) fn new<C>(frozen: &FrozenGraph<C>, run_id: RunId, started: Instant) -> RunReport {
+        let nodes = frozen.graph.nodes.iter().map(|node| NodeReport{
+            name: node.name.to_string(),
+            status: NodeStatus::NotRun,
+            worker: None,
+            ready_at: None,
+            started_at: None,
+            finished_at: None,
+            fallback_used: false,
+            barrier: node.barrier,
+            error: None,
+            error_type: None,
+        }).collect();
+        return RunReport{
+            run_id: run_id,
+            nodes: nodes,
+            duration: Duration::ZERO,
+            started: started,
+            free_workers: BinaryHeap::new(),
+            n_worker: 0,
+        };
+    }
+
+    pub fn run_id(&self) -> &RunId {
+        return &self.run_id;
+    }
+
+    // one entry per node of the graph, in insertion order
+    pub fn nodes(&self) -> &[NodeReport] {
+        return &self.nodes;
+    }
+
+    pub fn node(&self, name: &str) -> Option<&NodeReport> {
+        return self.nodes.iter().find(|node| node.name == name);
+    }
+
+    pub fn duration(&self) -> Duration {
+        return self.duration;
+    }
+
+    // number of distinct worker slots used during the run
+    pub fn n_worker(&self) -> usize {
+        return self.n_worker;
+    }
+
+    // Highest number of nodes that were running at the same instant, taken
+    // from the recorded start and finish times.
+    pub fn max_parallelism_observed(&self) -> usize {
+        let mut events: Vec<(Duration, i32)> = vec![];
+        for (begin, end) in self.intervals() {
+            events.push((begin, 1));
+            events.push((end, -1));
+        }
+        // a node finishing at the very instant another one starts does
+        // not count as overlapping
+        events.sort();
+        let mut n_running: i32 = 0;
+        let mut max_running: i32 = 0;
+        for (_, delta) in events {
+            n_running += delta;
+            max_running = max_running.max(n_running);
+        }
+        return max_running as usize;
+    }
+
+    // Splits the run into `bins` equal time slices and returns, for every
+    // slice, the fraction of worker slots that were busy.
+    pub fn utilization(&self, bins: usize) -> Vec<f32> {
+        let mut busy = vec![0f64; bins];
+        let total = self.duration.as_secs_f64();
+        if bins == 0 || total <= 0.0 || self.n_worker == 0 {
+            return vec![0f32; bins];
+        }
+        let width = total / bins as f64;
+        for (begin, end) in self.intervals() {
+            let (begin, end) = (begin.as_secs_f64(), end.as_secs_f64().min(total));
+            let first = ((begin / width) as usize).min(bins - 1);
+            let last = ((end / width) as usize).min(bins - 1);
+            for (i, busy) in busy.iter_mut().enumerate().take(last + 1).skip(first) {
+                let bin_begin = i as f64 * width;
+                let bin_end = bin_begin + width;
+                *busy += (end.min(bin_end) - begin.max(bin_begin)).max(0.0);
+            }
+        }
+        return busy.iter()
+            .map(|busy| (busy / (width * self.n_worker as f64)) as f32)
+            .collect();
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        return serde_json::to_string(self).expect("a run report is always serializable");
+    }
+
+    #[cfg(feature = "serde")]
+    pub fn from_json(json: &str) -> Result<RunReport, serde_json::Error> {
+        return serde_json::from_str(json);
+    }
+
+    fn intervals(&self) -> impl Iterator<Item = (Duration, Duration)> + '_ {
+        return self.nodes.iter().filter_map(|node| match (node.started_at, node.finished_at) {
+            (Some(begin), Some(end)) => Some((begin, end)),
+            _ => None,
+        });
+    }
+
+    pub(crate) fn assign_worker(&mut self, index: usize) {
+        let worker = match self.free_workers.pop() {
+            Some(Reverse(worker)) => worker,
+            None => {
+                self.n_worker += 1;
+                self.n_worker - 1
+            },
+        };
+        self.nodes[index].worker = Some(worker);
+    }
+
+    pub(crate) fn ready(&mut self, index: usize, at: Instant) {
+        self.nodes[index].ready_at = Some(at.saturating_duration_since(self.started));
+    }
+
+    pub(crate) fn record(&mut self, index: usize, status: NodeStatus, started_at: Instant, finished_at: Instant, fallback_used: bool, error: (Option<String>, Option<String>)) {
+        let node = &mut self.nodes[index];
+        node.status = status;
+        node.fallback_used = fallback_used;
+        (node.error, node.error_type) = error;
+        node.started_at = Some(started_at.saturating_duration_since(self.started));
+        node.finished_at = Some(finished_at.saturating_duration_since(self.started));
+        if let Some(worker) = node.worker {
+            self.free_workers.push(Reverse(worker));
+        }
+    }
+
+    // the chain is filled in by `finish`
+    pub(crate) fn skip(&mut self, index: usize) {
+        self.nodes[index].status = NodeStatus::Skipped{
+            because_of: String::new(),
+            path: vec![],
+        };
+    }
+
+    pub(crate) fn miss_deadline(&mut self, index: usize, estimate: Duration, remaining: Duration) {
+        self.nodes[index].status = NodeStatus::SkippedForDeadline{
+            estimate: estimate,
+            remaining: remaining,
+        };
+    }
+
+    pub(crate) fn finish<C>(&mut self, frozen: &FrozenGraph<C>, finished: Instant) {
+        self.duration = finished.saturating_duration_since(self.started);
+        self.resolve_skips(frozen);
+    }
+
+    // A breadth-first walk out of all failed nodes through the skipped ones
+    // remembers where every skipped node was first reached from; walking
+    // that back up yields the shortest chain to a failed ancestor.
+    fn resolve_skips<C>(&mut self, frozen: &FrozenGraph<C>) {
+        let is_skipped = |node: &NodeReport| matches!(node.status, NodeStatus::Skipped{..});
+        if !self.nodes.iter().any(is_skipped) {
+            return;
+        }
+        let mut reached_from: Vec<Option<usize>> = vec![None; self.nodes.len()];
+        let mut queue: VecDeque<usize> = self.nodes.iter().enumerate()
+            .filter(|(_, node)| matches!(node.status, NodeStatus::Failed | NodeStatus::Panicked))
+            .map(|(index, _)| index)
+            .collect();
+        while let Some(index) = queue.pop_front() {
+            for child_index in frozen.childrens_of(index) {
+                let child_index = *child_index as usize;
+                if reached_from[child_index].is_none() && is_skipped(&self.nodes[child_index]) {
+                    reached_from[child_index] = Some(index);
+                    queue.push_back(child_index);
+                }
+            }
+        }
+        for index in 0..self.nodes.len() {
+            if !is_skipped(&self.nodes[index]) {
+                continue;
+            }
+            let mut path = vec![self.nodes[index].name.clone()];
+            let mut current = index;
+            while let Some(parent) = reached_from[current] {
+                path.push(self.nodes[parent].name.clone());
+                current = parent;
+            }
+            path.reverse();
+            self.nodes[index].status = NodeStatus::Skipped{
+                because_of: path[0].clone(),
+                path: path,
+            };
+        }
+    }
+}
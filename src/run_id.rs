@@ -0,0 +1,59 @@
+use std::fmt;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+// Tells the runs of a scheduler apart in observer events, log messages,
+// run reports and runtime errors. Generated ids count up per process from
+// 1; `RunOptions::run_id` and `Scheduler::run_resumable` use the one given
+// by the caller instead, e.g. the id of an upstream request.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RunId(Arc<str>);
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+impl RunId {
+    pub fn new(id: &str) -> RunId {
+        return RunId(Arc::from(id));
+    }
+
+    pub(crate) fn next() -> RunId {
+        return RunId::new(&NEXT_ID.fetch_add(1, Ordering::Relaxed).to_string());
+    }
+
+    pub fn as_str(&self) -> &str {
+        return &self.0;
+    }
+}
+
+impl fmt::Display for RunId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return f.write_str(&self.0);
+    }
+}
+
+// as the plain string
+#[cfg(feature = "serde")]
+impl serde::Serialize for RunId {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        return serializer.serialize_str(&self.0);
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RunId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<RunId, D::Error> {
+        return String::deserialize(deserializer).map(RunId::from);
+    }
+}
+
+impl From<&str> for RunId {
+    fn from(id: &str) -> RunId {
+        return RunId::new(id);
+    }
+}
+
+impl From<String> for RunId {
+    fn from(id: String) -> RunId {
+        return RunId(Arc::from(id));
+    }
+}
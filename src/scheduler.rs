@@ -1,105 +1,1877 @@
-use std::panic::{self, AssertUnwindSafe};
-use std::sync::mpsc;
-use std::thread::{self, Builder};
+use std::any::Any;
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, VecDeque};
+#[cfg(not(target_arch = "wasm32"))]
+use std::panic;
+use std::sync::{mpsc, Arc, Mutex};
+use std::sync::atomic::{AtomicBool, Ordering};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
+use std::time::Duration;
 
+use crate::builder::{SchedulerBuilder, RunOptions};
+use crate::checkpoint::Checkpoint;
+use crate::clock::Clock;
+use crate::context::{Output, RunScratch, TaskCtx};
+use crate::cost_model::CostModel;
 use crate::error::Error::{self, *};
-use crate::error::{TaskError, PanicError};
-use crate::graph::{Node, FrozenGraph};
+use crate::error::{panic_message, task_error, TaskError, PanicError, PanickedTaskError, PostTaskCheckError, TimeoutError};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::error::SpawnError;
+use crate::graph::{TaskRun, NodeResult, Node, FrozenGraph, PanicPolicy};
+use crate::logging::RunLog;
+use crate::metrics::{ReadyGauge, SchedulerMetrics};
+use crate::observer::{Event, Observer};
+use crate::panics::{self, HookGuard, PanicInfoCaptured};
+use crate::report::{NodeStatus, RunReport, RunSummary};
+use crate::run_id::RunId;
+use crate::scratch::{NodeScratch, ScratchDirs};
+use crate::spawner::{Spawner, ThreadSpawner};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::spawner::ThreadPoolSpawner;
+use crate::time::Instant;
+
+// What a run does once a task fails or panics: `FailFast` returns that
+// error right away, `ContinueOnError` skips every node depending on the
+// failed one, still runs everything else, and returns all failures at the
+// end as one `MultipleFailures`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    #[default]
+    FailFast,
+    ContinueOnError,
+}
+
+// Which of the nodes ready at the same time is dispatched first: the one
+// which became ready first, the one added to the graph first, or the one
+// with the longest path of estimated costs still ahead of it, itself
+// included. Estimates come from the cost model if the scheduler has one
+// which knows the node, from `Graph::set_cost` otherwise. Sequential runs
+// use the insertion order unless `CriticalPath` is chosen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DispatchStrategy {
+    #[default]
+    Fifo,
+    InsertionOrder,
+    CriticalPath,
+}
 
 pub struct Scheduler<C> {
-    frozen: FrozenGraph<C>,
-    sender: mpsc::Sender<RunningResult>,
-    receiver: mpsc::Receiver<RunningResult>,
+    pub(crate) frozen: Arc<FrozenGraph<C>>,
+    pub(crate) threads: ThreadSpawner,
+    pub(crate) spawner: Option<Arc<dyn Spawner>>,
+    pub(crate) metrics: Arc<SchedulerMetrics>,
+    pub(crate) failure_policy: FailurePolicy,
+    pub(crate) max_failures: usize,
+    pub(crate) max_concurrency: usize,
+    pub(crate) max_total_weight: u64,
+    pub(crate) slow_task_factor: f64,
+    // limits by class, and the class of every node as an index into them;
+    // both are empty without any limit
+    pub(crate) class_limits: Vec<usize>,
+    pub(crate) node_classes: Vec<Option<usize>>,
+    pub(crate) each: EachHooks<C>,
+    pub(crate) watchdog: Option<Watchdog>,
+    pub(crate) observers: Vec<Arc<dyn Observer>>,
+    pub(crate) dispatch_strategy: DispatchStrategy,
+    pub(crate) cost_model: Option<Arc<Mutex<CostModel>>>,
+    pub(crate) abandon_in_flight: bool,
+    pub(crate) on_abandoned: Option<AbandonedCallback>,
+    pub(crate) scratch: Option<ScratchDirs>,
+    pub(crate) deadline: Option<Duration>,
+    pub(crate) skip_hopeless: bool,
+    pub(crate) clock: Arc<dyn Clock>,
+    pub(crate) max_ready_queue: usize,
+    pub(crate) panics_as_errors: bool,
+    pub(crate) gate_poll_interval: Duration,
+    pub(crate) gate_timeout: Option<Duration>,
 }
 
-impl<C: Send + Sync> Scheduler<C> {
+pub(crate) type BeforeEach<C> = Arc<dyn Fn(&str, &C) + Send + Sync + 'static>;
+pub(crate) type AfterEach<C> = Arc<dyn Fn(&str, &C, &NodeResult) + Send + Sync + 'static>;
+pub(crate) type PostTaskCheck<C> = Arc<dyn Fn(&C) -> Result<(), TaskError> + Send + Sync + 'static>;
+
+pub(crate) struct EachHooks<C> {
+    pub before: Option<BeforeEach<C>>,
+    pub after: Option<AfterEach<C>>,
+    pub check: Option<PostTaskCheck<C>>,
+}
+
+impl<C> Clone for EachHooks<C> {
+    fn clone(&self) -> EachHooks<C> {
+        return EachHooks{
+            before: self.before.clone(),
+            after: self.after.clone(),
+            check: self.check.clone(),
+        };
+    }
+}
+
+pub(crate) type AbandonedCallback = Arc<dyn Fn(&str, NodeStatus) + Send + Sync + 'static>;
+pub(crate) type StuckCallback = Arc<dyn Fn(&str, Duration) + Send + Sync + 'static>;
+
+pub(crate) struct Watchdog {
+    pub after: Duration,
+    pub callback: StuckCallback,
+}
+
+impl Watchdog {
+    // Waits for the next result like `recv`, or `recv_timeout` up to
+    // `until`, meanwhile warning about every node in `running`, which maps
+    // dispatched nodes to when they were dispatched and how many intervals
+    // were checked so far.
+    fn wait<C>(
+        &self,
+        receiver: &mpsc::Receiver<RunningResult>,
+        frozen: &FrozenGraph<C>,
+        metrics: &SchedulerMetrics,
+        clock: &dyn Clock,
+        running: &mut HashMap<usize, (Instant, u32)>,
+        until: Option<Instant>,
+    ) -> Option<RunningResult> {
+        loop {
+            let now = clock.now();
+            let mut next: Option<Instant> = None;
+            for (index, (since, n_warned)) in running.iter_mut() {
+                let elapsed = now - *since;
+                let n_due = (elapsed.as_nanos() / self.after.as_nanos()) as u32;
+                if n_due > *n_warned {
+                    *n_warned = n_due;
+                    // a heartbeat within the interval means slow, not stuck
+                    let alive = metrics.node(*index).last_heartbeat()
+                        .is_some_and(|beat| now.saturating_duration_since(beat) < self.after);
+                    if !alive {
+                        (self.callback)(&frozen.graph.nodes[*index].name, elapsed);
+                    }
+                }
+                let due = *since + self.after * (*n_warned + 1);
+                next = Some(next.map_or(due, |next| next.min(due)));
+            }
+            // `until` is in real time, whatever the clock
+            let timeout = match (next, until) {
+                (None, None) => return receiver.recv().ok(),
+                (Some(next), None) => next.saturating_duration_since(clock.now()),
+                (None, Some(until)) => until.saturating_duration_since(Instant::now()),
+                (Some(next), Some(until)) => next.saturating_duration_since(clock.now()).min(until.saturating_duration_since(Instant::now())),
+            };
+            match receiver.recv_timeout(timeout) {
+                Err(mpsc::RecvTimeoutError::Timeout) if until.is_none_or(|until| Instant::now() < until) => continue,
+                result => return result.ok(),
+            }
+        }
+    }
+}
+
+// Nodes whose parents have all finished: `ByIndex` hands out the one added
+// to the graph first, `Fifo` the one that became ready first.
+enum ReadyQueue {
+    ByIndex(BinaryHeap<Reverse<usize>>),
+    Fifo(VecDeque<usize>),
+    // highest priority first, ties by index
+    ByPriority(BinaryHeap<(Duration, Reverse<usize>)>, Vec<Duration>),
+}
+
+impl ReadyQueue {
+    fn new(by_index: bool, indices: Vec<usize>) -> ReadyQueue {
+        return match by_index {
+            true => ReadyQueue::ByIndex(indices.into_iter().map(Reverse).collect()),
+            false => ReadyQueue::Fifo(indices.into()),
+        };
+    }
+
+    fn by_priority(priorities: Vec<Duration>, indices: Vec<usize>) -> ReadyQueue {
+        let heap = indices.into_iter().map(|index| (priorities[index], Reverse(index))).collect();
+        return ReadyQueue::ByPriority(heap, priorities);
+    }
+
+    fn push(&mut self, index: usize) {
+        match self {
+            ReadyQueue::ByIndex(heap) => heap.push(Reverse(index)),
+            ReadyQueue::Fifo(queue) => queue.push_back(index),
+            ReadyQueue::ByPriority(heap, priorities) => heap.push((priorities[index], Reverse(index))),
+        }
+    }
+
+    fn peek(&self) -> Option<usize> {
+        return match self {
+            ReadyQueue::ByIndex(heap) => heap.peek().map(|Reverse(index)| *index),
+            ReadyQueue::Fifo(queue) => queue.front().copied(),
+            ReadyQueue::ByPriority(heap, _) => heap.peek().map(|(_, Reverse(index))| *index),
+        };
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        return match self {
+            ReadyQueue::ByIndex(heap) => heap.pop().map(|Reverse(index)| index),
+            ReadyQueue::Fifo(queue) => queue.pop_front(),
+            ReadyQueue::ByPriority(heap, _) => heap.pop().map(|(_, Reverse(index))| index),
+        };
+    }
+
+    fn len(&self) -> usize {
+        return match self {
+            ReadyQueue::ByIndex(heap) => heap.len(),
+            ReadyQueue::Fifo(queue) => queue.len(),
+            ReadyQueue::ByPriority(heap, _) => heap.len(),
+        };
+    }
+}
+
+// Whatever a single run may differ in from the next one.
+struct RunArgs<'a, C> {
+    report: Option<&'a mut RunReport>,
+    // every node is executed inline, one at a time
+    sequential: bool,
+    // the nodes left out count as finished, `None` runs all of them
+    selected: Option<Vec<bool>>,
+    // where every successful node is recorded, with the run id
+    checkpoint: Option<(&'a str, &'a dyn Checkpoint)>,
+    // overriding the options of the scheduler
+    failure_policy: Option<FailurePolicy>,
+    max_failures: Option<usize>,
+    max_concurrency: Option<usize>,
+    // once set, nothing more is dispatched
+    drain: Option<&'a AtomicBool>,
+    // once set, the run fails with `Cancelled` without waiting for the
+    // nodes still running
+    cancel: Option<&'a AtomicBool>,
+    // generated when not given
+    run_id: Option<RunId>,
+    // where the output of every node goes, by index
+    outputs: Option<&'a mut Vec<Option<Output>>>,
+    // by index, the params of the nodes the run sets any for, merged over
+    // those of the graph; empty if it sets none
+    params: Vec<Option<Arc<HashMap<String, String>>>>,
+    // see `Spawner::scope_with_priority`
+    priority: u8,
+    // by index, the tasks run instead of those of the graph; empty if the
+    // run replaces none
+    tasks: Vec<Option<Arc<dyn TaskRun<C>>>>,
+    // see `Scheduler::run_with_scratch`
+    run_scratch: Option<RunScratch>,
+}
+
+impl<C> Default for RunArgs<'_, C> {
+    fn default() -> Self {
+        return RunArgs{
+            report: None,
+            sequential: false,
+            selected: None,
+            checkpoint: None,
+            failure_policy: None,
+            max_failures: None,
+            max_concurrency: None,
+            drain: None,
+            cancel: None,
+            run_id: None,
+            outputs: None,
+            params: vec![],
+            priority: 0,
+            tasks: vec![],
+            run_scratch: None,
+        };
+    }
+}
+
+// What one node gets for a run besides the context.
+struct NodeEnv<C> {
+    scratch: Option<NodeScratch>,
+    params: Option<Arc<HashMap<String, String>>>,
+    task: Option<Arc<dyn TaskRun<C>>>,
+    run_scratch: Option<RunScratch>,
+    // the nodes fused to this one, see `FrozeOptions::fuse_linear_chains`,
+    // run in order after it as long as every one succeeds
+    then: Vec<(usize, NodeEnv<C>)>,
+    // a node fused to another is not started once that one finished after
+    // this, see `Drive::latest_start`
+    latest_start: Option<Instant>,
+}
+
+impl<C> NodeEnv<C> {
+    fn chain(mut self, index: usize) -> impl Iterator<Item = (usize, NodeEnv<C>)> {
+        let then = std::mem::take(&mut self.then);
+        return std::iter::once((index, self)).chain(then);
+    }
+
+    // whether the node is given up, the one before it on its chain having
+    // finished at `at`
+    fn is_late(&self, at: Instant) -> bool {
+        return self.latest_start.is_some_and(|latest_start| at > latest_start);
+    }
+}
+
+impl<C> Scheduler<C> {
+    // all options at their defaults, see `SchedulerBuilder` for the others
     pub fn new(frozen: FrozenGraph<C>) -> Scheduler<C> {
+        return SchedulerBuilder::new(frozen).build();
+    }
+
+    // Starts what the spawner would start on the first run, e.g. the threads
+    // of a `ThreadPoolSpawner`; nothing for the default one.
+    pub fn warm_up(&self) {
+        self.spawner().warm_up();
+    }
+
+    pub fn metrics(&self) -> Arc<SchedulerMetrics> {
+        return self.metrics.clone();
+    }
+
+    // Runs the tasks through `spawner` instead of one std thread per task.
+    pub fn with_spawner<S>(frozen: FrozenGraph<C>, spawner: S) -> Scheduler<C>
+        where
+            S: Spawner + 'static
+    {
+        return SchedulerBuilder::new(frozen).spawner(spawner).build();
+    }
+
+    // For contexts which are `Send` but not `Sync`, e.g. holding a `RefCell`
+    // cache: the context moves to a single worker thread, which runs every
+    // task in the order of `run_sequential`, and is handed back afterwards,
+    // on failure as well. On wasm it runs on the calling thread.
+    pub fn run_sequential_send(&self, ctx: C) -> Result<C, (C, Error)>
+        where
+            C: Send
+    {
+        let run = move || {
+            let (_sender, receiver) = mpsc::channel();
+            let args = RunArgs{
+                sequential: true,
+                ..RunArgs::default()
+            };
+            let result = self.schedule(&ctx, &receiver, args, |_, _| {
+                unreachable!("sequential runs execute every node inline");
+            });
+            return match result {
+                Ok(_) => Ok(ctx),
+                Err(err) => Err((ctx, err)),
+            };
+        };
+        if cfg!(target_arch = "wasm32") {
+            return run();
+        }
+        return std::thread::scope(|scope| {
+            let worker = std::thread::Builder::new().name("dag-worker".to_string()).spawn_scoped(scope, run)
+                .expect("failed to spawn the worker thread");
+            return worker.join().unwrap_or_else(|err| std::panic::resume_unwind(err));
+        });
+    }
+}
+
+impl<C: Send + Sync> Scheduler<C> {
+    // Runs every task on a thread of its own by default; for lots of small
+    // tasks, `ThreadPoolSpawner` reuses its threads instead. Tasks which may
+    // never finish can be bounded with `task::with_timeout`.
+    pub fn run(&self, ctx: &C) -> Result<(), Error> {
+        return self.run_in(self.spawner(), ctx, RunArgs::default()).map(|_| ());
+    }
+
+    // Same as `run`, with `make_scratch` called once for the run, and what
+    // it makes handed to the tasks added through `Graph::add_node_scratch`,
+    // for state which is not worth a new context every run. It is dropped
+    // before this returns.
+    pub fn run_with_scratch<S, F>(&self, ctx: &C, make_scratch: F) -> Result<(), Error>
+        where
+            S: Send + Sync + 'static,
+            F: FnOnce() -> S
+    {
+        let result = self.run_in(self.spawner(), ctx, RunArgs{
+            run_scratch: Some(Arc::new(make_scratch())),
+            ..RunArgs::default()
+        });
+        return result.map(|_| ());
+    }
+
+    // Runs the graph once for every context of `ctxs`, each run the same as
+    // `run` and failing on its own, results in the order of `ctxs`. The runs
+    // go on side by side, their tasks sharing `max_concurrency` threads, one
+    // per core if 0, or else the spawner of the scheduler if it has one.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn run_many(&self, ctxs: &[C]) -> Vec<Result<(), Error>> {
+        let pool;
+        let spawner: &dyn Spawner = match &self.spawner {
+            Some(spawner) => &**spawner,
+            None => {
+                pool = ThreadPoolSpawner::new(self.max_concurrency);
+                &pool
+            },
+        };
+        let n_driver = match self.max_concurrency {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        let next = std::sync::atomic::AtomicUsize::new(0);
+        let results: Vec<Mutex<Option<Result<(), Error>>>> = ctxs.iter().map(|_| Mutex::new(None)).collect();
+        thread::scope(|scope| {
+            for _ in 0..n_driver.min(ctxs.len()) {
+                scope.spawn(|| {
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        if i >= ctxs.len() {
+                            break;
+                        }
+                        let result = self.run_in(spawner, &ctxs[i], RunArgs::default()).map(|_| ());
+                        *results[i].lock().unwrap_or_else(|err| err.into_inner()) = Some(result);
+                    }
+                });
+            }
+        });
+        return results.into_iter()
+            .map(|result| result.into_inner().unwrap_or_else(|err| err.into_inner()).expect("every context was run"))
+            .collect();
+    }
+
+    // Same as `run`, telling how many nodes actually ran.
+    pub fn run_full(&self, ctx: &C) -> Result<RunSummary, Error> {
+        return self.run_in(self.spawner(), ctx, RunArgs::default());
+    }
+
+    // Same as `run`, handing back the output of every leaf node, see
+    // `TaskCtx::set_output` and `Graph::add_output_node`, by node name.
+    // Each of them must have given an output of type `T`, the outputs of
+    // all other nodes are dropped.
+    pub fn run_collect<T: Any>(&self, ctx: &C) -> Result<HashMap<String, T>, Error> {
+        let mut outputs = vec![];
+        self.run_in(self.spawner(), ctx, RunArgs{
+            outputs: Some(&mut outputs),
+            ..RunArgs::default()
+        })?;
+        let mut collected = HashMap::new();
+        for node in self.frozen.graph.nodes.iter().filter(|node| node.childrens.is_empty()) {
+            let Some(output) = outputs.get_mut(node.index).and_then(|output| output.take()) else {
+                return Err(MissingOutput{node: node.name.to_string(), index: node.index});
+            };
+            let Ok(output) = output.downcast::<T>() else {
+                return Err(OutputTypeMismatch{
+                    node: node.name.to_string(),
+                    index: node.index,
+                    expected: std::any::type_name::<T>(),
+                });
+            };
+            collected.insert(node.name.to_string(), *output);
+        }
+        return Ok(collected);
+    }
+
+    // Same as `run`, additionally recording when and where every node ran.
+    pub fn run_with_report(&self, ctx: &C) -> (Result<(), Error>, RunReport) {
+        let run_id = RunId::next();
+        let mut report = RunReport::new(&self.frozen, run_id.clone(), self.clock.now());
+        let result = self.run_in(self.spawner(), ctx, RunArgs{
+            report: Some(&mut report),
+            run_id: Some(run_id),
+            ..RunArgs::default()
+        }).map(|_| ());
+        report.finish(&self.frozen, self.clock.now());
+        return (result, report);
+    }
+
+    // the context is handed back on failure as well, so partial results
+    // gathered before the error are not lost
+    pub fn run_owned(&self, ctx: C) -> Result<C, (C, Error)> {
+        return match self.run(&ctx) {
+            Ok(_) => Ok(ctx),
+            Err(err) => Err((ctx, err)),
+        };
+    }
+
+    // Same as `run`, but tasks are spawned on plain 'static threads which
+    // hold their own clone of `ctx`. All spawned threads are still joined
+    // before returning. On wasm it is the same as `run`, every task running
+    // on the calling thread.
+    pub fn run_arc(&self, ctx: Arc<C>) -> Result<(), Error>
+        where
+            C: 'static
+    {
+        #[cfg(target_arch = "wasm32")]
+        return self.run(&ctx);
+        #[cfg(not(target_arch = "wasm32"))]
+        return self.run_arc_with(ctx, RunArgs::default());
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    fn run_arc_with(&self, ctx: Arc<C>, args: RunArgs<'_, C>) -> Result<(), Error>
+        where
+            C: 'static
+    {
+        let (sender, receiver) = mpsc::channel();
+        let mut handles = vec![];
+        // set once the run gave up on the nodes still running, which then
+        // report to `on_abandoned` instead
+        let abandoned = Arc::new(Mutex::new(false));
+
+        let result = panic::catch_unwind(panic::AssertUnwindSafe(|| self.schedule(&ctx, &receiver, args, |index, env| {
+            handles.push(self.spawn_detached(&ctx, &sender, &abandoned, index, env)?);
+            return Ok(());
+        })));
+        // a node panicked with `PanicPolicy::Abort`, raised again once the
+        // others are done, as `run` does
+        let result = match result {
+            Ok(result) => result.map(|_| ()),
+            Err(err) => {
+                for handle in handles {
+                    let _ = handle.join();
+                }
+                panic::resume_unwind(err);
+            },
+        };
+        if matches!(result, Err(Cancelled{..})) || (result.is_err() && self.abandon_in_flight) {
+            *abandoned.lock().unwrap_or_else(|err| err.into_inner()) = true;
+            while let Ok(result) = receiver.try_recv() {
+                if let Some(on_abandoned) = &self.on_abandoned {
+                    on_abandoned(&self.frozen.graph.nodes[result.index].name, status_of(&result, self.budget_of(result.index)));
+                }
+            }
+            return result;
+        }
+        for handle in handles {
+            let _ = handle.join();
+        }
+        return result;
+    }
+
+    // A thread of its own for the node at `index`, holding clones of `ctx`
+    // and of everything else it needs, so it may outlive the run.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn_detached(
+        &self,
+        ctx: &Arc<C>,
+        sender: &mpsc::Sender<RunningResult>,
+        abandoned: &Arc<Mutex<bool>>,
+        index: usize,
+        env: NodeEnv<C>,
+    ) -> Result<std::thread::JoinHandle<()>, Error>
+        where
+            C: 'static
+    {
+        let frozen = self.frozen.clone();
+        let metrics = self.metrics.clone();
+        let each = self.each.clone();
+        let clock = self.clock.clone();
+        let ctx = ctx.clone();
+        let sender = sender.clone();
+        let abandoned = abandoned.clone();
+        let on_abandoned = self.on_abandoned.clone();
+        let budgets: Vec<Option<Duration>> = std::iter::once(index)
+            .chain(env.then.iter().map(|(index, _)| *index))
+            .map(|index| self.budget_of(index))
+            .collect();
+        let f = move || {
+            let mut finished_at = None;
+            for ((index, env), budget) in env.chain(index).zip(budgets) {
+                if finished_at.is_some_and(|at| env.is_late(at)) {
+                    break;
+                }
+                let node = &frozen.graph.nodes[index];
+                let result = execute(node, &*ctx, &metrics, &each, env, &*clock);
+                let failed = !matches!(result.outcome, Outcome::Done);
+                finished_at = Some(result.finished_at);
+                let abandoned = abandoned.lock().unwrap_or_else(|err| err.into_inner());
+                if !*abandoned {
+                    let _ = sender.send(result);
+                } else if let Some(on_abandoned) = &on_abandoned {
+                    on_abandoned(&node.name, status_of(&result, budget));
+                }
+                if failed {
+                    break;
+                }
+            }
+        };
+        let name = &self.frozen.graph.nodes[index].name;
+        let handle = self.threads.builder(name).spawn(f).map_err(|err| SpawnFailed{
+            node: name.to_string(),
+            index: index,
+            err: SpawnError::from(err),
+        })?;
+        return Ok(handle);
+    }
+
+    // Starts a run which only makes progress while its caller steps it, see
+    // `Execution::step`, for callers which must never block for long, like
+    // a game loop or a GUI. The tasks run on threads of their own holding a
+    // clone of `ctx`, as with `run_arc`; inline and pinned nodes run within
+    // `step`, on the calling thread.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn start(&self, ctx: Arc<C>) -> Execution<'_, C>
+        where
+            C: 'static
+    {
+        let mut args = RunArgs::default();
+        let (log, started_at) = self.run_started(&mut args);
+        let drive = Drive::new(self, args, &log);
         let (sender, receiver) = mpsc::channel();
-        return Scheduler{
-            frozen: frozen,
+        return Execution{
+            scheduler: self,
+            ctx: ctx,
+            log: log,
+            started_at: started_at,
+            drive: Some(drive),
             sender: sender,
             receiver: receiver,
+            abandoned: Arc::new(Mutex::new(false)),
+            handles: vec![],
+        };
+    }
+
+    // Starts `run_arc` on a dedicated thread and returns at once. Dropping
+    // the returned handle detaches the run, it keeps going to completion.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn spawn_run(self: Arc<Self>, ctx: Arc<C>) -> RunHandle
+        where
+            C: 'static
+    {
+        let drain = Arc::new(AtomicBool::new(false));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let flags = (drain.clone(), cancel.clone());
+        let handle = thread::spawn(move || self.run_arc_with(ctx, RunArgs{
+            drain: Some(&flags.0),
+            cancel: Some(&flags.1),
+            ..RunArgs::default()
+        }));
+        return RunHandle{
+            handle: handle,
+            drain: drain,
+            cancel: cancel,
+        };
+    }
+
+    // Runs every task on the calling thread, one at a time. Among the ready
+    // nodes the one added to the graph first always goes first, so the
+    // execution order is the same for every run.
+    pub fn run_sequential(&self, ctx: &C) -> Result<(), Error> {
+        let (_sender, receiver) = mpsc::channel();
+        let args = RunArgs{
+            sequential: true,
+            ..RunArgs::default()
+        };
+        return self.schedule(ctx, &receiver, args, |_, _| {
+            unreachable!("sequential runs execute every node inline");
+        }).map(|_| ());
+    }
+
+    // Runs the `dirty` nodes and everything depending on them, in the
+    // usual order among themselves; all other nodes are taken as already
+    // done and are not run, counting as skipped.
+    pub fn run_dirty(&self, ctx: &C, dirty: &[&str]) -> Result<RunSummary, Error> {
+        let selected = self.frozen.descendants_mask(&self.indices_of(dirty)?);
+        return self.run_in(self.spawner(), ctx, RunArgs{
+            selected: Some(selected),
+            ..RunArgs::default()
+        });
+    }
+
+    // `run` with some of the options replaced for this run only.
+    pub fn run_with(&self, ctx: &C, options: RunOptions<C>) -> Result<RunSummary, Error> {
+        let mut selected = match &options.targets {
+            Some(targets) => Some(self.frozen.ancestors_mask(&self.indices_of(targets)?)),
+            None => None,
+        };
+        for index in self.indices_of(&options.skip)? {
+            selected.get_or_insert_with(|| vec![true; self.frozen.graph.nodes.len()])[index] = false;
+        }
+        let mut params = vec![];
+        for (name, overrides) in options.node_params.into_iter() {
+            let node = &self.frozen.graph.nodes[self.frozen.graph.index_of(&name)?];
+            let mut merged = node.params.clone();
+            merged.extend(overrides);
+            params.resize(self.frozen.graph.nodes.len(), None);
+            params[node.index] = Some(Arc::new(merged));
         }
+        let mut tasks = vec![];
+        for (name, task) in options.task_overrides.into_iter() {
+            let index = self.frozen.graph.index_of(&name)?;
+            tasks.resize(self.frozen.graph.nodes.len(), None);
+            tasks[index] = Some(task);
+        }
+        return self.run_in(self.spawner(), ctx, RunArgs{
+            selected: selected,
+            params: params,
+            failure_policy: options.failure_policy,
+            max_failures: options.max_failures,
+            max_concurrency: options.max_concurrency,
+            run_id: options.run_id,
+            priority: options.priority,
+            tasks: tasks,
+            ..RunArgs::default()
+        });
     }
 
-    // TODO:
-    //  - 1 thread for 1 task may not be very suitable for cases with
-    //      lots of small tasks, consider reusing threads.
-    //  - implement timeout control to prevent unfinishable tasks.
-    //
-    pub fn run(&self, ctx: &C) -> Result<(), Error> {
-        let root = RunningNode::new(&self.frozen.root);
-        let mut running_nodes: Vec<RunningNode> = self.frozen.graph.nodes.iter()
-            .map(|node| RunningNode::new(node)).collect();
-
-        return thread::scope(|s| -> Result<(), Error> {
-            let mut cursor = &root;
-            for _ in 0..running_nodes.len() {
-                let parent = if cursor.index == root.index {
-                    &self.frozen.root
-                } else {
-                    &self.frozen.graph.nodes[cursor.index]
-                };
-                for child_index in parent.childrens.iter() {
-                    let index = *child_index;
-                    let running_node = &mut running_nodes[index];
-                    running_node.n_unfinished -= 1;
-                    if running_node.n_unfinished > 0 {
-                        continue;
+    fn indices_of<S: AsRef<str>>(&self, names: &[S]) -> Result<Vec<usize>, Error> {
+        return names.iter().map(|name| self.frozen.graph.index_of(name.as_ref())).collect();
+    }
+
+    // Skips the nodes `checkpoint` already has as done for `run_id`, and
+    // records there every node that succeeds now, so that calling this
+    // again after a failure or a crash picks up where the run stopped.
+    // Names the graph does not know are ignored, the skipped nodes count as
+    // cached.
+    pub fn run_resumable(&self, ctx: &C, run_id: &str, checkpoint: &dyn Checkpoint) -> Result<RunSummary, Error> {
+        let done = checkpoint.load_done(run_id).map_err(|err| CheckpointFailed{
+            run_id: run_id.to_string(),
+            err: err,
+        })?;
+        let selected = self.frozen.graph.nodes.iter()
+            .map(|node| !done.contains(&*node.name))
+            .collect();
+        return self.run_in(self.spawner(), ctx, RunArgs{
+            selected: Some(selected),
+            checkpoint: Some((run_id, checkpoint)),
+            run_id: Some(RunId::new(run_id)),
+            ..RunArgs::default()
+        });
+    }
+
+    fn run_in(&self, spawner: &dyn Spawner, ctx: &C, args: RunArgs<'_, C>) -> Result<RunSummary, Error> {
+        // per-run state only, so that concurrent runs never share a channel
+        let (sender, receiver) = mpsc::channel();
+        let frozen = &*self.frozen;
+        let metrics = &*self.metrics;
+        let each = &self.each;
+        let clock = &*self.clock;
+        let sender = &sender;
+        let priority = args.priority;
+        let mut args = Some(args);
+        let mut result = None;
+
+        spawner.scope_with_priority(priority, &mut |scope| {
+            let args = args.take().expect("spawner ran the scheduling loop twice");
+            result = Some(self.schedule(ctx, &receiver, args, |index, env| {
+                let node = &frozen.graph.nodes[index];
+                let job = Box::new(move || {
+                    let mut finished_at = None;
+                    for (index, env) in env.chain(index) {
+                        if finished_at.is_some_and(|at| env.is_late(at)) {
+                            break;
+                        }
+                        let result = execute(&frozen.graph.nodes[index], ctx, metrics, each, env, clock);
+                        let failed = !matches!(result.outcome, Outcome::Done);
+                        finished_at = Some(result.finished_at);
+                        let _ = sender.send(result);
+                        if failed {
+                            break;
+                        }
                     }
-                    let task = &self.frozen.graph.nodes[index].task;
-                    let sender = &self.sender;
-                    let f = move || {
-                        let result = panic::catch_unwind(AssertUnwindSafe(|| {
-                            return task(ctx);
-                        }));
-                        let message = match result {
-                            Ok(v) => match v {
-                                Ok(_) => RunningResult::Done{index},
-                                Err(err) => RunningResult::Error{index, err},
-                            },
-                            Err(err) => RunningResult::Panic{index, err},
-                        };
-                        let _ = sender.send(message);
-                    };
-                    Builder::new()
-                        .name(self.frozen.graph.nodes[index].name.clone())
-                        .spawn_scoped(s, f)
-                        .unwrap();
-                }
-                cursor = match self.receiver.recv().unwrap() {
-                    RunningResult::Done{index} => &running_nodes[index],
-                    RunningResult::Error{index, err} => return Err(RuntimeFailed{
-                        node: self.frozen.graph.nodes[index].name.clone(),
-                        err: err,
-                    }),
-                    RunningResult::Panic{index, err} => return Err(RuntimePanicked{
-                        node: self.frozen.graph.nodes[index].name.clone(),
-                        err: err,
-                    }),
-                };
+                });
+                return scope.spawn(&node.name, job).map_err(|err| SpawnFailed{
+                    node: node.name.to_string(),
+                    index: index,
+                    err: err,
+                });
+            }));
+        });
+        return result.expect("spawner did not run the scheduling loop");
+    }
+
+}
+
+impl<C> Scheduler<C> {
+    fn spawner(&self) -> &dyn Spawner {
+        return match &self.spawner {
+            Some(spawner) => &**spawner,
+            None => &self.threads,
+        };
+    }
+
+    fn schedule<F>(
+        &self,
+        ctx: &C,
+        receiver: &mpsc::Receiver<RunningResult>,
+        mut args: RunArgs<'_, C>,
+        dispatch: F,
+    ) -> Result<RunSummary, Error>
+        where
+            F: FnMut(usize, NodeEnv<C>) -> Result<(), Error>
+    {
+        let (log, started_at) = self.run_started(&mut args);
+        let result = self.drive(ctx, receiver, args, &log, dispatch).map(|summary| RunSummary{
+            duration: self.clock.now().saturating_duration_since(started_at),
+            ..summary
+        });
+        self.run_finished(&log, started_at, &result);
+        return result;
+    }
+
+    fn run_started(&self, args: &mut RunArgs<'_, C>) -> (RunLog, Instant) {
+        let started_at = self.clock.now();
+        let log = RunLog::start(args.run_id.take().unwrap_or_else(RunId::next));
+        self.emit(Event::RunStarted{run_id: log.run_id()});
+        return (log, started_at);
+    }
+
+    fn run_finished(&self, log: &RunLog, started_at: Instant, result: &Result<RunSummary, Error>) {
+        if let Some(scratch) = &self.scratch {
+            scratch.remove_run_dir(log.run_id());
+        }
+        log.finish(self.clock.now().saturating_duration_since(started_at), result.as_ref().err());
+        self.emit(Event::RunFinished{run_id: log.run_id(), succeeded: result.is_ok()});
+    }
+
+    fn check_saturation(&self, depth: u64, saturated: &mut bool, log: &RunLog) {
+        if self.max_ready_queue == 0 {
+            return;
+        }
+        let over = depth > self.max_ready_queue as u64;
+        if over && !*saturated {
+            self.emit(Event::QueueSaturated{run_id: log.run_id(), depth: depth as usize});
+        }
+        *saturated = over;
+    }
+
+    fn node_ready(&self, index: usize, report: Option<&mut RunReport>, log: &RunLog) {
+        let at = self.clock.now();
+        if let Some(report) = report {
+            report.ready(index, at);
+        }
+        self.emit(Event::NodeReady{run_id: log.run_id(), name: &self.frozen.graph.nodes[index].name, at: at});
+    }
+
+    fn emit(&self, event: Event<'_>) {
+        for observer in self.observers.iter() {
+            observer.on_event(&event);
+        }
+    }
+
+    // Drives a run: `dispatch` is called once for every non-inline node
+    // whose parents have all finished, and must eventually send its result
+    // to `receiver`; inline nodes are executed right here after all their
+    // siblings have been dispatched. Every wakeup drains all completions
+    // that are already queued, so that a burst of finished tasks gets its
+    // children dispatched in one pass.
+    fn drive<F>(
+        &self,
+        ctx: &C,
+        receiver: &mpsc::Receiver<RunningResult>,
+        args: RunArgs<'_, C>,
+        log: &RunLog,
+        mut dispatch: F,
+    ) -> Result<RunSummary, Error>
+        where
+            F: FnMut(usize, NodeEnv<C>) -> Result<(), Error>
+    {
+        let mut drive = Drive::new(self, args, log);
+        while drive.turn(ctx, receiver, log, &mut dispatch, None)? {}
+        return drive.finish();
+    }
+
+    // what a node whose gate timed out fails with, it never started
+    fn gate_timeout_result(&self, index: usize, since: Instant, until: Instant) -> RunningResult {
+        let timeout = self.gate_timeout.unwrap_or_default();
+        return RunningResult{
+            index: index,
+            started_at: since,
+            finished_at: until,
+            fallback_used: false,
+            outcome: Outcome::Error{err: Box::new(TimeoutError::new(timeout, until.saturating_duration_since(since)))},
+            output: None,
+        };
+    }
+
+    // Marks everything reachable from the failed node as skipped and
+    // returns how many nodes were newly marked among the selected ones.
+    fn skip_descendants(
+        &self,
+        index: usize,
+        skipped: &mut [bool],
+        is_selected: &dyn Fn(usize) -> bool,
+        mut report: Option<&mut RunReport>,
+    ) -> usize {
+        let mut stack = vec![index];
+        let mut n_skipped = 0;
+        while let Some(index) = stack.pop() {
+            for child_index in self.frozen.childrens_of(index) {
+                let child_index = *child_index as usize;
+                if skipped[child_index] {
+                    continue;
+                }
+                skipped[child_index] = true;
+                stack.push(child_index);
+                if !is_selected(child_index) {
+                    continue;
+                }
+                n_skipped += 1;
+                if let Some(report) = report.as_deref_mut() {
+                    report.skip(child_index);
+                }
             }
+        }
+        return n_skipped;
+    }
+
+    // the longest path of estimated costs starting at every node
+    // from the cost model if it knows the node, from the graph otherwise
+    fn estimates(&self) -> Vec<Duration> {
+        let graph = &self.frozen.graph;
+        let model = self.cost_model.as_ref().map(|model| model.lock().unwrap_or_else(|err| err.into_inner()));
+        return graph.nodes.iter().map(|node| {
+            return match model.as_ref().and_then(|model| model.estimate(&node.name)) {
+                Some(cost) => cost,
+                None => graph.cost_of(node.index),
+            };
+        }).collect();
+    }
+
+    fn remaining_costs(&self) -> Vec<Duration> {
+        let mut remaining = self.estimates();
+        for index in self.frozen.topological_order().into_iter().rev() {
+            let longest = self.frozen.childrens_of(index).iter()
+                .map(|child_index| remaining[*child_index as usize])
+                .max().unwrap_or(Duration::ZERO);
+            remaining[index] += longest;
+        }
+        return remaining;
+    }
+
+    fn env_of(&self, index: usize, log: &RunLog, params: &[Option<Arc<HashMap<String, String>>>], tasks: &[Option<Arc<dyn TaskRun<C>>>], run_scratch: &Option<RunScratch>) -> NodeEnv<C> {
+        return NodeEnv{
+            scratch: self.scratch.as_ref().map(|scratch| scratch.node(log.run_id(), index, &self.frozen.graph.nodes[index].name)),
+            params: params.get(index).cloned().flatten(),
+            task: tasks.get(index).cloned().flatten(),
+            run_scratch: run_scratch.clone(),
+            then: vec![],
+            latest_start: None,
+        };
+    }
+
+    // the longest a node may take before it is over budget
+    fn budget_of(&self, index: usize) -> Option<Duration> {
+        let cost = self.frozen.graph.cost_of(index);
+        if self.slow_task_factor == 0.0 || cost.is_zero() {
+            return None;
+        }
+        return Some(cost.mul_f64(self.slow_task_factor));
+    }
+
+    // index of the finished node, or the error the node failed with
+    fn finish(&self, result: RunningResult, report: Option<&mut RunReport>, log: &RunLog) -> Result<usize, (usize, Error)> {
+        let status = status_of(&result, self.budget_of(result.index));
+        let RunningResult{index, started_at, finished_at, fallback_used, outcome, ..} = result;
+        let name = &self.frozen.graph.nodes[index].name;
+        if let NodeStatus::Succeeded{duration, over_budget: true} = status {
+            let budget = self.budget_of(index).unwrap_or_default();
+            log.node_slow(name, duration, budget);
+            self.emit(Event::NodeSlow{
+                run_id: log.run_id(),
+                name: name,
+                duration: duration,
+                budget: budget,
+            });
+        }
+        if !self.observers.is_empty() {
+            self.emit(Event::NodeFinished{
+                run_id: log.run_id(),
+                name: name,
+                status: status.clone(),
+                duration: finished_at - started_at,
+            });
+        }
+        if let Some(report) = report {
+            report.record(index, status, started_at, finished_at, fallback_used, error_of(&outcome));
+        }
+        let node = || name.to_string();
+        let err = match outcome {
+            Outcome::Panic{err, ..} if self.frozen.graph.nodes[index].panic_policy == PanicPolicy::Abort => {
+                std::panic::resume_unwind(err);
+            },
+            Outcome::Done => {
+                if let (Some(model), false) = (&self.cost_model, fallback_used) {
+                    model.lock().unwrap_or_else(|err| err.into_inner()).record(name, finished_at - started_at);
+                }
+                log.node_finished(name, finished_at - started_at, fallback_used);
+                return Ok(index);
+            },
+            Outcome::Error{err} if err.is::<TimeoutError>() => {
+                let timeout = err.downcast_ref::<TimeoutError>().unwrap();
+                RuntimeTimedOut{
+                    node: node(),
+                    index: index,
+                    limit: timeout.limit,
+                    elapsed: timeout.elapsed,
+                    in_flight: vec![],
+                    run_id: log.run_id().clone(),
+                }
+            },
+            Outcome::Error{err} => RuntimeFailed{
+                node: node(),
+                index: index,
+                err: err,
+                in_flight: vec![],
+                run_id: log.run_id().clone(),
+            },
+            Outcome::Panic{err, info} if self.panics_as_errors => RuntimeFailed{
+                node: node(),
+                index: index,
+                err: Box::new(PanickedTaskError::new(err, info)),
+                in_flight: vec![],
+                run_id: log.run_id().clone(),
+            },
+            Outcome::Panic{err, info} => RuntimePanicked{
+                node: node(),
+                index: index,
+                err: err,
+                info: info,
+                in_flight: vec![],
+                run_id: log.run_id().clone(),
+            },
+        };
+        log.node_failed(&err);
+        return Err((index, err));
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<C: Send + Sync> Scheduler<C> {
+    pub fn run_on_rayon(&self, ctx: &C) -> Result<(), Error> {
+        return self.run_in(&crate::spawner::RayonSpawner::default(), ctx, RunArgs::default()).map(|_| ());
+    }
+
+    // The scheduling loop itself blocks the calling thread, so call this
+    // from outside of `pool`, otherwise one of its workers is lost for the
+    // whole run.
+    pub fn run_on_pool(&self, pool: &rayon::ThreadPool, ctx: &C) -> Result<(), Error> {
+        return self.run_in(&crate::spawner::PoolSpawner(pool), ctx, RunArgs::default()).map(|_| ());
+    }
+}
+
+#[cfg(feature = "signals")]
+impl<C: Send + Sync> Scheduler<C> {
+    // Same as `run`, but SIGINT or SIGTERM drain the run like
+    // `RunHandle::drain`. A second signal terminates the process the way
+    // the first one would have without this: the running tasks borrow
+    // `ctx`, so the run itself can not return before they are done. Outside
+    // of such runs signals terminate as usual, handlers installed by the
+    // application are still called but no longer prevent terminating.
+    pub fn run_with_signal_drain(&self, ctx: &C) -> Result<(), Error> {
+        let signals = crate::signals::SignalDrain::install();
+        return self.run_in(self.spawner(), ctx, RunArgs{
+            drain: Some(signals.flag()),
+            ..RunArgs::default()
+        }).map(|_| ());
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub struct RunHandle {
+    handle: thread::JoinHandle<Result<(), Error>>,
+    drain: Arc<AtomicBool>,
+    cancel: Arc<AtomicBool>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl RunHandle {
+    // Stops dispatching nodes, the ones running are let finish. The run
+    // then fails with `Drained`, unless nothing was left to dispatch, or
+    // with the failures of the run if there were any.
+    pub fn drain(&self) {
+        self.drain.store(true, Ordering::Relaxed);
+    }
+
+    // Stops the run as soon as the scheduling loop sees it, without waiting
+    // for the nodes still running: they are left to finish on their own
+    // threads and reported to `SchedulerBuilder::on_abandoned`, if set. The
+    // run then fails with `Cancelled`, whatever else happened before.
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.handle.is_finished();
+    }
+
+    pub fn join(self) -> Result<(), Error> {
+        return match self.handle.join() {
+            Ok(result) => result,
+            Err(err) => panic::resume_unwind(err),
+        };
+    }
+}
+
+// What `Execution::step` left the run in.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Debug)]
+pub enum StepStatus {
+    Running,
+    Finished(Result<(), Error>),
+}
+
+// A run started by `Scheduler::start`. Dropping it before the run finished
+// drains it: nothing more is dispatched, the tasks still running are left
+// to finish on their own threads, and reported to
+// `SchedulerBuilder::on_abandoned`, if set, instead.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct Execution<'s, C: Send + Sync + 'static> {
+    scheduler: &'s Scheduler<C>,
+    ctx: Arc<C>,
+    log: RunLog,
+    started_at: Instant,
+    // `None` once finished
+    drive: Option<Drive<'s, C>>,
+    sender: mpsc::Sender<RunningResult>,
+    receiver: mpsc::Receiver<RunningResult>,
+    abandoned: Arc<Mutex<bool>>,
+    // the threads which may still be running, joined before a panic of
+    // `PanicPolicy::Abort` leaves `step`
+    handles: Vec<thread::JoinHandle<()>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<C: Send + Sync + 'static> Execution<'_, C> {
+    // Dispatches the nodes which became ready and handles the results which
+    // came in, waiting for more for up to `budget`, then returns. Inline
+    // nodes are executed here and may take longer than that. Panics if the
+    // run was reported finished already.
+    pub fn step(&mut self, budget: Duration) -> StepStatus {
+        let until = Instant::now() + budget;
+        let Execution{scheduler, ctx, log, sender, receiver, abandoned, handles, ..} = self;
+        let drive = self.drive.as_mut().expect("step called after the run finished");
+        handles.retain(|handle| !handle.is_finished());
+        let mut dispatch = |index, env| {
+            handles.push(scheduler.spawn_detached(ctx, sender, abandoned, index, env)?);
             return Ok(());
+        };
+        let turns = panic::catch_unwind(panic::AssertUnwindSafe(|| loop {
+            match drive.turn(&**ctx, receiver, log, &mut dispatch, Some(until)) {
+                Ok(true) if drive.n_finished == drive.n_node => return Ok(false),
+                Ok(true) if Instant::now() < until => continue,
+                Ok(running) => return Ok(running),
+                Err(err) => return Err(err),
+            }
+        }));
+        match turns {
+            Ok(Ok(true)) => return StepStatus::Running,
+            Ok(Ok(false)) => {},
+            Ok(Err(err)) => return StepStatus::Finished(self.finish(Err(err))),
+            Err(err) => {
+                for handle in self.handles.drain(..) {
+                    let _ = handle.join();
+                }
+                panic::resume_unwind(err);
+            },
+        }
+        let result = self.drive.take().unwrap().finish();
+        return StepStatus::Finished(self.finish(result));
+    }
+
+    pub fn is_finished(&self) -> bool {
+        return self.drive.is_none();
+    }
+
+    fn finish(&mut self, result: Result<RunSummary, Error>) -> Result<(), Error> {
+        self.drive = None;
+        if result.is_err() {
+            *self.abandoned.lock().unwrap_or_else(|err| err.into_inner()) = true;
+            let s = self.scheduler;
+            while let Ok(result) = self.receiver.try_recv() {
+                if let Some(on_abandoned) = &s.on_abandoned {
+                    on_abandoned(&s.frozen.graph.nodes[result.index].name, status_of(&result, s.budget_of(result.index)));
+                }
+            }
+        }
+        let result = result.map(|summary| RunSummary{
+            duration: self.scheduler.clock.now().saturating_duration_since(self.started_at),
+            ..summary
         });
+        self.scheduler.run_finished(&self.log, self.started_at, &result);
+        return result.map(|_| ());
     }
 }
 
-struct RunningNode {
-    index: usize,
-    n_unfinished: usize,
+#[cfg(not(target_arch = "wasm32"))]
+impl<C: Send + Sync + 'static> Drop for Execution<'_, C> {
+    fn drop(&mut self) {
+        if let Some(mut drive) = self.drive.take() {
+            drive.drained = true;
+            let _ = self.finish(drive.finish());
+        }
+    }
 }
 
-impl RunningNode {
-    fn new<C>(node: &Node<C>) -> RunningNode {
-        RunningNode{
-            index: node.index,
-            n_unfinished: node.parent_count,
+// how long a run which may be cancelled waits for results at most before
+// looking at its cancel flag again
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// The state of a run between two turns of its scheduling loop, see
+// `Scheduler::drive`. A turn dispatches whatever is ready, executes the
+// inline nodes and then handles the results which came in meanwhile,
+// waiting for at least one unless given a time to stop waiting at.
+struct Drive<'r, C> {
+    scheduler: &'r Scheduler<C>,
+    started_at: Instant,
+    n_node: usize,
+    report: Option<&'r mut RunReport>,
+    sequential: bool,
+    selected: Option<Vec<bool>>,
+    checkpoint: Option<(&'r str, &'r dyn Checkpoint)>,
+    drain: Option<&'r AtomicBool>,
+    cancel: Option<&'r AtomicBool>,
+    outputs: Option<&'r mut Vec<Option<Output>>>,
+    params: Vec<Option<Arc<HashMap<String, String>>>>,
+    tasks: Vec<Option<Arc<dyn TaskRun<C>>>>,
+    run_scratch: Option<RunScratch>,
+    continue_on_error: bool,
+    max_failures: usize,
+    max_in_flight: usize,
+    max_total_weight: u64,
+    in_flight_weight: u64,
+    class_running: Vec<usize>,
+    class_waiting: Vec<VecDeque<usize>>,
+    n_unfinished: Vec<u32>,
+    n_finished: usize,
+    ready: ReadyQueue,
+    // the estimate of every node, and of the longest path ahead of it
+    estimates: Option<(Vec<Duration>, Vec<Duration>)>,
+    n_hopeless: usize,
+    expired: bool,
+    skipped: Vec<bool>,
+    failures: Vec<Error>,
+    inline: Vec<usize>,
+    results: Vec<RunningResult>,
+    n_in_flight: usize,
+    aborted: bool,
+    drained: bool,
+    dispatched: Vec<bool>,
+    // dispatched, their results not processed yet
+    in_flight: Vec<bool>,
+    running: HashMap<usize, (Instant, u32)>,
+    // ready nodes whose gate is closed, with when they became ready;
+    // once open or timed out they are ready again and let through
+    gated: Vec<(usize, Instant)>,
+    gate_passed: Vec<bool>,
+    gate_timed_out: HashMap<usize, (Instant, Instant)>,
+    gauge: ReadyGauge<'r>,
+    saturated: bool,
+    _hook: HookGuard,
+}
+
+fn is_selected(selected: &Option<Vec<bool>>, index: usize) -> bool {
+    return selected.as_ref().is_none_or(|selected| selected[index]);
+}
+
+impl<'r, C> Drive<'r, C> {
+    fn new(scheduler: &'r Scheduler<C>, args: RunArgs<'r, C>, log: &RunLog) -> Drive<'r, C> {
+        let s = scheduler;
+        let frozen = &*s.frozen;
+        let n_node = frozen.graph.nodes.len();
+        let RunArgs{mut report, sequential, selected, checkpoint, mut outputs, params, tasks, run_scratch, ..} = args;
+        if let Some(outputs) = outputs.as_deref_mut() {
+            outputs.resize_with(n_node, || None);
         }
+        let continue_on_error = args.failure_policy.unwrap_or(s.failure_policy) == FailurePolicy::ContinueOnError;
+        let max_in_flight = match (sequential, args.max_concurrency.unwrap_or(s.max_concurrency)) {
+            (true, _) => 1,
+            (false, 0) => usize::MAX,
+            (false, n) => n,
+        };
+        let mut n_unfinished = frozen.in_degrees.clone();
+        let mut n_finished: usize = 0;
+        let initial: Vec<usize> = match &selected {
+            None => frozen.roots.clone(),
+            // the nodes left out are finished before the run even starts
+            Some(selected) => {
+                for index in (0..n_node).filter(|index| !selected[*index]) {
+                    n_finished += 1;
+                    for child_index in frozen.childrens_of(index) {
+                        n_unfinished[*child_index as usize] -= 1;
+                    }
+                }
+                (0..n_node).filter(|index| selected[*index] && n_unfinished[*index] == 0).collect()
+            },
+        };
+        for index in initial.iter() {
+            s.node_ready(*index, report.as_deref_mut(), log);
+        }
+        let ready = match s.dispatch_strategy {
+            DispatchStrategy::CriticalPath => ReadyQueue::by_priority(s.remaining_costs(), initial),
+            strategy => ReadyQueue::new(sequential || strategy == DispatchStrategy::InsertionOrder, initial),
+        };
+        let estimates = match (s.deadline, s.skip_hopeless) {
+            (Some(_), true) => Some((s.estimates(), s.remaining_costs())),
+            _ => None,
+        };
+        let _hook = HookGuard::install();
+        let mut gauge = s.metrics.ready_queue();
+        s.metrics.run_started();
+        gauge.push(ready.len());
+        let mut drive = Drive{
+            scheduler: s,
+            started_at: s.clock.now(),
+            n_node: n_node,
+            report: report,
+            sequential: sequential,
+            selected: selected,
+            checkpoint: checkpoint,
+            drain: args.drain,
+            cancel: args.cancel,
+            outputs: outputs,
+            params: params,
+            tasks: tasks,
+            run_scratch: run_scratch,
+            continue_on_error: continue_on_error,
+            max_failures: args.max_failures.unwrap_or(s.max_failures),
+            max_in_flight: max_in_flight,
+            max_total_weight: match s.max_total_weight {
+                0 => u64::MAX,
+                n => n,
+            },
+            in_flight_weight: 0,
+            class_running: vec![0usize; s.class_limits.len()],
+            class_waiting: vec![VecDeque::new(); s.class_limits.len()],
+            n_unfinished: n_unfinished,
+            n_finished: n_finished,
+            ready: ready,
+            skipped: vec![false; if continue_on_error || estimates.is_some() { n_node } else { 0 }],
+            estimates: estimates,
+            n_hopeless: 0,
+            expired: false,
+            failures: vec![],
+            inline: vec![],
+            results: vec![],
+            n_in_flight: 0,
+            aborted: false,
+            drained: false,
+            dispatched: vec![false; n_node],
+            in_flight: vec![false; n_node],
+            running: HashMap::new(),
+            gated: vec![],
+            gate_passed: vec![false; if frozen.graph.nodes.iter().any(|node| node.gate.is_some()) { n_node } else { 0 }],
+            gate_timed_out: HashMap::new(),
+            gauge: gauge,
+            saturated: false,
+            _hook: _hook,
+        };
+        s.check_saturation(drive.gauge.depth(), &mut drive.saturated, log);
+        return drive;
+    }
+
+    fn elapsed(&self) -> Duration {
+        return self.scheduler.clock.now().saturating_duration_since(self.started_at);
     }
+
+    fn class_of(&self, index: usize) -> Option<usize> {
+        return self.scheduler.node_classes.get(index).copied().flatten();
+    }
+
+    // One turn of the loop, false once the run is over. Without `until`, it
+    // blocks until a result comes in, with it no longer than until then, in
+    // real time whatever the clock of the scheduler.
+    fn turn<F>(
+        &mut self,
+        ctx: &C,
+        receiver: &mpsc::Receiver<RunningResult>,
+        log: &RunLog,
+        dispatch: &mut F,
+        until: Option<Instant>,
+    ) -> Result<bool, Error>
+        where
+            F: FnMut(usize, NodeEnv<C>) -> Result<(), Error>
+    {
+        let s = self.scheduler;
+        let frozen = &*s.frozen;
+        if self.n_finished == self.n_node {
+            return Ok(false);
+        }
+        if self.cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+            return Err(self.cancelled());
+        }
+        self.drained = self.drained || self.drain.is_some_and(|drain| drain.load(Ordering::Relaxed));
+        self.expired = self.expired || s.deadline.is_some_and(|deadline| self.elapsed() >= deadline);
+        if (self.aborted || self.drained || self.expired) && self.n_in_flight == 0 {
+            return Ok(false);
+        }
+        if !self.gated.is_empty() {
+            let now = s.clock.now();
+            let (ready, gate_passed, gate_timed_out) = (&mut self.ready, &mut self.gate_passed, &mut self.gate_timed_out);
+            self.gated.retain(|(index, since)| {
+                let timed_out = s.gate_timeout.is_some_and(|timeout| now.saturating_duration_since(*since) >= timeout);
+                if !timed_out && !frozen.graph.nodes[*index].gate.as_ref().is_some_and(|gate| gate.is_open()) {
+                    return true;
+                }
+                if timed_out {
+                    gate_timed_out.insert(*index, (*since, now));
+                }
+                gate_passed[*index] = true;
+                ready.push(*index);
+                return false;
+            });
+        }
+        while !self.aborted && !self.drained && !self.expired && self.n_in_flight < self.max_in_flight {
+            let Some(index) = self.ready.peek() else {
+                break;
+            };
+            if let (Some(deadline), Some((own, ahead))) = (s.deadline, &self.estimates) {
+                let remaining = deadline.saturating_sub(self.elapsed());
+                // nodes without an estimate of their own always run
+                if !own[index].is_zero() && ahead[index] > remaining {
+                    let ahead = ahead[index];
+                    self.ready.pop();
+                    self.gauge.pop();
+                    log.node_hopeless(&frozen.graph.nodes[index].name, ahead, remaining);
+                    if let Some(report) = self.report.as_deref_mut() {
+                        report.miss_deadline(index, ahead, remaining);
+                    }
+                    self.skipped[index] = true;
+                    self.n_hopeless += 1;
+                    let selected = &self.selected;
+                    self.n_finished += 1 + s.skip_descendants(index, &mut self.skipped, &|index| is_selected(selected, index), None);
+                    continue;
+                }
+            }
+            if let Some(gate) = &frozen.graph.nodes[index].gate {
+                if !self.gate_passed[index] && !gate.is_open() {
+                    self.ready.pop();
+                    self.gated.push((index, s.clock.now()));
+                    continue;
+                }
+                self.gate_passed[index] = true;
+            }
+            if let Some(class) = self.class_of(index) {
+                if self.class_running[class] == s.class_limits[class] {
+                    self.ready.pop();
+                    self.class_waiting[class].push_back(index);
+                    continue;
+                }
+            }
+            // the queue is not searched for a lighter node, which could
+            // keep a heavy one waiting forever
+            let weight = frozen.graph.nodes[index].weight;
+            if self.in_flight_weight > 0 && self.in_flight_weight.saturating_add(weight) > self.max_total_weight {
+                break;
+            }
+            self.ready.pop();
+            self.dispatched[index] = true;
+            self.in_flight[index] = true;
+            self.in_flight_weight += weight;
+            if let Some(class) = self.class_of(index) {
+                self.class_running[class] += 1;
+            }
+            self.n_in_flight += 1;
+            if let Some((since, until)) = self.gate_timed_out.remove(&index) {
+                self.gauge.pop();
+                self.results.push(s.gate_timeout_result(index, since, until));
+                continue;
+            }
+            if self.sequential || frozen.graph.nodes[index].inline || frozen.graph.nodes[index].pinned {
+                self.inline.push(index);
+                continue;
+            }
+            if let Some(report) = self.report.as_deref_mut() {
+                report.assign_worker(index);
+            }
+            log.node_started(&frozen.graph.nodes[index].name);
+            s.emit(Event::NodeStarted{run_id: log.run_id(), name: &frozen.graph.nodes[index].name});
+            let mut env = s.env_of(index, log, &self.params, &self.tasks, &self.run_scratch);
+            let mut last = index;
+            while let Some(next) = frozen.fused_next(last).filter(|next| is_selected(&self.selected, *next)) {
+                self.dispatched[next] = true;
+                self.in_flight[next] = true;
+                self.in_flight_weight += frozen.graph.nodes[next].weight;
+                let mut next_env = s.env_of(next, log, &self.params, &self.tasks, &self.run_scratch);
+                next_env.latest_start = self.latest_start(next);
+                env.then.push((next, next_env));
+                last = next;
+            }
+            dispatch(index, env)?;
+            self.gauge.pop();
+            if s.watchdog.is_some() {
+                self.running.insert(index, (s.clock.now(), 0));
+            }
+        }
+        for index in std::mem::take(&mut self.inline) {
+            self.gauge.pop();
+            if let Some(report) = self.report.as_deref_mut() {
+                report.assign_worker(index);
+            }
+            log.node_started(&frozen.graph.nodes[index].name);
+            s.emit(Event::NodeStarted{run_id: log.run_id(), name: &frozen.graph.nodes[index].name});
+            let env = s.env_of(index, log, &self.params, &self.tasks, &self.run_scratch);
+            self.results.push(execute(&frozen.graph.nodes[index], ctx, &s.metrics, &s.each, env, &*s.clock));
+        }
+        // a cancel is only seen between two turns
+        let until = match self.cancel {
+            Some(_) => {
+                let poll = Instant::now() + CANCEL_POLL_INTERVAL;
+                Some(until.map_or(poll, |until| until.min(poll)))
+            },
+            None => until,
+        };
+        let wait = |at_most: Duration| match until {
+            None => at_most,
+            Some(until) => at_most.min(until.saturating_duration_since(Instant::now())),
+        };
+        if self.results.is_empty() && !self.gated.is_empty() {
+            // look at the gates again in a while, or once a node finishes
+            if self.n_in_flight == 0 {
+                s.clock.sleep(wait(s.gate_poll_interval));
+            } else if let Ok(result) = receiver.recv_timeout(wait(s.gate_poll_interval)) {
+                self.results.push(result);
+            }
+        } else if self.results.is_empty() && self.n_in_flight > 0 {
+            // nothing to wait for otherwise, e.g. when the last ready node
+            // was skipped as hopeless
+            let result = match (&s.watchdog, until) {
+                (Some(watchdog), _) => watchdog.wait(receiver, frozen, &s.metrics, &*s.clock, &mut self.running, until),
+                (None, None) => receiver.recv().ok(),
+                (None, Some(_)) => receiver.recv_timeout(wait(Duration::MAX)).ok(),
+            };
+            self.results.extend(result);
+        }
+        while let Ok(result) = receiver.try_recv() {
+            self.results.push(result);
+        }
+        if !self.running.is_empty() {
+            for result in self.results.iter() {
+                self.running.remove(&result.index);
+            }
+        }
+        for index in self.results.iter().map(|result| result.index) {
+            self.in_flight_weight -= frozen.graph.nodes[index].weight;
+            if let Some(class) = self.class_of(index) {
+                self.class_running[class] -= 1;
+                if let Some(index) = self.class_waiting[class].pop_front() {
+                    self.ready.push(index);
+                }
+            }
+        }
+        self.n_finished += self.results.len();
+        for mut result in std::mem::take(&mut self.results) {
+            self.in_flight[result.index] = false;
+            if let Some(outputs) = self.outputs.as_deref_mut() {
+                outputs[result.index] = result.output.take();
+            }
+            let finished_at = result.finished_at;
+            let finished = s.finish(result, self.report.as_deref_mut(), log);
+            match &finished {
+                // the rest of its chain is never run
+                Err((index, _)) => self.unchain(frozen.fused_next(*index)),
+                // the same check as on the worker, the rest of the chain
+                // goes through the ready queue instead
+                Ok(index) => {
+                    let next = frozen.fused_next(*index).filter(|next| self.in_flight[*next]);
+                    if next.and_then(|next| self.latest_start(next)).is_some_and(|latest_start| finished_at > latest_start) {
+                        self.unchain(next);
+                    }
+                },
+            }
+            // a chain takes a single slot, free once its worker is done
+            let (Ok(index) | Err((index, _))) = &finished;
+            if frozen.fused_next(*index).is_none_or(|next| !self.in_flight[next]) {
+                self.n_in_flight -= 1;
+            }
+            let index = match finished {
+                Ok(index) => {
+                    if let Some((run_id, checkpoint)) = self.checkpoint {
+                        checkpoint.mark_done(run_id, &frozen.graph.nodes[index].name)
+                            .map_err(|err| CheckpointFailed{
+                                run_id: run_id.to_string(),
+                                err: err,
+                            })?;
+                    }
+                    index
+                },
+                Err((index, err)) if self.continue_on_error => {
+                    self.failures.push(err);
+                    let selected = &self.selected;
+                    self.n_finished += s.skip_descendants(index, &mut self.skipped, &|index| is_selected(selected, index), self.report.as_deref_mut());
+                    self.aborted = self.aborted || self.failures.len() == self.max_failures;
+                    continue;
+                },
+                Err((_, err)) => {
+                    let names = (0..self.n_node).filter(|index| self.in_flight[*index])
+                        .map(|index| frozen.graph.nodes[index].name.to_string())
+                        .collect();
+                    return Err(err.with_in_flight(names));
+                },
+            };
+            for child_index in frozen.childrens_of(index) {
+                let child_index = *child_index as usize;
+                self.n_unfinished[child_index] -= 1;
+                if self.n_unfinished[child_index] == 0 && is_selected(&self.selected, child_index)
+                    && !(self.continue_on_error && self.skipped[child_index]) {
+                    s.node_ready(child_index, self.report.as_deref_mut(), log);
+                    if !self.dispatched[child_index] {
+                        self.ready.push(child_index);
+                        self.gauge.push(1);
+                        continue;
+                    }
+                    // fused, already on its way on the worker of its parent
+                    if let Some(report) = self.report.as_deref_mut() {
+                        report.assign_worker(child_index);
+                    }
+                    log.node_started(&frozen.graph.nodes[child_index].name);
+                    s.emit(Event::NodeStarted{run_id: log.run_id(), name: &frozen.graph.nodes[child_index].name});
+                    if s.watchdog.is_some() {
+                        self.running.insert(child_index, (s.clock.now(), 0));
+                    }
+                }
+            }
+        }
+        s.check_saturation(self.gauge.depth(), &mut self.saturated, log);
+        return Ok(true);
+    }
+
+    // Past when the node at `index` may still be started on the worker of
+    // its chain: the deadline, or the last moment it is not yet hopeless if
+    // it has an estimate of its own.
+    fn latest_start(&self, index: usize) -> Option<Instant> {
+        let deadline = self.scheduler.deadline?;
+        let ahead = match &self.estimates {
+            Some((own, ahead)) if !own[index].is_zero() => ahead[index],
+            _ => Duration::ZERO,
+        };
+        return Some(self.started_at + deadline.saturating_sub(ahead));
+    }
+
+    // the nodes of a chain from `next` on, which its worker does not run
+    fn unchain(&mut self, mut next: Option<usize>) {
+        let frozen = &*self.scheduler.frozen;
+        while let Some(index) = next.filter(|next| self.in_flight[*next]) {
+            self.dispatched[index] = false;
+            self.in_flight[index] = false;
+            self.in_flight_weight -= frozen.graph.nodes[index].weight;
+            next = frozen.fused_next(index);
+        }
+    }
+
+    // the nodes whose results came in, and all others
+    fn cancelled(&self) -> Error {
+        let frozen = &*self.scheduler.frozen;
+        let names = |completed: bool| -> Vec<String> {
+            return (0..self.n_node)
+                .filter(|index| is_selected(&self.selected, *index))
+                .filter(|index| (self.dispatched[*index] && !self.in_flight[*index]) == completed)
+                .map(|index| frozen.graph.nodes[index].name.to_string())
+                .collect();
+        };
+        return Cancelled{
+            completed: names(true),
+            pending: names(false),
+        };
+    }
+
+    fn finish(self) -> Result<RunSummary, Error> {
+        let frozen = &*self.scheduler.frozen;
+        let n_node = self.n_node;
+        if !self.failures.is_empty() {
+            return Err(MultipleFailures{
+                failures: self.failures,
+                aborted: self.aborted,
+            });
+        }
+        // without failures, only a drain or the deadline stops a run early
+        let names = |was_dispatched: bool| -> Vec<String> {
+            return (0..n_node)
+                .filter(|index| is_selected(&self.selected, *index) && self.dispatched[*index] == was_dispatched)
+                .map(|index| frozen.graph.nodes[index].name.to_string())
+                .collect();
+        };
+        if self.n_hopeless > 0 || (self.expired && self.n_finished < n_node) {
+            return Err(DeadlineExceeded{
+                deadline: self.scheduler.deadline.unwrap_or_default(),
+                completed: names(true),
+                not_run: names(false),
+            });
+        }
+        if self.n_finished < n_node {
+            return Err(Drained{
+                completed: names(true),
+                not_run: names(false),
+            });
+        }
+        let counted = |index: &usize| !frozen.graph.nodes[*index].barrier;
+        let n_left_out = (0..n_node).filter(counted).filter(|index| !is_selected(&self.selected, *index)).count();
+        return Ok(RunSummary{
+            executed: (0..n_node).filter(counted).filter(|index| is_selected(&self.selected, *index)).count(),
+            skipped: if self.checkpoint.is_none() { n_left_out } else { 0 },
+            cached: if self.checkpoint.is_some() { n_left_out } else { 0 },
+            duration: Duration::ZERO,
+        });
+    }
+}
+
+// Runs the task of `node` between its hooks and the scheduler-wide ones,
+// falling back to its fallback
+// task if it fails. When both fail, the error of the fallback is the one
+// reported. The scratch directories of the node are gone once this returns,
+// unless kept for a failure.
+fn execute<C>(node: &Node<C>, ctx: &C, metrics: &SchedulerMetrics, each: &EachHooks<C>, env: NodeEnv<C>, clock: &dyn Clock) -> RunningResult {
+    let NodeEnv{scratch, params, task, run_scratch, ..} = env;
+    let params = params.as_deref().unwrap_or(&node.params);
+    let task = task.as_deref().unwrap_or(&*node.task);
+    metrics.task_started();
+    let progress = metrics.node(node.index);
+    progress.reset();
+    let first_attempt = scratch.as_ref().map(|scratch| scratch.attempt(1));
+    let output = Mutex::new(None);
+    let task_ctx = TaskCtx::new(ctx, &node.name, progress, first_attempt.as_ref(), &output, clock, params)
+        .with_run_scratch(run_scratch.as_deref());
+    let started_at = clock.now();
+    // the task runs all the same, the panic is only reported once the task
+    // and the other hooks are done
+    let before_panic = each.before.as_ref().and_then(|before| panics::catch(|| before(&node.name, ctx)).err());
+    let mut fallback_used = false;
+    let mut run_task = || {
+        let outcome = call(task, &task_ctx);
+        if let (Outcome::Error{..} | Outcome::Panic{..}, Some(fallback)) = (&outcome, &node.fallback) {
+            fallback_used = true;
+            let second_attempt = scratch.as_ref().map(|scratch| scratch.attempt(2));
+            let fallback_ctx = TaskCtx::new(ctx, &node.name, progress, second_attempt.as_ref(), &output, clock, params)
+                .with_run_scratch(run_scratch.as_deref());
+            return call(&**fallback, &fallback_ctx);
+        }
+        return outcome;
+    };
+    let mut outcome = match &node.hooks {
+        None => run_task(),
+        Some(hooks) => match call(&hooks.before, &task_ctx) {
+            Outcome::Done => call_after(run_task(), |result| (hooks.after)(ctx, result)),
+            outcome => outcome,
+        },
+    };
+    if let Some(check) = &each.check {
+        outcome = call_check(outcome, &node.name, || check(ctx));
+    }
+    if let Some(after) = &each.after {
+        outcome = call_after(outcome, |result| after(&node.name, ctx, result));
+    }
+    if let (Some((err, info)), false) = (before_panic, matches!(outcome, Outcome::Panic{..})) {
+        outcome = Outcome::Panic{err, info};
+    }
+    if let Some(scratch) = &scratch {
+        scratch.clean_up(!matches!(outcome, Outcome::Done));
+    }
+    let mut result = finished(node.index, started_at, clock.now(), fallback_used, outcome, metrics);
+    result.output = output.into_inner().unwrap_or_else(|err| err.into_inner());
+    return result;
 }
 
-enum RunningResult {
-    Done{index: usize},
-    Error{index: usize, err: TaskError},
-    Panic{index: usize, err: PanicError},
+fn finished(index: usize, started_at: Instant, finished_at: Instant, fallback_used: bool, outcome: Outcome, metrics: &SchedulerMetrics) -> RunningResult {
+    match outcome {
+        Outcome::Done => metrics.task_succeeded(),
+        Outcome::Error{..} => metrics.task_failed(),
+        Outcome::Panic{..} => metrics.task_panicked(),
+    }
+    return RunningResult{
+        index: index,
+        started_at: started_at,
+        finished_at: finished_at,
+        fallback_used: fallback_used,
+        outcome: outcome,
+        output: None,
+    };
+}
+
+fn call<C>(task: &dyn TaskRun<C>, ctx: &TaskCtx<'_, C>) -> Outcome {
+    let result = panics::catch(|| {
+        return task.run_with_ctx(ctx);
+    });
+    return match result {
+        Ok(Ok(_)) => Outcome::Done,
+        Ok(Err(err)) => Outcome::Error{err},
+        Err((err, info)) => Outcome::Panic{err, info},
+    };
+}
+
+// a panic of the task is handed to `after` as an error, and stays a panic
+// afterwards; a panic of `after` itself makes the node panic
+fn call_after<F>(outcome: Outcome, after: F) -> Outcome
+    where
+        F: FnOnce(&NodeResult)
+{
+    let (result, panic_err) = match outcome {
+        Outcome::Done => (Ok(()), None),
+        Outcome::Error{err} => (Err(err), None),
+        Outcome::Panic{err, info} => (Err(panic_to_error(&err)), Some((err, info))),
+    };
+    if let Err((err, info)) = panics::catch(|| after(&result)) {
+        return Outcome::Panic{err, info};
+    }
+    return match (result, panic_err) {
+        (_, Some((err, info))) => Outcome::Panic{err, info},
+        (Ok(_), None) => Outcome::Done,
+        (Err(err), None) => Outcome::Error{err},
+    };
+}
+
+// only a node which succeeded is failed by the check, or made to panic by
+// a panic of the check
+fn call_check<F>(outcome: Outcome, name: &str, check: F) -> Outcome
+    where
+        F: FnOnce() -> Result<(), TaskError>
+{
+    return match (outcome, panics::catch(check)) {
+        (Outcome::Done, Ok(Err(err))) => Outcome::Error{err: Box::new(PostTaskCheckError{
+            node: name.to_string(),
+            err: err,
+        })},
+        (Outcome::Done, Err((err, info))) => Outcome::Panic{err, info},
+        (outcome, _) => outcome,
+    };
+}
+
+fn panic_to_error(err: &PanicError) -> TaskError {
+    return task_error(match panic_message(err) {
+        Some(s) => format!("task panicked: {}", s),
+        None => String::from("task panicked"),
+    });
+}
+
+struct RunningResult {
+    index: usize,
+    started_at: Instant,
+    finished_at: Instant,
+    fallback_used: bool,
+    outcome: Outcome,
+    output: Option<Output>,
+}
+
+enum Outcome {
+    Done,
+    Error{err: TaskError},
+    Panic{err: PanicError, info: Option<Box<PanicInfoCaptured>>},
+}
+
+// the Display of what a node failed or panicked with, and its type name
+// where it is known, see `NodeReport::error`
+fn error_of(outcome: &Outcome) -> (Option<String>, Option<String>) {
+    return match outcome {
+        Outcome::Done => (None, None),
+        Outcome::Error{err} => {
+            let type_name = if err.is::<TimeoutError>() {
+                Some(std::any::type_name::<TimeoutError>())
+            } else if err.is::<PostTaskCheckError>() {
+                Some(std::any::type_name::<PostTaskCheckError>())
+            } else if err.is::<PanickedTaskError>() {
+                Some(std::any::type_name::<PanickedTaskError>())
+            } else {
+                None
+            };
+            #[cfg(feature = "anyhow")]
+            let type_name = type_name.or_else(|| {
+                err.is::<crate::error::AnyhowError>().then(std::any::type_name::<crate::error::AnyhowError>)
+            });
+            (Some(err.to_string()), type_name.map(String::from))
+        },
+        Outcome::Panic{err, ..} => {
+            let type_name = if err.is::<String>() {
+                Some(std::any::type_name::<String>())
+            } else if err.is::<&'static str>() {
+                Some(std::any::type_name::<&'static str>())
+            } else {
+                None
+            };
+            (Some(panic_message(err).unwrap_or_else(|| String::from("panic occurred"))), type_name.map(String::from))
+        },
+    };
+}
+
+fn status_of(result: &RunningResult, budget: Option<Duration>) -> NodeStatus {
+    return match result.outcome {
+        Outcome::Done => {
+            let duration = result.finished_at - result.started_at;
+            NodeStatus::Succeeded{
+                duration: duration,
+                over_budget: budget.is_some_and(|budget| duration > budget),
+            }
+        },
+        Outcome::Error{..} => NodeStatus::Failed,
+        Outcome::Panic{..} => NodeStatus::Panicked,
+    };
 }
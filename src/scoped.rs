@@ -0,0 +1,87 @@
+use crate::error::{Error, TaskError};
+use crate::graph::{Graph, TaskRun};
+use crate::scheduler::Scheduler;
+
+// A graph whose tasks may borrow from the enclosing scope, e.g. closures
+// capturing `&local_vec`, as all of them are done once `run` returns.
+// `Graph` keeps its tasks behind `'static` so that frozen graphs may be
+// shared with other threads (`Scheduler::run_arc`, `spawn_run`); this one
+// only keeps names and edges in a graph of its own and builds a scheduler
+// for every run, with tasks forwarding to the borrowed ones.
+pub struct ScopedGraph<'a, C> {
+    skeleton: Graph<()>,
+    tasks: Vec<Box<dyn TaskRun<C> + 'a>>,
+}
+
+// what the forwarding tasks get as context
+struct Scope<'r, 'a, C> {
+    ctx: &'r C,
+    tasks: &'r [Box<dyn TaskRun<C> + 'a>],
+}
+
+struct Forward(usize);
+
+impl<C: Sync> TaskRun<Scope<'_, '_, C>> for Forward {
+    fn run(&self, scope: &Scope<'_, '_, C>) -> Result<(), TaskError> {
+        return scope.tasks[self.0].run(scope.ctx);
+    }
+}
+
+impl<C: Sync> Default for ScopedGraph<'_, C> {
+    fn default() -> Self {
+        return ScopedGraph::new();
+    }
+}
+
+impl<'a, C: Sync> ScopedGraph<'a, C> {
+    pub fn new() -> ScopedGraph<'a, C> {
+        return ScopedGraph{
+            skeleton: Graph::new(),
+            tasks: vec![],
+        };
+    }
+
+    pub fn add_node<F>(&mut self, name: &str, task: F) -> Result<(), Error>
+        where
+            F: Fn(&C) -> Result<(), TaskError> + Send + Sync + 'a
+    {
+        return self.add_task(name, task);
+    }
+
+    pub fn add_task<T>(&mut self, name: &str, task: T) -> Result<(), Error>
+        where
+            T: TaskRun<C> + 'a
+    {
+        self.skeleton.add_task(name, |_: &()| Ok(()))?;
+        self.tasks.push(Box::new(task));
+        return Ok(());
+    }
+
+    pub fn add_edge(&mut self, from_node: &str, to_node: &str) -> Result<(), Error> {
+        return self.skeleton.add_edge(from_node, to_node);
+    }
+
+    // nodes run on scoped threads, as with `Scheduler::run`
+    pub fn run(&self, ctx: &C) -> Result<(), Error> {
+        let scope = Scope{ctx: ctx, tasks: &self.tasks};
+        return self.scheduler()?.run(&scope);
+    }
+
+    pub fn run_sequential(&self, ctx: &C) -> Result<(), Error> {
+        let scope = Scope{ctx: ctx, tasks: &self.tasks};
+        return self.scheduler()?.run_sequential(&scope);
+    }
+
+    fn scheduler<'r>(&self) -> Result<Scheduler<Scope<'r, 'a, C>>, Error> {
+        let mut graph = Graph::with_capacity(self.tasks.len(), 0);
+        for node in self.skeleton.nodes.iter() {
+            graph.add_task(&node.name, Forward(node.index))?;
+        }
+        for node in self.skeleton.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                graph.add_edge(&node.name, &self.skeleton.nodes[*child_index].name)?;
+            }
+        }
+        return Ok(Scheduler::new(graph.froze()?));
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Once;
+
+use crate::run_id::RunId;
+
+// Where `SchedulerBuilder::scratch_dirs` puts the scratch directories, one
+// per attempt under `base/run_id/node_name/attempt_n`: the task itself is
+// the 1st attempt, its fallback the 2nd.
+#[derive(Clone, Debug)]
+pub(crate) struct ScratchDirs {
+    pub base: PathBuf,
+    pub keep_on_failure: bool,
+}
+
+impl Default for ScratchDirs {
+    fn default() -> ScratchDirs {
+        return ScratchDirs{
+            base: std::env::temp_dir(),
+            keep_on_failure: false,
+        };
+    }
+}
+
+impl ScratchDirs {
+    pub fn run_dir(&self, run_id: &RunId) -> PathBuf {
+        return self.base.join(component(run_id.as_str()));
+    }
+
+    // names which are not a plain path component get the node index
+    // appended, so that "a/b" and "a?b" do not end up in the same place
+    pub fn node(&self, run_id: &RunId, index: usize, name: &str) -> NodeScratch {
+        let mut dir_name = component(name);
+        if dir_name != name {
+            dir_name = format!("{}-{}", dir_name, index);
+        }
+        return NodeScratch{
+            dir: self.run_dir(run_id).join(dir_name),
+            keep_on_failure: self.keep_on_failure,
+        };
+    }
+
+    // only once every node removed its own directory, so that nodes still
+    // creating theirs never miss the run directory
+    pub fn remove_run_dir(&self, run_id: &RunId) {
+        let _ = fs::remove_dir(self.run_dir(run_id));
+    }
+}
+
+pub(crate) struct NodeScratch {
+    dir: PathBuf,
+    keep_on_failure: bool,
+}
+
+impl NodeScratch {
+    pub fn attempt(&self, n: u32) -> Scratch {
+        return Scratch{
+            path: self.dir.join(format!("attempt_{}", n)),
+            created: Once::new(),
+        };
+    }
+
+    pub fn clean_up(&self, failed: bool) {
+        if failed && self.keep_on_failure {
+            return;
+        }
+        if let Err(_err) = fs::remove_dir_all(&self.dir) {
+            #[cfg(feature = "log")]
+            if _err.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("could not remove scratch directory {}: {}", self.dir.display(), _err);
+            }
+        }
+    }
+}
+
+// created on first use, a node never asking for it leaves nothing behind
+pub(crate) struct Scratch {
+    path: PathBuf,
+    created: Once,
+}
+
+impl Scratch {
+    pub fn path(&self) -> &Path {
+        self.created.call_once(|| {
+            if let Err(err) = fs::create_dir_all(&self.path) {
+                panic!("could not create scratch directory {}: {}", self.path.display(), err);
+            }
+        });
+        return &self.path;
+    }
+}
+
+fn component(name: &str) -> String {
+    let component: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' { c } else { '_' })
+        .collect();
+    return match component.as_str() {
+        "" | "." | ".." => component.replace('.', "_") + "_",
+        _ => component,
+    };
+}
@@ -0,0 +1,69 @@
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::SigId;
+
+const SIGNALS: [i32; 2] = [SIGINT, SIGTERM];
+
+// Once signal-hook handles a signal it never gives it back, so the default
+// action, terminating, is emulated instead whenever this is set: outside of
+// runs draining on signals, and after the first signal within one.
+fn terminate_on_signal() -> &'static Arc<AtomicBool> {
+    static TERMINATE: OnceLock<Arc<AtomicBool>> = OnceLock::new();
+    return TERMINATE.get_or_init(|| {
+        let terminate = Arc::new(AtomicBool::new(true));
+        for signal in SIGNALS {
+            signal_hook::flag::register_conditional_default(signal, terminate.clone())
+                .expect("failed to install signal handler");
+        }
+        return terminate;
+    });
+}
+
+// number of runs draining on signals right now
+static ACTIVE: Mutex<usize> = Mutex::new(0);
+
+// Sets `drain` on the first SIGINT or SIGTERM while alive.
+pub(crate) struct SignalDrain {
+    drain: Arc<AtomicBool>,
+    ids: Vec<SigId>,
+}
+
+impl SignalDrain {
+    pub(crate) fn install() -> SignalDrain {
+        let terminate = terminate_on_signal();
+        let mut active = ACTIVE.lock().unwrap_or_else(|err| err.into_inner());
+        *active += 1;
+        terminate.store(false, Ordering::SeqCst);
+        let drain = Arc::new(AtomicBool::new(false));
+        let mut ids = vec![];
+        for signal in SIGNALS {
+            // called in this order, after the check of `terminate`
+            for flag in [&drain, terminate] {
+                ids.push(signal_hook::flag::register(signal, flag.clone()).expect("failed to install signal handler"));
+            }
+        }
+        return SignalDrain{
+            drain: drain,
+            ids: ids,
+        };
+    }
+
+    pub(crate) fn flag(&self) -> &AtomicBool {
+        return &self.drain;
+    }
+}
+
+impl Drop for SignalDrain {
+    fn drop(&mut self) {
+        let mut active = ACTIVE.lock().unwrap_or_else(|err| err.into_inner());
+        for id in self.ids.drain(..) {
+            signal_hook::low_level::unregister(id);
+        }
+        *active -= 1;
+        if *active == 0 {
+            terminate_on_signal().store(true, Ordering::SeqCst);
+        }
+    }
+}
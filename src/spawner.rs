@@ -0,0 +1,466 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread::Builder;
+#[cfg(not(target_arch = "wasm32"))]
+use std::marker::PhantomData;
+#[cfg(not(target_arch = "wasm32"))]
+use std::panic::{self, AssertUnwindSafe};
+#[cfg(not(target_arch = "wasm32"))]
+use std::collections::VecDeque;
+use std::sync::Arc;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::{Condvar, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread::{self, JoinHandle};
+
+use crate::error::SpawnError;
+
+pub type Job<'env> = Box<dyn FnOnce() + Send + 'env>;
+
+// A `Spawner` decides where the tasks of a run are executed. For every run
+// the scheduler opens a scope and drives the whole run inside `body`; jobs
+// may borrow from the caller of `run`, so every job spawned through the
+// scope must have finished by the time `scope` returns.
+pub trait Spawner: Send + Sync {
+    fn scope<'env>(&self, body: &mut dyn FnMut(&dyn SpawnScope<'env>));
+
+    // `scope` for a run of `RunOptions::priority`, which spawners sharing
+    // their threads between runs may take into account
+    fn scope_with_priority<'env>(&self, _priority: u8, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        self.scope(body);
+    }
+
+    // start whatever is started lazily otherwise, e.g. the threads of a pool
+    fn warm_up(&self) {
+
+    }
+}
+
+// so that several schedulers may share one spawner, e.g. one pool
+impl<S: Spawner + ?Sized> Spawner for Arc<S> {
+    fn scope<'env>(&self, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        (**self).scope(body);
+    }
+
+    fn scope_with_priority<'env>(&self, priority: u8, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        (**self).scope_with_priority(priority, body);
+    }
+
+    fn warm_up(&self) {
+        (**self).warm_up();
+    }
+}
+
+pub trait SpawnScope<'env> {
+    fn spawn(&self, name: &str, job: Job<'env>) -> Result<(), SpawnError>;
+}
+
+// longest thread name the OS keeps, excluding the trailing NUL
+#[cfg(any(target_os = "linux", target_os = "android"))]
+const THREAD_NAME_MAX: usize = 15;
+#[cfg(any(target_os = "macos", target_os = "ios"))]
+const THREAD_NAME_MAX: usize = 63;
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios", target_arch = "wasm32")))]
+const THREAD_NAME_MAX: usize = 15;
+
+// The default: one scoped std thread per task.
+#[derive(Clone, Debug)]
+pub struct ThreadSpawner {
+    pub name_threads: bool,
+    pub stack_size: Option<usize>,
+}
+
+impl Default for ThreadSpawner {
+    fn default() -> ThreadSpawner {
+        return ThreadSpawner{
+            name_threads: true,
+            stack_size: None,
+        };
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ThreadSpawner {
+    pub(crate) fn builder(&self, name: &str) -> Builder {
+        let mut builder = Builder::new();
+        if self.name_threads {
+            builder = builder.name(thread_name(name));
+        }
+        if let Some(size) = self.stack_size {
+            builder = builder.stack_size(size);
+        }
+        return builder;
+    }
+}
+
+impl Spawner for ThreadSpawner {
+    #[cfg(not(target_arch = "wasm32"))]
+    fn scope<'env>(&self, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        thread::scope(|s| {
+            body(&ThreadScope{spawner: self, scope: s});
+        });
+    }
+
+    // no threads on wasm, every task runs inline on the scheduling thread
+    #[cfg(target_arch = "wasm32")]
+    fn scope<'env>(&self, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        body(&InlineSpawner);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct ThreadScope<'a, 'scope, 'env> {
+    spawner: &'a ThreadSpawner,
+    scope: &'scope thread::Scope<'scope, 'env>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'env> SpawnScope<'env> for ThreadScope<'_, '_, 'env> {
+    fn spawn(&self, name: &str, job: Job<'env>) -> Result<(), SpawnError> {
+        self.spawner.builder(name).spawn_scoped(self.scope, job)?;
+        return Ok(());
+    }
+}
+
+// Names are cut to what the platform supports, e.g. 15 bytes on Linux.
+#[cfg(not(target_arch = "wasm32"))]
+fn thread_name(name: &str) -> String {
+    let name = match name.find('\0') {
+        Some(i) => &name[..i],
+        None => name,
+    };
+    let mut end = name.len().min(THREAD_NAME_MAX);
+    while !name.is_char_boundary(end) {
+        end -= 1;
+    }
+    return name[..end].to_string();
+}
+
+// A fixed number of std threads shared by all runs, started by the first
+// run or by `warm_up`, so that no run pays for spawning threads. A run
+// waits for all its jobs before returning, whichever thread ran them; the
+// threads are stopped and joined when the spawner is dropped.
+//
+// Every run queues its jobs on a lane of its own, and the threads take
+// them from the lanes in turn, `priority + 1` jobs at a time, so that a
+// small run started next to a huge one gets its share of the threads
+// instead of waiting for all jobs queued before its own. Shared by several
+// schedulers as `Arc<ThreadPoolSpawner>`, this holds for all their runs.
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ThreadPoolSpawner {
+    n_worker: usize,
+    pool: Mutex<Option<Pool>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+type PoolJob = Box<dyn FnOnce() + Send + 'static>;
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Pool {
+    shared: Arc<Shared>,
+    workers: Vec<JoinHandle<()>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Shared {
+    queue: Mutex<Queue>,
+    queued: Condvar,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Default)]
+struct Queue {
+    // the front one is served next
+    lanes: VecDeque<Lane>,
+    next_id: u64,
+    stopped: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+struct Lane {
+    id: u64,
+    weight: usize,
+    jobs: VecDeque<PoolJob>,
+    // jobs taken since this lane came to the front
+    served: usize,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Queue {
+    fn open_lane(&mut self, priority: u8) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.lanes.push_back(Lane{
+            id: id,
+            weight: priority as usize + 1,
+            jobs: VecDeque::new(),
+            served: 0,
+        });
+        return id;
+    }
+
+    fn close_lane(&mut self, id: u64) {
+        self.lanes.retain(|lane| lane.id != id);
+    }
+
+    fn push(&mut self, id: u64, job: PoolJob) {
+        if let Some(lane) = self.lanes.iter_mut().find(|lane| lane.id == id) {
+            lane.jobs.push_back(job);
+        }
+    }
+
+    fn pop(&mut self) -> Option<PoolJob> {
+        for _ in 0..self.lanes.len() {
+            let lane = self.lanes.front_mut()?;
+            if let Some(job) = lane.jobs.pop_front() {
+                lane.served += 1;
+                if lane.served >= lane.weight || lane.jobs.is_empty() {
+                    lane.served = 0;
+                    self.lanes.rotate_left(1);
+                }
+                return Some(job);
+            }
+            self.lanes.rotate_left(1);
+        }
+        return None;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Shared {
+    fn lock(&self) -> std::sync::MutexGuard<'_, Queue> {
+        return self.queue.lock().unwrap_or_else(|err| err.into_inner());
+    }
+
+    // `None` once stopped and every queued job was taken
+    fn next_job(&self) -> Option<PoolJob> {
+        let mut queue = self.lock();
+        loop {
+            if let Some(job) = queue.pop() {
+                return Some(job);
+            }
+            if queue.stopped {
+                return None;
+            }
+            queue = self.queued.wait(queue).unwrap_or_else(|err| err.into_inner());
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ThreadPoolSpawner {
+    // 0 workers means one per available core
+    pub fn new(n_worker: usize) -> ThreadPoolSpawner {
+        let n_worker = match n_worker {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        return ThreadPoolSpawner{
+            n_worker: n_worker,
+            pool: Mutex::new(None),
+        };
+    }
+
+    pub fn n_worker(&self) -> usize {
+        return self.n_worker;
+    }
+
+    // whether the threads are running already
+    pub fn is_started(&self) -> bool {
+        return self.pool.lock().unwrap_or_else(|err| err.into_inner()).is_some();
+    }
+
+    fn shared(&self) -> Arc<Shared> {
+        let mut pool = self.pool.lock().unwrap_or_else(|err| err.into_inner());
+        if let Some(pool) = &*pool {
+            return pool.shared.clone();
+        }
+        let shared = Arc::new(Shared{
+            queue: Mutex::new(Queue::default()),
+            queued: Condvar::new(),
+        });
+        let workers = (0..self.n_worker).map(|i| {
+            let shared = shared.clone();
+            return Builder::new().name(format!("dag-worker-{}", i)).spawn(move || {
+                while let Some(job) = shared.next_job() {
+                    job();
+                }
+            }).expect("failed to spawn pool thread");
+        }).collect();
+        *pool = Some(Pool{
+            shared: shared.clone(),
+            workers: workers,
+        });
+        return shared;
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for ThreadPoolSpawner {
+    fn drop(&mut self) {
+        let pool = self.pool.get_mut().unwrap_or_else(|err| err.into_inner()).take();
+        if let Some(Pool{shared, workers}) = pool {
+            shared.lock().stopped = true;
+            shared.queued.notify_all();
+            for worker in workers {
+                let _ = worker.join();
+            }
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Spawner for ThreadPoolSpawner {
+    fn scope<'env>(&self, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        self.scope_with_priority(0, body);
+    }
+
+    fn scope_with_priority<'env>(&self, priority: u8, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        let shared = self.shared();
+        let lane = shared.lock().open_lane(priority);
+        let scope = PoolScope{
+            shared: shared,
+            lane: lane,
+            pending: Arc::new((Mutex::new(0), Condvar::new())),
+            panicked: Arc::new(Mutex::new(None)),
+            env: PhantomData,
+        };
+        body(&scope);
+        // like `thread::scope`, a panic of a job is raised once all are done
+        let panicked = scope.panicked.clone();
+        drop(scope);
+        let err = panicked.lock().unwrap_or_else(|err| err.into_inner()).take();
+        if let Some(err) = err {
+            panic::resume_unwind(err);
+        }
+    }
+
+    fn warm_up(&self) {
+        self.shared();
+    }
+}
+
+// Dropping the scope waits for its jobs, also when `body` panics, which is
+// what makes lending `'env` borrows to the pool threads sound.
+#[cfg(not(target_arch = "wasm32"))]
+struct PoolScope<'env> {
+    shared: Arc<Shared>,
+    lane: u64,
+    pending: Arc<(Mutex<usize>, Condvar)>,
+    // the payload of the first job which panicked
+    panicked: Arc<Mutex<Option<Box<dyn std::any::Any + Send>>>>,
+    env: PhantomData<&'env ()>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl<'env> SpawnScope<'env> for PoolScope<'env> {
+    fn spawn(&self, _: &str, job: Job<'env>) -> Result<(), SpawnError> {
+        // SAFETY: the pool threads are 'static, so the job is passed off as
+        // borrowing nothing, although it may borrow for `'env`. That is
+        // sound only if every job is done before `'env` ends, which rests on:
+        //  - `Drop for PoolScope` waiting until no job is pending, a job
+        //    counting as pending from before it is queued until after it
+        //    ran, panics included;
+        //  - that drop being sure to run before `'env` ends: the scope is
+        //    created, lent to `body` by reference and dropped within
+        //    `ThreadPoolSpawner::scope_with_priority`, unwinding included,
+        //    and never handed out by value, so nothing can `mem::forget` it
+        //    or move it past `'env`. A leaked scope would let `'env` end
+        //    with jobs still queued or running, hence it stays private.
+        let job: PoolJob = unsafe { std::mem::transmute::<Job<'env>, PoolJob>(job) };
+        let mut queue = self.shared.lock();
+        if queue.stopped {
+            return Err(SpawnError::new("thread pool is stopped"));
+        }
+        *self.pending.0.lock().unwrap_or_else(|err| err.into_inner()) += 1;
+        let pending = self.pending.clone();
+        let panicked = self.panicked.clone();
+        queue.push(self.lane, Box::new(move || {
+            if let Err(err) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                panicked.lock().unwrap_or_else(|err| err.into_inner()).get_or_insert(err);
+            }
+            let (count, done) = &*pending;
+            *count.lock().unwrap_or_else(|err| err.into_inner()) -= 1;
+            done.notify_all();
+        }));
+        drop(queue);
+        self.shared.queued.notify_one();
+        return Ok(());
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for PoolScope<'_> {
+    fn drop(&mut self) {
+        let (count, done) = &*self.pending;
+        let mut count = count.lock().unwrap_or_else(|err| err.into_inner());
+        while *count > 0 {
+            count = done.wait(count).unwrap_or_else(|err| err.into_inner());
+        }
+        self.shared.lock().close_lane(self.lane);
+    }
+}
+
+// Runs every job right away on the thread that spawns it, mostly useful
+// for deterministic tests of the scheduling logic.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct InlineSpawner;
+
+impl Spawner for InlineSpawner {
+    fn scope<'env>(&self, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        body(&InlineSpawner);
+    }
+}
+
+impl<'env> SpawnScope<'env> for InlineSpawner {
+    fn spawn(&self, _: &str, job: Job<'env>) -> Result<(), SpawnError> {
+        job();
+        return Ok(());
+    }
+}
+
+// Runs the tasks on a rayon pool, the global one unless a pool is given.
+#[cfg(feature = "rayon")]
+#[derive(Clone, Debug, Default)]
+pub struct RayonSpawner {
+    pool: Option<std::sync::Arc<rayon::ThreadPool>>,
+}
+
+#[cfg(feature = "rayon")]
+impl RayonSpawner {
+    pub fn new(pool: std::sync::Arc<rayon::ThreadPool>) -> RayonSpawner {
+        return RayonSpawner{
+            pool: Some(pool),
+        };
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl Spawner for RayonSpawner {
+    fn scope<'env>(&self, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        match &self.pool {
+            Some(pool) => pool.in_place_scope(|s| body(&RayonScope(s))),
+            None => rayon::in_place_scope(|s| body(&RayonScope(s))),
+        }
+    }
+}
+
+#[cfg(feature = "rayon")]
+pub(crate) struct PoolSpawner<'p>(pub &'p rayon::ThreadPool);
+
+#[cfg(feature = "rayon")]
+impl Spawner for PoolSpawner<'_> {
+    fn scope<'env>(&self, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        self.0.in_place_scope(|s| body(&RayonScope(s)));
+    }
+}
+
+#[cfg(feature = "rayon")]
+struct RayonScope<'a, 'env>(&'a rayon::Scope<'env>);
+
+#[cfg(feature = "rayon")]
+impl<'env> SpawnScope<'env> for RayonScope<'_, 'env> {
+    fn spawn(&self, _: &str, job: Job<'env>) -> Result<(), SpawnError> {
+        self.0.spawn(move |_| job());
+        return Ok(());
+    }
+}
@@ -0,0 +1,82 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::error::{task_error, TaskError, TimeoutError};
+use crate::graph::Task;
+
+// Wrappers around a task returning a new task, so that they compose, e.g.
+// `with_retry(with_timeout(task, limit), 3)` retries every attempt that
+// failed or ran over `limit`.
+
+// Runs `task` again up to `retries` times while it returns an error, the
+// last error is returned. Panics are not retried.
+pub fn with_retry<C: 'static>(task: Task<C>, retries: usize) -> Task<C> {
+    return Box::new(move |ctx: &C| -> Result<(), TaskError> {
+        let mut result = task(ctx);
+        for _attempt in 0..retries {
+            let Err(_err) = &result else {
+                break;
+            };
+            #[cfg(feature = "log")]
+            log::warn!("retrying task, attempt {} of {} failed: {}", _attempt + 1, retries + 1, _err);
+            result = task(ctx);
+        }
+        return result;
+    });
+}
+
+// Same as `with_retry`, sleeping on `clock` before every retry, for
+// `backoff` first and twice as long every time after.
+pub fn with_backoff<C: 'static>(task: Task<C>, retries: usize, backoff: Duration, clock: Arc<dyn Clock>) -> Task<C> {
+    return Box::new(move |ctx: &C| -> Result<(), TaskError> {
+        let mut result = task(ctx);
+        let mut pause = backoff;
+        for _attempt in 0..retries {
+            let Err(_err) = &result else {
+                break;
+            };
+            #[cfg(feature = "log")]
+            log::warn!("retrying task in {:?}, attempt {} of {} failed: {}", pause, _attempt + 1, retries + 1, _err);
+            clock.sleep(pause);
+            pause = pause.saturating_mul(2);
+            result = task(ctx);
+        }
+        return result;
+    });
+}
+
+// A running task is never interrupted: once it returns after more than
+// `limit`, its result is replaced by a `TimeoutError`. Tasks that may
+// hang have to watch the time themselves.
+pub fn with_timeout<C: 'static>(task: Task<C>, limit: Duration) -> Task<C> {
+    return with_timeout_on(task, limit, Arc::new(SystemClock));
+}
+
+// Same as `with_timeout`, measuring on `clock`.
+pub fn with_timeout_on<C: 'static>(task: Task<C>, limit: Duration, clock: Arc<dyn Clock>) -> Task<C> {
+    return Box::new(move |ctx: &C| -> Result<(), TaskError> {
+        let started_at = clock.now();
+        let result = task(ctx);
+        let elapsed = clock.now().saturating_duration_since(started_at);
+        if elapsed > limit {
+            return Err(Box::new(TimeoutError::new(limit, elapsed)));
+        }
+        return result;
+    });
+}
+
+pub fn map_err<C: 'static, F>(task: Task<C>, f: F) -> Task<C>
+    where
+        F: Fn(TaskError) -> TaskError + Send + Sync + 'static
+{
+    return Box::new(move |ctx: &C| -> Result<(), TaskError> {
+        return task(ctx).map_err(&f);
+    });
+}
+
+// prefixes every error of `task` with `name`
+pub fn with_name<C: 'static>(task: Task<C>, name: &str) -> Task<C> {
+    let name = name.to_string();
+    return map_err(task, move |err| task_error(format!("{}: {}", name, err)));
+}
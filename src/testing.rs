@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+
+use rand::Rng;
+
+use crate::graph::{Graph, TaskRun};
+
+// Helpers for property tests of code built on top of dag_engine.
+
+// Nodes are named "0" to "n_nodes - 1", and every pair `i < j` gets the
+// edge `i -> j` with probability `edge_prob`. Edges only ever go forward,
+// so the graph is always acyclic. `task_factory` gets the index of the
+// node to make the task of.
+pub fn random_dag<C, R, F, T>(rng: &mut R, n_nodes: usize, edge_prob: f64, mut task_factory: F) -> Graph<C>
+    where
+        R: Rng,
+        F: FnMut(usize) -> T,
+        T: TaskRun<C> + 'static
+{
+    let mut graph = Graph::new();
+    for i in 0..n_nodes {
+        graph.add_task(&i.to_string(), task_factory(i)).unwrap();
+        for j in 0..i {
+            if rng.gen_bool(edge_prob) {
+                graph.add_edge(&j.to_string(), &i.to_string()).unwrap();
+            }
+        }
+    }
+    return graph;
+}
+
+// Panics unless `order` could have been run by `graph`: every name is a
+// node of it and appears once, and every node comes after all its parents.
+// Nodes missing from `order` are fine, a run may stop early.
+pub fn assert_valid_execution<C>(order: &[String], graph: &Graph<C>) {
+    let mut positions: HashMap<&str, usize> = HashMap::with_capacity(order.len());
+    for (position, name) in order.iter().enumerate() {
+        assert!(graph.nodes_indices.contains_key(name.as_str()), "node {} is not in the graph", name);
+        if let Some(first) = positions.insert(name, position) {
+            panic!("node {} ran twice, at {} and {}", name, first, position);
+        }
+    }
+    for node in graph.nodes.iter() {
+        let parent_position = positions.get(&*node.name);
+        for child_index in node.childrens.iter() {
+            let child = &graph.nodes[*child_index].name;
+            let Some(child_position) = positions.get(&**child) else {
+                continue;
+            };
+            match parent_position {
+                Some(parent_position) => assert!(parent_position < child_position,
+                    "node {} ran before its parent {}", child, node.name),
+                None => panic!("node {} ran without its parent {}", child, node.name),
+            }
+        }
+    }
+}
@@ -0,0 +1,6 @@
+// `std::time::Instant::now` panics on wasm32-unknown-unknown, where the
+// clock of the host is read instead.
+#[cfg(not(target_arch = "wasm32"))]
+pub use std::time::Instant;
+#[cfg(target_arch = "wasm32")]
+pub use web_time::Instant;
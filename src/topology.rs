@@ -0,0 +1,676 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, BinaryHeap, HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use crate::error::Error;
+use crate::graph::{Graph, FrozenGraph, PanicPolicy, TaskClass};
+
+// Read-only questions about the shape of a graph: node names, edges and
+// per-node settings. Tasks are never looked at.
+
+impl<C> Graph<C> {
+    pub fn node_count(&self) -> usize {
+        return self.nodes.len();
+    }
+
+    // while in bulk mode, duplicated edges are counted each time
+    pub fn edge_count(&self) -> usize {
+        return self.n_edge;
+    }
+
+    // every edge as (parent, child), parents and then their children in the
+    // order they were added
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        return self.nodes.iter().flat_map(move |node| {
+            return node.childrens.iter().map(move |child_index| (&*node.name, &*self.nodes[*child_index].name));
+        });
+    }
+
+    // A hash of the node names, their settings and the edges, independent of
+    // the order they were added in. Tasks are not part of it: changing what
+    // a node does without changing the graph keeps the fingerprint, and of
+    // gates, fallbacks and hooks only whether a node has one counts. The
+    // hash (64 bit FNV-1a) is fixed, so fingerprints can be stored and
+    // compared across processes and versions.
+    pub fn fingerprint(&self) -> u64 {
+        let mut nodes: Vec<usize> = (0..self.nodes.len()).collect();
+        nodes.sort_by(|a, b| self.nodes[*a].name.cmp(&self.nodes[*b].name));
+        let mut hasher = Fnv::new();
+        hasher.write_usize(nodes.len());
+        for index in nodes {
+            let node = &self.nodes[index];
+            hasher.write_str(&node.name);
+            hasher.write(&[node.inline as u8]);
+            // optional settings are a presence byte, then the value if any
+            hasher.write(&[node.cost.is_some() as u8]);
+            if let Some(cost) = node.cost {
+                hasher.write(&cost.as_secs().to_le_bytes());
+                hasher.write(&cost.subsec_nanos().to_le_bytes());
+            }
+            hasher.write(&node.weight.to_le_bytes());
+            match &node.class {
+                None => hasher.write(&[0]),
+                Some(TaskClass::Io) => hasher.write(&[1]),
+                Some(TaskClass::Cpu) => hasher.write(&[2]),
+                Some(TaskClass::Custom(name)) => {
+                    hasher.write(&[3]);
+                    hasher.write_str(name);
+                },
+            }
+            hasher.write(&[node.barrier as u8]);
+            let mut params: Vec<(&String, &String)> = node.params.iter().collect();
+            params.sort();
+            hasher.write_usize(params.len());
+            for (key, value) in params {
+                hasher.write_str(key);
+                hasher.write_str(value);
+            }
+            hasher.write(&[node.gate.is_some() as u8]);
+            hasher.write(&[node.pinned as u8]);
+            hasher.write(&[match node.panic_policy {
+                PanicPolicy::Capture => 0,
+                PanicPolicy::Abort => 1,
+            }]);
+            hasher.write(&[node.fallback.is_some() as u8]);
+            hasher.write(&[node.hooks.is_some() as u8]);
+            let mut childrens: Vec<&str> = node.childrens.iter()
+                .map(|child_index| &*self.nodes[*child_index].name).collect();
+            childrens.sort();
+            hasher.write_usize(childrens.len());
+            for child in childrens {
+                hasher.write_str(child);
+            }
+        }
+        return hasher.finish();
+    }
+}
+
+// How `other` differs from `self` in `self.topology_diff(other)`: nodes and
+// edges of `self` which `other` lacks are missing, the ones only `other`
+// has are extra. Every list is sorted.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TopologyDiff {
+    pub missing_nodes: Vec<String>,
+    pub extra_nodes: Vec<String>,
+    pub missing_edges: Vec<(String, String)>,
+    pub extra_edges: Vec<(String, String)>,
+}
+
+impl TopologyDiff {
+    pub fn is_empty(&self) -> bool {
+        return self.missing_nodes.is_empty() && self.extra_nodes.is_empty()
+            && self.missing_edges.is_empty() && self.extra_edges.is_empty();
+    }
+}
+
+impl<C> Graph<C> {
+    // same node names and edges, whatever the order they were added in
+    pub fn topology_eq(&self, other: &Graph<C>) -> bool {
+        return self.topology_diff(other).is_empty();
+    }
+
+    pub fn topology_diff(&self, other: &Graph<C>) -> TopologyDiff {
+        let (nodes, edges) = (self.node_set(), self.edge_set());
+        let (other_nodes, other_edges) = (other.node_set(), other.edge_set());
+        let owned = |names: BTreeSet<&str>| names.into_iter().map(String::from).collect();
+        let owned_edges = |edges: BTreeSet<(&str, &str)>| edges.into_iter()
+            .map(|(from, to)| (from.to_string(), to.to_string())).collect();
+        return TopologyDiff{
+            missing_nodes: owned(&nodes - &other_nodes),
+            extra_nodes: owned(&other_nodes - &nodes),
+            missing_edges: owned_edges(&edges - &other_edges),
+            extra_edges: owned_edges(&other_edges - &edges),
+        };
+    }
+
+    fn node_set(&self) -> BTreeSet<&str> {
+        return self.nodes.iter().map(|node| &*node.name).collect();
+    }
+
+    fn edge_set(&self) -> BTreeSet<(&str, &str)> {
+        return self.nodes.iter()
+            .flat_map(|node| node.childrens.iter()
+                .map(move |child_index| (&*node.name, &*self.nodes[*child_index].name)))
+            .collect();
+    }
+}
+
+impl<C> Graph<C> {
+    // Edges `a -> c` which are implied by a longer path `a -> b -> .. -> c`,
+    // in the order they were added per parent. A cyclic graph has no
+    // meaningful reduction, there the result is always empty.
+    pub fn redundant_edges(&self) -> Vec<(String, String)> {
+        return self.redundant_edge_indices().into_iter()
+            .map(|(parent, child)| (self.nodes[parent].name.to_string(), self.nodes[child].name.to_string()))
+            .collect();
+    }
+
+    // A small set of edges whose removal leaves the graph acyclic, empty if
+    // it is already; not necessarily the smallest one. Nodes are ordered by
+    // the greedy heuristic of Eades, Lin and Smyth: sinks go last, sources
+    // first, otherwise the node with the most outgoing over incoming edges
+    // goes next. The edges pointing backwards in that order break all
+    // cycles, and any of them not closing a cycle on its own is dropped
+    // again.
+    pub fn feedback_edges(&self) -> Vec<(String, String)> {
+        return self.feedback_edge_indices().into_iter()
+            .map(|(parent, child)| (self.nodes[parent].name.to_string(), self.nodes[child].name.to_string()))
+            .collect();
+    }
+
+    fn feedback_edge_indices(&self) -> Vec<(usize, usize)> {
+        let n_node = self.nodes.len();
+        let mut parents: Vec<Vec<usize>> = vec![vec![]; n_node];
+        for node in self.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                parents[*child_index].push(node.index);
+            }
+        }
+        let mut in_degrees: Vec<usize> = parents.iter().map(Vec::len).collect();
+        let mut out_degrees: Vec<usize> = self.nodes.iter().map(|node| node.childrens.len()).collect();
+        let mut sinks: Vec<usize> = (0..n_node).filter(|index| out_degrees[*index] == 0).collect();
+        let mut sources: Vec<usize> = (0..n_node).filter(|index| in_degrees[*index] == 0).collect();
+        let mut removed = vec![false; n_node];
+        let (mut head, mut tail) = (vec![], vec![]);
+        loop {
+            // a node may be queued as both, only the first counts
+            let index = if let Some(index) = sinks.pop() {
+                if removed[index] {
+                    continue;
+                }
+                tail.push(index);
+                index
+            } else if let Some(index) = sources.pop() {
+                if removed[index] {
+                    continue;
+                }
+                head.push(index);
+                index
+            } else {
+                let best = (0..n_node).filter(|index| !removed[*index])
+                    .max_by_key(|index| (out_degrees[*index] as i64 - in_degrees[*index] as i64, Reverse(*index)));
+                let Some(index) = best else {
+                    break;
+                };
+                head.push(index);
+                index
+            };
+            removed[index] = true;
+            for child_index in self.nodes[index].childrens.iter() {
+                in_degrees[*child_index] -= 1;
+                if in_degrees[*child_index] == 0 && !removed[*child_index] {
+                    sources.push(*child_index);
+                }
+            }
+            for parent_index in parents[index].iter() {
+                out_degrees[*parent_index] -= 1;
+                if out_degrees[*parent_index] == 0 && !removed[*parent_index] {
+                    sinks.push(*parent_index);
+                }
+            }
+        }
+        let mut positions = vec![0; n_node];
+        for (position, index) in head.iter().chain(tail.iter().rev()).enumerate() {
+            positions[*index] = position;
+        }
+        let mut backwards: Vec<(usize, usize)> = vec![];
+        let mut seen = HashSet::new();
+        for node in self.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                if positions[node.index] >= positions[*child_index] && seen.insert((node.index, *child_index)) {
+                    backwards.push((node.index, *child_index));
+                }
+            }
+        }
+
+        // an edge may go back if its child still does not reach its parent
+        let mut feedback: HashSet<(usize, usize)> = backwards.iter().copied().collect();
+        for (parent, child) in backwards.iter() {
+            feedback.remove(&(*parent, *child));
+            if self.reaches_without(*child, *parent, &feedback) {
+                feedback.insert((*parent, *child));
+            }
+        }
+        if !self.is_acyclic_without(&feedback) {
+            // not expected, all edges left then point forward
+            return backwards;
+        }
+        return backwards.into_iter().filter(|edge| feedback.contains(edge)).collect();
+    }
+
+    fn reaches_without(&self, from: usize, to: usize, removed: &HashSet<(usize, usize)>) -> bool {
+        let mut visited = vec![false; self.nodes.len()];
+        let mut stack = vec![from];
+        visited[from] = true;
+        while let Some(index) = stack.pop() {
+            if index == to {
+                return true;
+            }
+            for child_index in self.nodes[index].childrens.iter() {
+                if !visited[*child_index] && !removed.contains(&(index, *child_index)) {
+                    visited[*child_index] = true;
+                    stack.push(*child_index);
+                }
+            }
+        }
+        return false;
+    }
+
+    fn is_acyclic_without(&self, removed: &HashSet<(usize, usize)>) -> bool {
+        let mut in_degrees = vec![0usize; self.nodes.len()];
+        for node in self.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                if !removed.contains(&(node.index, *child_index)) {
+                    in_degrees[*child_index] += 1;
+                }
+            }
+        }
+        let mut queue: Vec<usize> = (0..self.nodes.len()).filter(|index| in_degrees[*index] == 0).collect();
+        let mut n_visited = 0;
+        while let Some(index) = queue.pop() {
+            n_visited += 1;
+            for child_index in self.nodes[index].childrens.iter() {
+                if removed.contains(&(index, *child_index)) {
+                    continue;
+                }
+                in_degrees[*child_index] -= 1;
+                if in_degrees[*child_index] == 0 {
+                    queue.push(*child_index);
+                }
+            }
+        }
+        return n_visited == self.nodes.len();
+    }
+
+    // Removes every redundant edge, leaving which node reaches which as it
+    // was. Returns the number of removed edges.
+    pub fn transitive_reduction(&mut self) -> usize {
+        let redundant = self.redundant_edge_indices();
+        for (parent, child) in redundant.iter() {
+            self.remove_child(*parent, *child);
+        }
+        return redundant.len();
+    }
+
+    // `c` is reachable from `a` other than through the edge `a -> c` iff it
+    // is a descendant of another child of `a`. Descendant sets are bitsets
+    // built children first.
+    fn redundant_edge_indices(&self) -> Vec<(usize, usize)> {
+        let n_node = self.nodes.len();
+        let Some(order) = self.topological_order() else {
+            return vec![];
+        };
+        let n_word = n_node.div_ceil(64);
+        let mut descendants = vec![0u64; n_node * n_word];
+        for index in order.iter().rev() {
+            for child_index in self.nodes[*index].childrens.iter() {
+                let (to, from) = (*index * n_word, *child_index * n_word);
+                for i in 0..n_word {
+                    descendants[to + i] |= descendants[from + i];
+                }
+                descendants[to + *child_index / 64] |= 1 << (*child_index % 64);
+            }
+        }
+        let mut redundant = vec![];
+        for node in self.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                let implied = node.childrens.iter().any(|other| {
+                    let word = descendants[*other * n_word + *child_index / 64];
+                    return other != child_index && word & (1 << (*child_index % 64)) != 0;
+                });
+                if implied {
+                    redundant.push((node.index, *child_index));
+                }
+            }
+        }
+        return redundant;
+    }
+
+    // Groups of nodes connected by edges in either direction. Nodes keep
+    // their insertion order, groups are ordered by their first node.
+    pub fn weakly_connected_components(&self) -> Vec<Vec<String>> {
+        return self.component_indices().into_iter()
+            .map(|component| component.into_iter().map(|index| self.nodes[index].name.to_string()).collect())
+            .collect();
+    }
+
+    pub(crate) fn component_indices(&self) -> Vec<Vec<usize>> {
+        let n_node = self.nodes.len();
+        let mut leaders: Vec<usize> = (0..n_node).collect();
+        fn find(leaders: &mut [usize], mut index: usize) -> usize {
+            while leaders[index] != index {
+                leaders[index] = leaders[leaders[index]];
+                index = leaders[index];
+            }
+            return index;
+        }
+        for node in self.nodes.iter() {
+            for child_index in node.childrens.iter() {
+                let (a, b) = (find(&mut leaders, node.index), find(&mut leaders, *child_index));
+                leaders[a.max(b)] = a.min(b);
+            }
+        }
+        let mut components: Vec<Vec<usize>> = vec![];
+        let mut component_of: Vec<Option<usize>> = vec![None; n_node];
+        for index in 0..n_node {
+            let leader = find(&mut leaders, index);
+            match component_of[leader] {
+                Some(i) => components[i].push(index),
+                None => {
+                    component_of[leader] = Some(components.len());
+                    components.push(vec![index]);
+                },
+            }
+        }
+        return components;
+    }
+
+    fn topological_order(&self) -> Option<Vec<usize>> {
+        let mut in_degrees: Vec<usize> = self.nodes.iter().map(|node| node.parent_count).collect();
+        let mut order: Vec<usize> = (0..self.nodes.len()).filter(|index| in_degrees[*index] == 0).collect();
+        let mut i = 0;
+        while i < order.len() {
+            for child_index in self.nodes[order[i]].childrens.iter() {
+                in_degrees[*child_index] -= 1;
+                if in_degrees[*child_index] == 0 {
+                    order.push(*child_index);
+                }
+            }
+            i += 1;
+        }
+        if order.len() < self.nodes.len() {
+            return None;
+        }
+        return Some(order);
+    }
+}
+
+impl<C> FrozenGraph<C> {
+    pub fn fingerprint(&self) -> u64 {
+        return self.graph.fingerprint();
+    }
+
+    pub fn node_count(&self) -> usize {
+        return self.graph.nodes.len();
+    }
+
+    pub fn edge_count(&self) -> usize {
+        return self.childrens.len();
+    }
+
+    // in the same order as `Graph::edges`
+    pub fn edges(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        let nodes = &self.graph.nodes;
+        return (0..nodes.len()).flat_map(move |index| {
+            return self.childrens_of(index).iter().map(move |child_index| (&*nodes[index].name, &*nodes[*child_index as usize].name));
+        });
+    }
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn new() -> Fnv {
+        return Fnv(0xcbf29ce484222325);
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn write_usize(&mut self, n: usize) {
+        self.write(&(n as u64).to_le_bytes());
+    }
+
+    // length prefixed, so that "ab" + "c" differs from "a" + "bc"
+    fn write_str(&mut self, s: &str) {
+        self.write_usize(s.len());
+        self.write(s.as_bytes());
+    }
+
+    fn finish(&self) -> u64 {
+        return self.0;
+    }
+}
+
+impl<C> FrozenGraph<C> {
+    // The path whose node costs add up the most, that is the shortest
+    // possible run with unlimited workers, and that sum.
+    pub fn critical_path(&self) -> (Duration, Vec<String>) {
+        let graph = &self.graph;
+        let n_node = graph.nodes.len();
+        let order = self.topological_order();
+        // longest path ending at every node, and where it came from
+        let mut finish = vec![Duration::ZERO; n_node];
+        let mut via: Vec<Option<usize>> = vec![None; n_node];
+        for index in order {
+            finish[index] += graph.cost_of(index);
+            for child_index in self.childrens_of(index) {
+                let child_index = *child_index as usize;
+                if via[child_index].is_none() || finish[index] > finish[child_index] {
+                    finish[child_index] = finish[index];
+                    via[child_index] = Some(index);
+                }
+            }
+        }
+        let Some(last) = (0..n_node).max_by_key(|index| (finish[*index], Reverse(*index))) else {
+            return (Duration::ZERO, vec![]);
+        };
+        return (finish[last], self.path_to(last, &via));
+    }
+
+    pub(crate) fn topological_order(&self) -> Vec<usize> {
+        let mut in_degrees = self.in_degrees.clone();
+        let mut order: Vec<usize> = self.roots.clone();
+        let mut i = 0;
+        while i < order.len() {
+            for child_index in self.childrens_of(order[i]) {
+                let child_index = *child_index as usize;
+                in_degrees[child_index] -= 1;
+                if in_degrees[child_index] == 0 {
+                    order.push(child_index);
+                }
+            }
+            i += 1;
+        }
+        return order;
+    }
+
+    // Simulates a run with `workers` workers (0 means unlimited) where every
+    // node takes exactly its cost, dispatching ready nodes in the same order
+    // as the scheduler does, and returns how long it takes.
+    pub fn estimate_makespan(&self, workers: usize) -> Duration {
+        let costs: Vec<Duration> = (0..self.graph.nodes.len()).map(|index| self.graph.cost_of(index)).collect();
+        return self.list_schedule(&costs, workers, &mut vec![Duration::ZERO; costs.len()]);
+    }
+
+    // Same as `estimate_makespan`, with the costs taken from `costs` by node
+    // name instead of from the graph, and more details about the outcome.
+    pub fn simulate(&self, costs: &HashMap<String, Duration>, workers: usize) -> SimulationResult {
+        let graph = &self.graph;
+        let mut missing_costs = vec![];
+        let costs: Vec<Duration> = graph.nodes.iter().map(|node| match costs.get(&*node.name) {
+            Some(cost) => *cost,
+            None => {
+                missing_costs.push(node.name.to_string());
+                Duration::ZERO
+            },
+        }).collect();
+        let mut starts = vec![Duration::ZERO; costs.len()];
+        let makespan = self.list_schedule(&costs, workers, &mut starts);
+        let busy: Duration = costs.iter().sum();
+        let n_worker = match workers {
+            0 => self.max_width(&costs, &starts),
+            n => n,
+        };
+        let utilization = match makespan.is_zero() {
+            true => 0.0,
+            false => busy.as_secs_f32() / (n_worker as f32 * makespan.as_secs_f32()),
+        };
+        return SimulationResult{
+            makespan: makespan,
+            start_times: graph.nodes.iter().map(|node| (node.name.to_string(), starts[node.index])).collect(),
+            utilization: utilization,
+            missing_costs: missing_costs,
+        };
+    }
+
+    // most nodes running at the same time, the same way as
+    // `RunReport::max_parallelism_observed` counts them
+    fn max_width(&self, costs: &[Duration], starts: &[Duration]) -> usize {
+        let mut events: Vec<(Duration, i32)> = vec![];
+        for (start, cost) in starts.iter().zip(costs.iter()) {
+            events.push((*start, 1));
+            events.push((*start + *cost, -1));
+        }
+        events.sort();
+        let mut n_running: i32 = 0;
+        let mut max_running: i32 = 0;
+        for (_, delta) in events {
+            n_running += delta;
+            max_running = max_running.max(n_running);
+        }
+        return max_running as usize;
+    }
+
+    // discrete-event list scheduling, recording when every node starts
+    fn list_schedule(&self, costs: &[Duration], workers: usize, starts: &mut [Duration]) -> Duration {
+        let workers = if workers == 0 { usize::MAX } else { workers };
+        let mut n_unfinished = self.in_degrees.clone();
+        let mut ready: BinaryHeap<Reverse<usize>> = self.roots.iter()
+            .map(|index| Reverse(*index)).collect();
+        let mut running: BinaryHeap<Reverse<(Duration, usize)>> = BinaryHeap::new();
+        let mut now = Duration::ZERO;
+        loop {
+            while running.len() < workers {
+                let Some(Reverse(index)) = ready.pop() else {
+                    break;
+                };
+                starts[index] = now;
+                running.push(Reverse((now + costs[index], index)));
+            }
+            let Some(Reverse((finished_at, index))) = running.pop() else {
+                return now;
+            };
+            now = finished_at;
+            for child_index in self.childrens_of(index) {
+                let child_index = *child_index as usize;
+                n_unfinished[child_index] -= 1;
+                if n_unfinished[child_index] == 0 {
+                    ready.push(Reverse(child_index));
+                }
+            }
+        }
+    }
+}
+
+// What `FrozenGraph::simulate` predicts. `utilization` is the busy fraction
+// of all workers over the whole run, with unlimited workers counted as the
+// most that were busy at once. Nodes without a cost take no time and are
+// listed in `missing_costs`, in insertion order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SimulationResult {
+    pub makespan: Duration,
+    pub start_times: HashMap<String, Duration>,
+    pub utilization: f32,
+    pub missing_costs: Vec<String>,
+}
+
+impl<C> FrozenGraph<C> {
+    // the given nodes and everything depending on them
+    pub(crate) fn descendants_mask(&self, indices: &[usize]) -> Vec<bool> {
+        let mut mask = vec![false; self.graph.nodes.len()];
+        let mut stack = indices.to_vec();
+        while let Some(index) = stack.pop() {
+            if !mask[index] {
+                mask[index] = true;
+                stack.extend(self.childrens_of(index).iter().map(|index| *index as usize));
+            }
+        }
+        return mask;
+    }
+
+    // the given nodes and everything they depend on
+    pub(crate) fn ancestors_mask(&self, indices: &[usize]) -> Vec<bool> {
+        let n_node = self.graph.nodes.len();
+        let mut parents: Vec<Vec<usize>> = vec![vec![]; n_node];
+        for index in 0..n_node {
+            for child_index in self.childrens_of(index) {
+                parents[*child_index as usize].push(index);
+            }
+        }
+        let mut mask = vec![false; n_node];
+        let mut stack = indices.to_vec();
+        while let Some(index) = stack.pop() {
+            if !mask[index] {
+                mask[index] = true;
+                stack.extend(parents[index].iter());
+            }
+        }
+        return mask;
+    }
+
+    // whether `to` depends on `from`, directly or not; a node reaches itself
+    pub fn is_reachable(&self, from: &str, to: &str) -> Result<bool, Error> {
+        return Ok(self.shortest_path(from, to)?.is_some());
+    }
+
+    // the path from `from` to `to` with the fewest edges, both ends included
+    pub fn shortest_path(&self, from: &str, to: &str) -> Result<Option<Vec<String>>, Error> {
+        let (from, to) = (self.graph.index_of(from)?, self.graph.index_of(to)?);
+        let mut via: Vec<Option<usize>> = vec![None; self.graph.nodes.len()];
+        let mut queue = VecDeque::from([from]);
+        while let Some(index) = queue.pop_front() {
+            if index == to {
+                return Ok(Some(self.path_to(to, &via)));
+            }
+            for child_index in self.childrens_of(index) {
+                let child_index = *child_index as usize;
+                if via[child_index].is_none() && child_index != from {
+                    via[child_index] = Some(index);
+                    queue.push_back(child_index);
+                }
+            }
+        }
+        return Ok(None);
+    }
+
+    // the path from `from` to `to` with the most edges, both ends included
+    pub fn longest_path(&self, from: &str, to: &str) -> Result<Option<Vec<String>>, Error> {
+        let (from, to) = (self.graph.index_of(from)?, self.graph.index_of(to)?);
+        let n_node = self.graph.nodes.len();
+        let mut length: Vec<Option<usize>> = vec![None; n_node];
+        let mut via: Vec<Option<usize>> = vec![None; n_node];
+        length[from] = Some(0);
+        for index in self.topological_order() {
+            let Some(n) = length[index] else {
+                continue;
+            };
+            for child_index in self.childrens_of(index) {
+                let child_index = *child_index as usize;
+                if length[child_index].is_none_or(|m| m < n + 1) {
+                    length[child_index] = Some(n + 1);
+                    via[child_index] = Some(index);
+                }
+            }
+        }
+        if length[to].is_none() {
+            return Ok(None);
+        }
+        return Ok(Some(self.path_to(to, &via)));
+    }
+
+    fn path_to(&self, to: usize, via: &[Option<usize>]) -> Vec<String> {
+        let mut path = vec![self.graph.nodes[to].name.to_string()];
+        let mut current = to;
+        while let Some(parent) = via[current] {
+            path.push(self.graph.nodes[parent].name.to_string());
+            current = parent;
+        }
+        path.reverse();
+        return path;
+    }
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "anyhow")]
+#![allow(clippy::needless_return)]
+
+use std::io;
+
+use anyhow::Context;
+
+use dag_engine::{Error::*, AnyhowError, Graph, Scheduler};
+
+fn read_config(_: &()) -> anyhow::Result<()> {
+    let err = io::Error::new(io::ErrorKind::NotFound, "no config");
+    Err(err)?;
+    return Ok(());
+}
+
+#[test]
+fn downcast() {
+    let mut g = Graph::new();
+    g.add_node_anyhow("A", |_: &()| -> anyhow::Result<()> { Ok(()) }).unwrap();
+    g.add_node_anyhow("B", read_config).unwrap();
+    g.add_edge("A", "B").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    assert!(s.run(&()).is_err_and(|e| -> bool {
+        if let RuntimeFailed{node, err, ..} = e {
+            let err = &err.downcast_ref::<AnyhowError>().unwrap().0;
+            if let Some(err) = err.downcast_ref::<io::Error>() {
+                return node == "B" && err.kind() == io::ErrorKind::NotFound;
+            }
+        }
+        return false;
+    }));
+}
+
+#[test]
+fn context_chain() {
+    let mut g = Graph::new();
+    g.add_node_anyhow("A", |ctx: &()| -> anyhow::Result<()> {
+        return read_config(ctx).context("loading config");
+    }).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    assert!(s.run(&()).is_err_and(|e| -> bool {
+        if let RuntimeFailed{err, ..} = e {
+            let source = err.source().unwrap().to_string();
+            let err = &err.downcast_ref::<AnyhowError>().unwrap().0;
+            return err.to_string() == "loading config" && source == "no config"
+                && err.root_cause().downcast_ref::<io::Error>().is_some();
+        }
+        return false;
+    }));
+}
@@ -0,0 +1,82 @@
+#![allow(clippy::needless_return)]
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use dag_engine::{Error::*, TaskError, Task, Graph, Scheduler, Checkpoint, FileCheckpoint};
+
+struct ResumeContext {
+    executed: Mutex<Vec<String>>,
+    broken: AtomicBool,
+}
+
+fn resume_task(name: &'static str) -> Task<ResumeContext> {
+    Box::new(move |ctx: &ResumeContext| -> Result<(), TaskError> {
+        if name == "C" && ctx.broken.load(Ordering::Relaxed) {
+            return Err(Box::new(std::fmt::Error));
+        }
+        ctx.executed.lock().unwrap().push(name.to_string());
+        Ok(())
+    })
+}
+
+fn temp_path(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("dag_engine_{}_{}", std::process::id(), name));
+    let _ = fs::remove_file(&path);
+    return path;
+}
+
+#[test]
+fn resume_after_failure() {
+    let mut g = Graph::new();
+    for name in ["A", "B", "C", "D"] {
+        g.add_node(name, resume_task(name)).unwrap();
+    }
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.add_edge("C", "D").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let checkpoint = FileCheckpoint::new(temp_path("resume")).sync(true);
+    let ctx = ResumeContext{
+        executed: Mutex::new(vec![]),
+        broken: AtomicBool::new(true),
+    };
+
+    assert!(s.run_resumable(&ctx, "run-1", &checkpoint).is_err_and(
+        |e| if let RuntimeFailed{node, ..} = e { node == "C" } else { false }
+    ));
+    assert_eq!(*ctx.executed.lock().unwrap(), ["A", "B"]);
+
+    ctx.broken.store(false, Ordering::Relaxed);
+    ctx.executed.lock().unwrap().clear();
+    let summary = s.run_resumable(&ctx, "run-1", &checkpoint).unwrap();
+    assert_eq!(*ctx.executed.lock().unwrap(), ["C", "D"]);
+    assert_eq!((summary.executed, summary.skipped, summary.cached), (2, 0, 2));
+
+    // another run id starts from scratch
+    ctx.executed.lock().unwrap().clear();
+    s.run_resumable(&ctx, "run-2", &checkpoint).unwrap();
+    assert_eq!(*ctx.executed.lock().unwrap(), ["A", "B", "C", "D"]);
+    fs::remove_file(checkpoint.path()).unwrap();
+}
+
+#[test]
+fn corrupted_file() {
+    let path = temp_path("corrupted");
+    let mut file = fs::File::create(&path).unwrap();
+    file.write_all(b"run\tA\nrun\tX\ngarbage\n\xff\xfe\nother\tB\nrun\tB").unwrap();
+    drop(file);
+    let checkpoint = FileCheckpoint::new(&path);
+    let mut done: Vec<String> = checkpoint.load_done("run").unwrap().into_iter().collect();
+    done.sort();
+    assert_eq!(done, ["A", "X"]);
+
+    checkpoint.mark_done("run", "C").unwrap();
+    let done = checkpoint.load_done("run").unwrap();
+    assert!(done.contains("C") && !done.contains("B"));
+    assert!(FileCheckpoint::new(temp_path("missing")).load_done("run").unwrap().is_empty());
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,67 @@
+#![allow(clippy::needless_return)]
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use dag_engine::{TaskError, Task, Graph, SchedulerBuilder, CostModel, DispatchStrategy};
+
+fn sleep_task(name: &'static str, duration_ms: u64) -> Task<Mutex<Vec<String>>> {
+    Box::new(move |ctx: &Mutex<Vec<String>>| -> Result<(), TaskError> {
+        ctx.lock().unwrap().push(name.to_string());
+        thread::sleep(Duration::from_millis(duration_ms));
+        Ok(())
+    })
+}
+
+#[test]
+fn record() {
+    let mut model = CostModel::with_alpha(0.25);
+    assert_eq!(model.estimate("A"), None);
+    model.record("A", Duration::from_millis(100));
+    assert_eq!(model.estimate("A"), Some(Duration::from_millis(100)));
+    model.record("A", Duration::from_millis(200));
+    assert_eq!(model.estimate("A"), Some(Duration::from_millis(125)));
+    assert_eq!(model.costs().len(), 1);
+    assert_eq!(CostModel::new().alpha(), 0.5);
+}
+
+#[test]
+fn critical_path_strategy() {
+    let mut g = Graph::new();
+    g.add_node("short", sleep_task("short", 10)).unwrap();
+    g.add_node("long", sleep_task("long", 60)).unwrap();
+    g.add_node("after_short", sleep_task("after_short", 10)).unwrap();
+    g.add_edge("short", "after_short").unwrap();
+    let model = Arc::new(Mutex::new(CostModel::new()));
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .max_concurrency(1)
+        .dispatch_strategy(DispatchStrategy::CriticalPath)
+        .cost_model(model.clone())
+        .build();
+
+    // nothing known yet, ties go by insertion order
+    let ctx = Mutex::new(vec![]);
+    s.run(&ctx).unwrap();
+    assert_eq!(*ctx.lock().unwrap(), ["short", "long", "after_short"]);
+    for (name, duration_ms) in [("short", 10), ("long", 60), ("after_short", 10)] {
+        let estimate = model.lock().unwrap().estimate(name).unwrap();
+        assert!(Duration::from_millis(duration_ms) <= estimate, "{}: {:?}", name, estimate);
+        assert!(estimate < Duration::from_millis(duration_ms + 50), "{}: {:?}", name, estimate);
+    }
+
+    let ctx = Mutex::new(vec![]);
+    s.run(&ctx).unwrap();
+    assert_eq!(*ctx.lock().unwrap(), ["long", "short", "after_short"]);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json() {
+    let mut model = CostModel::with_alpha(0.1);
+    model.record("A", Duration::from_millis(100));
+    model.record("B", Duration::from_micros(1));
+    let restored = CostModel::from_json(&model.to_json()).unwrap();
+    assert_eq!(restored, model);
+    assert!(CostModel::from_json("{").is_err());
+}
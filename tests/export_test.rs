@@ -0,0 +1,32 @@
+#![allow(clippy::needless_return)]
+
+use dag_engine::{TaskError, Graph};
+
+fn dummy_task(_: &()) -> Result<(), TaskError> {
+    Ok(())
+}
+
+#[test]
+fn graphml() {
+    let mut g = Graph::new();
+    g.add_node("fetch", dummy_task).unwrap();
+    g.add_node("<parse & \"check\">", dummy_task).unwrap();
+    g.add_node("store", dummy_task).unwrap();
+    g.add_edge("fetch", "<parse & \"check\">").unwrap();
+    g.add_edge("<parse & \"check\">", "store").unwrap();
+    g.add_edge("fetch", "store").unwrap();
+    g.set_inline("store", true).unwrap();
+
+    let xml = g.to_graphml();
+    assert!(xml.starts_with("<?xml"));
+    assert!(xml.contains("edgedefault=\"directed\""));
+    assert_eq!(xml.matches("<node ").count(), 3);
+    assert_eq!(xml.matches("<edge ").count(), 3);
+    assert!(xml.contains("<data key=\"name\">&lt;parse &amp; &quot;check&quot;&gt;</data>"));
+    assert!(!xml.contains("<parse"));
+    assert!(xml.contains("<edge source=\"n0\" target=\"n1\"/>"));
+    assert!(xml.contains("<edge source=\"n0\" target=\"n2\"/>"));
+    assert!(xml.contains("<data key=\"inline\">true</data>"));
+    assert_eq!(xml.matches("<graph ").count(), 1);
+    assert!(xml.trim_end().ends_with("</graphml>"));
+}
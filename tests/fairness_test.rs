@@ -0,0 +1,46 @@
+#![allow(clippy::needless_return)]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+use dag_engine::{TaskError, Graph, RunOptions, Scheduler, SchedulerBuilder, ThreadPoolSpawner};
+
+fn cheap_task(ctx: &AtomicUsize) -> Result<(), TaskError> {
+    std::thread::sleep(Duration::from_millis(1));
+    ctx.fetch_add(1, Ordering::SeqCst);
+    return Ok(());
+}
+
+fn scheduler_on(n_node: usize, pool: &Arc<ThreadPoolSpawner>) -> Scheduler<AtomicUsize> {
+    let mut g = Graph::new();
+    for i in 0..n_node {
+        g.add_node(&i.to_string(), cheap_task).unwrap();
+    }
+    return SchedulerBuilder::new(g.froze().unwrap()).spawner(pool.clone()).build();
+}
+
+#[test]
+fn shared_pool() {
+    let pool = Arc::new(ThreadPoolSpawner::new(2));
+    let big = scheduler_on(1000, &pool);
+    let small = scheduler_on(5, &pool);
+
+    let ctx = AtomicUsize::new(0);
+    small.run(&ctx).unwrap();
+
+    let big_done = AtomicUsize::new(0);
+    std::thread::scope(|s| {
+        s.spawn(|| big.run(&big_done).unwrap());
+        while big_done.load(Ordering::SeqCst) == 0 {
+            std::thread::yield_now();
+        }
+        let before = big_done.load(Ordering::SeqCst);
+        small.run_with(&ctx, RunOptions::default().priority(4)).unwrap();
+        let after = big_done.load(Ordering::SeqCst);
+        // queued behind the big run, it would only finish with it
+        assert!(after - before < 500, "{} nodes of the big run done meanwhile", after - before);
+    });
+    assert_eq!(big_done.load(Ordering::SeqCst), 1000);
+    assert_eq!(ctx.load(Ordering::SeqCst), 10);
+}
@@ -0,0 +1,69 @@
+#![allow(clippy::needless_return)]
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use dag_engine::{Error::*, TaskError, Graph, GraphBuilder, FrozeOptions, Scheduler};
+
+fn record(name: &'static str) -> impl Fn(&Mutex<Vec<&'static str>>) -> Result<(), TaskError> {
+    return move |ctx: &Mutex<Vec<&'static str>>| {
+        ctx.lock().unwrap().push(name);
+        Ok(())
+    };
+}
+
+#[test]
+fn build_and_query() {
+    let mut b = GraphBuilder::new();
+    for name in ["A", "B", "C", "D"] {
+        b.add_node(name, record(name)).unwrap();
+    }
+    b.add_edge("A", "B").unwrap();
+    b.add_edge("A", "C").unwrap();
+    b.add_edge("B", "D").unwrap();
+    b.add_edge("C", "D").unwrap();
+    assert!(b.add_edge("A", "X").is_err_and(|e| if let NodeNotFound{name} = e { name == "X" } else { false }));
+    let frozen = b.build().unwrap();
+
+    assert_eq!(frozen.node_names(), ["A", "B", "C", "D"]);
+    assert_eq!(frozen.roots(), ["A"]);
+    assert!(frozen.contains("D") && !frozen.contains("X"));
+    assert_eq!(frozen.children("A").unwrap(), ["B", "C"]);
+    assert_eq!(frozen.parents("D").unwrap(), ["B", "C"]);
+    assert!(frozen.parents("A").unwrap().is_empty());
+    assert!(frozen.children("X").is_err_and(|e| if let NodeNotFound{name} = e { name == "X" } else { false }));
+    assert_eq!((frozen.node_count(), frozen.edge_count()), (4, 4));
+
+    let ctx = Mutex::new(vec![]);
+    Scheduler::new(frozen).run_sequential(&ctx).unwrap();
+    assert_eq!(*ctx.lock().unwrap(), ["A", "B", "C", "D"]);
+}
+
+#[test]
+fn build_with() {
+    let mut b: GraphBuilder<Mutex<Vec<&'static str>>> = GraphBuilder::default();
+    b.add_node("A", record("A")).unwrap();
+    b.add_node("B", record("B")).unwrap();
+    let options = FrozeOptions{require_single_root: true, ..FrozeOptions::default()};
+    assert!(b.build_with(options).is_err_and(|e| if let MultipleRoots{roots} = e { roots.len() == 2 } else { false }));
+
+    let mut b = GraphBuilder::new();
+    b.add_node("A", record("A")).unwrap();
+    b.add_node("B", record("B")).unwrap();
+    b.add_edge("A", "B").unwrap();
+    b.add_edge("B", "A").unwrap();
+    assert!(b.build().is_err_and(|e| matches!(e, CyclicGraphFound{..})));
+}
+
+#[test]
+fn from_graph() {
+    let mut g = Graph::new();
+    g.add_node("A", record("A")).unwrap();
+    g.set_cost("A", Duration::from_millis(10)).unwrap();
+    let mut b = GraphBuilder::from(g);
+    b.add_node("B", record("B")).unwrap();
+    b.add_edge("A", "B").unwrap();
+    let frozen = b.build().unwrap();
+    assert_eq!(frozen.children("A").unwrap(), ["B"]);
+    assert_eq!(frozen.critical_path(), (Duration::from_millis(10), vec![String::from("A")]));
+}
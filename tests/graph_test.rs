@@ -1,4 +1,10 @@
-use dag_engine::{Error::*, TaskError, Graph};
+#![allow(clippy::needless_return)]
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+use dag_engine::{Error::*, TaskError, Graph, FrozeOptions, LimitKind, Scheduler};
 
 fn dummy_task(_: &()) -> Result<(), TaskError> {
     Ok(())
@@ -22,8 +28,49 @@ fn invalid_task() {
     g.add_node("A", dummy_task).unwrap();
     g.add_node("B", dummy_task).unwrap();
     assert!(g.add_node("", dummy_task).is_err_and(
-        |e| if let InvalidNode{name} = e { name == "" } else { false }
+        |e| if let InvalidNode{name, ..} = e { name.is_empty() } else { false }
+    ));
+}
+
+#[test]
+fn name_validation() {
+    let mut g = Graph::new();
+    assert!(g.add_node("a\0b", dummy_task).is_err_and(
+        |e| if let InvalidNode{name, reason} = e { name == "a\0b" && reason.contains("'\\0'") } else { false }
+    ));
+    assert!(g.add_node("tab\there", dummy_task).is_err());
+    g.add_node("ingest/fetch", dummy_task).unwrap();
+
+    g.set_name_validator(|name| match name.contains(' ') {
+        true => Err(String::from("no spaces")),
+        false => Ok(()),
+    });
+    assert!(g.add_node("with space", dummy_task).is_err_and(
+        |e| if let InvalidNode{reason, ..} = e { reason == "no spaces" } else { false }
+    ));
+    g.add_node("without_space", dummy_task).unwrap();
+    assert!(g.rename_node("without_space", "with space").is_err_and(|e| matches!(e, InvalidNode{..})));
+}
+
+#[cfg(feature = "nfc")]
+#[test]
+fn normalized_names() {
+    let (composed, decomposed) = ("caf\u{e9}", "cafe\u{301}");
+    let mut g = Graph::new();
+    g.add_node(composed, dummy_task).unwrap();
+    g.add_node(decomposed, dummy_task).unwrap();
+
+    let mut g = Graph::new();
+    g.normalize_names(true);
+    g.add_node(composed, dummy_task).unwrap();
+    assert!(g.add_node(decomposed, dummy_task).is_err_and(
+        |e| if let DuplicatedNode{name} = e { name == composed } else { false }
     ));
+    g.add_node("menu", dummy_task).unwrap();
+    g.add_edge(decomposed, "menu").unwrap();
+    let frozen = g.froze().unwrap();
+    assert!(frozen.contains(decomposed));
+    assert_eq!(frozen.name_of(frozen.index_of(decomposed).unwrap()), Some(composed));
 }
 
 #[test]
@@ -34,6 +81,9 @@ fn duplicated_task() {
     assert!(g.add_node("B", dummy_task).is_err_and(
         |e| if let DuplicatedNode{name} = e { name == "B" } else { false }
     ));
+    let err = g.add_node("A", dummy_task).unwrap_err();
+    assert!(err.is_build() && !err.is_runtime());
+    assert_eq!(err.node_name(), None);
 }
 
 #[test]
@@ -48,11 +98,11 @@ fn invalid_edge() {
     ));
     assert!(g.add_edge("", "A").is_err_and(
         |e| if let InvalidEdge{from_node, to_node} = e {
-            from_node == "" && to_node == "A" } else { false }
+            from_node.is_empty() && to_node == "A" } else { false }
     ));
     assert!(g.add_edge("A", "").is_err_and(
         |e| if let InvalidEdge{from_node, to_node} = e {
-            from_node == "A" && to_node == "" } else { false }
+            from_node == "A" && to_node.is_empty() } else { false }
     ));
     assert!(g.add_edge("A", "C").is_err_and(
         |e| if let NodeNotFound{name} = e { name == "C" } else { false }
@@ -74,7 +124,7 @@ fn cyclic_graph() {
     g.add_edge("A", "B").unwrap();
     g.add_edge("B", "A").unwrap();
     assert!(g.froze().is_err_and(
-        |e| if let CyclicGraphFound{ring} = e {
+        |e| if let CyclicGraphFound{ring, ..} = e {
             ring == "[A, B]" } else { false }
     ));
     let mut g = Graph::new();
@@ -85,7 +135,391 @@ fn cyclic_graph() {
     g.add_edge("B", "C").unwrap();
     g.add_edge("C", "A").unwrap();
     assert!(g.froze().is_err_and(
-        |e| if let CyclicGraphFound{ring} = e {
+        |e| if let CyclicGraphFound{ring, ..} = e {
             ring == "[A, B, C]" } else { false }
     ));
-}
\ No newline at end of file
+}
+
+#[test]
+fn feedback_edges() {
+    let edges = [("X", "A"), ("A", "B"), ("B", "C"), ("C", "A"), ("C", "D"), ("D", "E"), ("E", "D"), ("E", "F")];
+    let graph_without = |removed: &[(String, String)]| -> Graph<()> {
+        let mut g = Graph::new();
+        for name in ["X", "A", "B", "C", "D", "E", "F"] {
+            g.add_node(name, dummy_task).unwrap();
+        }
+        for (from_node, to_node) in edges {
+            if !removed.iter().any(|(from, to)| from == from_node && to == to_node) {
+                g.add_edge(from_node, to_node).unwrap();
+            }
+        }
+        return g;
+    };
+    let g = graph_without(&[]);
+    let feedback = g.feedback_edges();
+    assert_eq!(feedback.len(), 2);
+    let in_cycle = |nodes: &[&str]| feedback.iter().filter(|(from, to)| nodes.contains(&from.as_str()) && nodes.contains(&to.as_str())).count();
+    assert_eq!((in_cycle(&["A", "B", "C"]), in_cycle(&["D", "E"])), (1, 1));
+    assert!(g.froze().is_err_and(|e| {
+        let message = e.to_string();
+        if let CyclicGraphFound{feedback_edges, ..} = e {
+            feedback_edges == feedback && message.contains(", try removing ")
+        } else { false }
+    }));
+
+    let g = graph_without(&feedback);
+    assert!(g.feedback_edges().is_empty());
+    g.froze().unwrap();
+}
+
+#[test]
+fn edge_index_order() {
+    let n_node: usize = 16;
+
+    let mut g = Graph::new();
+    for i in 0..n_node {
+        g.add_node(&i.to_string(), dummy_task).unwrap();
+    }
+    for i in 0..n_node {
+        for j in i+1..n_node {
+            g.add_edge(&i.to_string(), &j.to_string()).unwrap();
+        }
+    }
+    for i in 0..n_node {
+        for j in i+1..n_node {
+            let (si, sj) = (i.to_string(), j.to_string());
+            assert!(g.add_edge(&si, &sj).is_err_and(
+                |e| if let DuplicatedEdge{from_node, to_node} = e {
+                    from_node == si && to_node == sj } else { false }
+            ));
+        }
+    }
+    g.froze().unwrap();
+
+    let mut g = Graph::new();
+    for i in 0..n_node {
+        g.add_node(&i.to_string(), dummy_task).unwrap();
+    }
+    for i in 0..n_node {
+        for j in i+1..n_node {
+            g.add_edge(&j.to_string(), &i.to_string()).unwrap();
+        }
+    }
+    for i in 0..n_node {
+        for j in i+1..n_node {
+            let (si, sj) = (i.to_string(), j.to_string());
+            assert!(g.add_edge(&sj, &si).is_err_and(
+                |e| if let DuplicatedEdge{from_node, to_node} = e {
+                    from_node == sj && to_node == si } else { false }
+            ));
+        }
+    }
+    g.froze().unwrap();
+}
+
+#[test]
+fn edges() {
+    let mut g = Graph::new();
+    for name in ["A", "B", "C", "D"] {
+        g.add_node(name, dummy_task).unwrap();
+    }
+    g.add_edge("A", "C").unwrap();
+    g.add_edge("B", "D").unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("C", "D").unwrap();
+    // by parent, then in the order the edges were added
+    let edges = vec![("A", "C"), ("A", "B"), ("B", "D"), ("C", "D")];
+    assert_eq!(g.edges().collect::<Vec<_>>(), edges);
+    assert_eq!(g.edges().count(), g.edge_count());
+    let frozen = g.froze().unwrap();
+    assert_eq!(frozen.edges().collect::<Vec<_>>(), edges);
+    assert_eq!(frozen.edges().count(), frozen.edge_count());
+
+    let g: Graph<()> = Graph::new();
+    assert_eq!(g.edges().count(), 0);
+}
+
+#[test]
+fn many_nodes() {
+    let n_node: usize = 10000;
+    let t0 = Instant::now();
+    let mut g = Graph::new();
+    for i in 0..n_node {
+        g.add_node(&format!("node-{}", i), dummy_task).unwrap();
+        if i > 0 {
+            g.add_edge(&format!("node-{}", i - 1), &format!("node-{}", i)).unwrap();
+        }
+    }
+    assert!(g.add_node("node-42", dummy_task).is_err_and(
+        |e| if let DuplicatedNode{name} = e { name == "node-42" } else { false }
+    ));
+    assert!(g.add_edge("node-0", "node-1").is_err_and(
+        |e| if let DuplicatedEdge{from_node, to_node} = e {
+            from_node == "node-0" && to_node == "node-1" } else { false }
+    ));
+    g.froze().unwrap();
+    let cost = t0.elapsed();
+    println!("many_nodes cost: {} ms", cost.as_millis());
+    assert!(cost < Duration::from_secs(5));
+}
+
+#[test]
+fn bulk_construction() {
+    let (n_node, n_edge) = (100_000usize, 500_000usize);
+    let t0 = Instant::now();
+    let names: Vec<String> = (0..n_node).map(|i| format!("node-{}", i)).collect();
+    let mut g = Graph::with_capacity(n_node, n_edge);
+    g.begin_bulk();
+    for name in names.iter() {
+        g.add_node(name, dummy_task).unwrap();
+    }
+    let mut seed: usize = 42;
+    for i in 0..n_edge {
+        seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+        let from = i % (n_node - 1);
+        let to = from + 1 + (seed >> 33) % 100.min(n_node - from - 1);
+        g.add_edge(&names[from], &names[to]).unwrap();
+    }
+    // duplicates are fine until the graph is frozen
+    g.add_edge("node-0", "node-1").unwrap();
+    g.add_edge("node-0", "node-1").unwrap();
+    let frozen = g.froze().unwrap();
+    assert_eq!(frozen.node_count(), n_node);
+    Scheduler::new(frozen).run_sequential(&()).unwrap();
+    let cost = t0.elapsed();
+    println!("bulk_construction cost: {} ms", cost.as_millis());
+    assert!(cost < Duration::from_secs(60));
+
+    let mut g = Graph::new();
+    g.begin_bulk();
+    g.add_node("A", dummy_task).unwrap();
+    g.add_node("B", dummy_task).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("A", "B").unwrap();
+    assert_eq!(g.edge_count(), 2);
+    g.end_bulk();
+    assert_eq!(g.edge_count(), 1);
+    assert!(g.add_edge("A", "B").is_err_and(
+        |e| if let DuplicatedEdge{from_node, ..} = e { from_node == "A" } else { false }
+    ));
+    assert_eq!(g.froze().unwrap().edge_count(), 1);
+}
+
+#[test]
+fn clone() {
+    let n_run = Arc::new(AtomicU32::new(0));
+    let mut g: Graph<()> = Graph::new();
+    for name in ["A", "B"] {
+        let n_run = n_run.clone();
+        g.add_node(name, move |_: &()| -> Result<(), TaskError> {
+            n_run.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }).unwrap();
+    }
+    g.add_edge("A", "B").unwrap();
+    let mut copy = g.clone();
+    copy.add_node("C", dummy_task).unwrap();
+    copy.add_edge("B", "C").unwrap();
+    assert!(copy.add_edge("A", "B").is_err());
+    assert_eq!((g.node_count(), g.edge_count()), (2, 1));
+    assert_eq!((copy.node_count(), copy.edge_count()), (3, 2));
+    assert!(g.add_edge("B", "C").is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "C" } else { false }
+    ));
+
+    let frozen = g.froze().unwrap();
+    Scheduler::new(frozen.clone()).run(&()).unwrap();
+    Scheduler::new(frozen).run(&()).unwrap();
+    assert_eq!(n_run.load(Ordering::Relaxed), 4);
+    Scheduler::new(copy.froze().unwrap()).run(&()).unwrap();
+    assert_eq!(n_run.load(Ordering::Relaxed), 6);
+}
+
+fn two_chains() -> Graph<()> {
+    let mut g = Graph::new();
+    for name in ["A1", "B1", "A2", "B2", "C2"] {
+        g.add_node(name, dummy_task).unwrap();
+    }
+    g.add_edge("A1", "B1").unwrap();
+    g.add_edge("A2", "B2").unwrap();
+    g.add_edge("C2", "B2").unwrap();
+    return g;
+}
+
+#[test]
+fn connected_components() {
+    let g = two_chains();
+    assert_eq!(g.weakly_connected_components(), [vec!["A1", "B1"], vec!["A2", "B2", "C2"]]);
+    let options = FrozeOptions{require_connected: true, ..Default::default()};
+    let err = g.froze_with(options).err().unwrap();
+    assert!(err.is_build());
+    assert_eq!(err.to_string(), "graph is not connected: [A1, B1], [A2, B2, C2]");
+    assert!(matches!(err, DisconnectedGraph{components} if components.len() == 2));
+    two_chains().froze().unwrap();
+    two_chains().froze_with(FrozeOptions::default()).unwrap();
+
+    let mut g = two_chains();
+    g.add_edge("B1", "B2").unwrap();
+    assert_eq!(g.weakly_connected_components().len(), 1);
+    g.froze_with(options).unwrap();
+
+    let mut g = two_chains();
+    g.add_edge("B1", "B2").unwrap();
+    let options = FrozeOptions{require_single_root: true, ..Default::default()};
+    assert!(g.froze_with(options).is_err_and(
+        |e| if let MultipleRoots{roots} = e { roots == ["A1", "A2", "C2"] } else { false }
+    ));
+    let g: Graph<()> = Graph::new();
+    assert!(g.weakly_connected_components().is_empty());
+    g.froze_with(FrozeOptions{require_connected: true, require_single_root: true, ..Default::default()}).unwrap();
+}
+
+#[test]
+fn dsl() {
+    let mut manual = Graph::new();
+    for name in ["A", "B", "C", "D"] {
+        manual.add_node(name, dummy_task).unwrap();
+    }
+    manual.add_edge("A", "B").unwrap();
+    manual.add_edge("B", "C").unwrap();
+    manual.add_edge("A", "D").unwrap();
+    manual.add_edge("D", "C").unwrap();
+
+    let mut g = Graph::new();
+    for name in ["A", "B", "C", "D"] {
+        g.add_node(name, dummy_task).unwrap();
+    }
+    g.add_edges_dsl("A -> B -> C; A -> D\n  D->C ;;\n").unwrap();
+    assert!(g.topology_eq(&manual));
+
+    let g = Graph::from_dsl("A -> B -> C; A -> D; D -> C", |_| Box::new(dummy_task)).unwrap();
+    assert!(g.topology_eq(&manual));
+    g.froze().unwrap();
+
+    let mut g = Graph::new();
+    for name in ["A", "B"] {
+        g.add_node(name, dummy_task).unwrap();
+    }
+    assert!(g.add_edges_dsl("A -> -> B").is_err_and(
+        |e| if let InvalidDsl{token, position} = e { token == "->" && position == 5 } else { false }
+    ));
+    assert!(g.add_edges_dsl("A -> B; B ->").is_err_and(
+        |e| if let InvalidDsl{position, ..} = e { position == 10 } else { false }
+    ));
+    assert!(g.add_edges_dsl("-> A").is_err_and(
+        |e| if let InvalidDsl{position, ..} = e { position == 0 } else { false }
+    ));
+    assert!(g.add_edges_dsl("A -> X").is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "X" } else { false }
+    ));
+}
+
+#[test]
+fn namespaces() {
+    let mut g = Graph::new();
+    {
+        let mut ns = g.namespace("other");
+        ns.add_node("clean", dummy_task).unwrap();
+    }
+    {
+        let mut ns = g.namespace("ingest");
+        ns.add_node("fetch", dummy_task).unwrap();
+        ns.add_node("parse", dummy_task).unwrap();
+        ns.add_edge("fetch", "parse").unwrap();
+        assert!(ns.add_edge("other/clean", "fetch").is_err_and(
+            |e| if let NodeNotFound{name} = e { name == "ingest/other/clean" } else { false }
+        ));
+        assert!(ns.import("other/missing").is_err());
+        ns.import("other/clean").unwrap();
+        ns.add_edge("other/clean", "fetch").unwrap();
+        let mut s3 = ns.namespace("s3");
+        s3.add_node("list", dummy_task).unwrap();
+        s3.add_edge("list", "other/clean").unwrap();
+        assert_eq!(s3.name("list"), "ingest/s3/list");
+    }
+
+    let mut manual = Graph::new();
+    for name in ["other/clean", "ingest/fetch", "ingest/parse", "ingest/s3/list"] {
+        manual.add_node(name, dummy_task).unwrap();
+    }
+    manual.add_edges_dsl("ingest/s3/list -> other/clean -> ingest/fetch -> ingest/parse").unwrap();
+    assert!(g.topology_eq(&manual));
+    g.froze().unwrap();
+}
+
+#[test]
+fn limits() {
+    let mut g: Graph<()> = Graph::new();
+    g.max_fan_out(3);
+    for name in ["A", "B", "C", "D", "E", "F"] {
+        g.add_infallible_node(name, |_| {}).unwrap();
+    }
+    for child in ["B", "C", "D"] {
+        g.add_edge("A", child).unwrap();
+    }
+    assert!(g.add_edge("A", "E").is_err_and(|e| if let LimitExceeded{kind, limit} = e { kind == LimitKind::FanOut && limit == 3 } else { false }));
+    // already there, so not one more
+    assert!(g.add_edge_idempotent("A", "B").is_ok_and(|added| !added));
+    g.add_edge("B", "E").unwrap();
+    g.add_edge("E", "F").unwrap();
+
+    g.max_edges(6);
+    g.add_edge("C", "F").unwrap();
+    assert!(g.add_edge("D", "F").is_err_and(|e| if let LimitExceeded{kind, ..} = e { kind == LimitKind::Edges } else { false }));
+    g.max_nodes(6);
+    assert!(g.add_infallible_node("G", |_| {}).is_err_and(|e| if let LimitExceeded{kind, limit} = e { kind == LimitKind::Nodes && limit == 6 } else { false }));
+    assert_eq!((g.node_count(), g.edge_count()), (6, 6));
+    assert_eq!(g.reversed().edge_count(), 6);
+    assert_eq!(g.subgraph(|name| name != "A").edge_count(), 3);
+    g.froze().unwrap();
+
+    // room again for an edge once a redundant one is gone
+    let mut g: Graph<()> = Graph::new();
+    for name in ["A", "B", "C", "D"] {
+        g.add_infallible_node(name, |_| {}).unwrap();
+    }
+    g.max_edges(3);
+    for (from_node, to_node) in [("A", "B"), ("B", "C"), ("A", "C")] {
+        g.add_edge(from_node, to_node).unwrap();
+    }
+    assert!(g.add_edge("C", "D").is_err());
+    assert_eq!(g.transitive_reduction(), 1);
+    assert_eq!(g.edge_count(), 2);
+    g.add_edge("C", "D").unwrap();
+    assert!(g.add_edge("A", "D").is_err());
+}
+
+#[test]
+fn lenient() {
+    let mut g = Graph::lenient();
+    g.add_edge("A", "B");
+    g.add_node("A", dummy_task);
+    g.add_node("A", dummy_task);
+    g.add_node("", dummy_task);
+    g.add_node("B", dummy_task);
+    g.add_edge("B", "B");
+    g.add_edge("A", "C");
+    g.add_edge("A", "B");
+    let Err(err) = g.finish() else {
+        panic!("five problems went unnoticed");
+    };
+    assert!(err.is_build());
+    let message = err.to_string();
+    let BuildFailed{diagnostics} = err else {
+        panic!("not a BuildFailed: {}", err);
+    };
+    assert_eq!(diagnostics.len(), 5);
+    assert!(matches!(&diagnostics[0], DuplicatedNode{name} if name == "A"));
+    assert!(matches!(&diagnostics[1], InvalidNode{name, ..} if name.is_empty()));
+    assert!(matches!(&diagnostics[2], InvalidEdge{from_node, to_node} if from_node == "B" && to_node == "B"));
+    assert!(matches!(&diagnostics[3], NodeNotFound{name} if name == "C"));
+    assert!(matches!(&diagnostics[4], DuplicatedEdge{from_node, to_node} if from_node == "A" && to_node == "B"));
+    assert!(message.starts_with("5 problems building the graph: duplicated node: A; "));
+
+    let mut g = Graph::lenient();
+    g.add_edge("A", "B");
+    g.add_node("A", dummy_task);
+    g.add_node("B", dummy_task);
+    let frozen = g.finish().unwrap().froze().unwrap();
+    assert_eq!(frozen.children("A").unwrap(), ["B"]);
+}
@@ -0,0 +1,65 @@
+#![cfg(feature = "log")]
+#![allow(clippy::needless_return)]
+
+use std::sync::Mutex;
+
+use log::{Level, Log, Metadata, Record};
+
+use dag_engine::{TaskError, Graph, SchedulerBuilder, FailurePolicy};
+
+static RECORDS: Mutex<Vec<(Level, String)>> = Mutex::new(vec![]);
+
+struct CapturingLogger;
+
+impl Log for CapturingLogger {
+    fn enabled(&self, _: &Metadata<'_>) -> bool {
+        return true;
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        RECORDS.lock().unwrap().push((record.level(), record.args().to_string()));
+    }
+
+    fn flush(&self) {
+
+    }
+}
+
+fn failed_task(_: &()) -> Result<(), TaskError> {
+    Err(Box::new(std::fmt::Error))
+}
+
+#[test]
+fn records() {
+    log::set_logger(&CapturingLogger).unwrap();
+    log::set_max_level(log::LevelFilter::Debug);
+
+    let mut g: Graph<()> = Graph::new();
+    g.add_infallible_node("fine", |_| {}).unwrap();
+    g.add_node("rescued", failed_task).unwrap();
+    g.set_fallback("rescued", |_: &()| Ok(())).unwrap();
+    g.add_node("broken", failed_task).unwrap();
+    g.add_edge("fine", "rescued").unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .failure_policy(FailurePolicy::ContinueOnError)
+        .build();
+    assert!(s.run(&()).is_err());
+    assert!(s.run(&()).is_err());
+
+    let records = RECORDS.lock().unwrap();
+    let first_run = records[0].1.split(':').next().unwrap().to_string();
+    let records: Vec<_> = records.iter().filter(|(_, message)| message.starts_with(&format!("{}:", first_run))).collect();
+    let count = |level: Level| records.iter().filter(|(l, _)| *l == level).count();
+    // run started, 3 nodes started, 2 finished, run failed
+    assert_eq!(count(Level::Debug), 1 + 3 + 2 + 1);
+    assert_eq!(count(Level::Warn), 1);
+    assert_eq!(count(Level::Error), 1);
+    for (level, message) in records.iter() {
+        println!("{} {}", level, message);
+    }
+    for name in ["fine", "rescued", "broken"] {
+        assert!(records.iter().any(|(_, message)| message.contains(&format!("node {} started", name))));
+    }
+    assert!(records.iter().any(|(level, message)| *level == Level::Warn && message.contains("rescued")));
+    assert!(records.iter().any(|(level, message)| *level == Level::Error && message.contains("run broken failed")));
+}
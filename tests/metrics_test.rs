@@ -0,0 +1,93 @@
+#![allow(clippy::needless_return)]
+
+use std::sync::atomic::{Ordering, AtomicU32};
+
+use dag_engine::{TaskError, Task, Graph, Scheduler};
+
+#[derive(Debug)]
+struct FailedError;
+
+impl std::fmt::Display for FailedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "failed")
+    }
+}
+
+impl std::error::Error for FailedError {
+
+}
+
+fn task(outcome: &'static str) -> Task<AtomicU32> {
+    Box::new(move |ctx: &AtomicU32| -> Result<(), TaskError> {
+        ctx.fetch_add(1, Ordering::Relaxed);
+        return match outcome {
+            "fail" => Err(Box::new(FailedError)),
+            "panic" => panic!("panic"),
+            _ => Ok(()),
+        };
+    })
+}
+
+// A -> {B1, B2, B3} -> C
+fn graph(b2: &'static str) -> Graph<AtomicU32> {
+    let mut g = Graph::new();
+    g.add_node("A", task("")).unwrap();
+    g.add_node("B1", task("")).unwrap();
+    g.add_node("B2", task(b2)).unwrap();
+    g.add_node("B3", task("")).unwrap();
+    g.add_node("C", task("")).unwrap();
+    for b in ["B1", "B2", "B3"] {
+        g.add_edge("A", b).unwrap();
+        g.add_edge(b, "C").unwrap();
+    }
+    return g;
+}
+
+#[test]
+fn succeeded() {
+    let s = Scheduler::new(graph("").froze().unwrap());
+    let metrics = s.metrics();
+    s.run(&AtomicU32::new(0)).unwrap();
+    assert_eq!(metrics.runs(), 1);
+    assert_eq!(metrics.tasks_started(), 5);
+    assert_eq!(metrics.tasks_succeeded(), 5);
+    assert_eq!(metrics.tasks_failed(), 0);
+    assert_eq!(metrics.tasks_panicked(), 0);
+    assert_eq!(metrics.tasks_in_flight(), 0);
+    assert_eq!(metrics.ready_queue_depth(), 0);
+
+    s.run_sequential(&AtomicU32::new(0)).unwrap();
+    assert_eq!(metrics.runs(), 2);
+    assert_eq!(metrics.tasks_started(), 10);
+    assert_eq!(metrics.tasks_succeeded(), 10);
+    assert_eq!(metrics.tasks_in_flight(), 0);
+    assert_eq!(metrics.ready_queue_depth(), 0);
+}
+
+#[test]
+fn failed() {
+    let s = Scheduler::new(graph("fail").froze().unwrap());
+    let metrics = s.metrics();
+    let ctx = AtomicU32::new(0);
+    s.run(&ctx).unwrap_err();
+    let n_run = ctx.load(Ordering::Relaxed) as u64;
+    assert_eq!(metrics.runs(), 1);
+    assert_eq!(metrics.tasks_started(), n_run);
+    assert_eq!(metrics.tasks_succeeded(), n_run - 1);
+    assert_eq!(metrics.tasks_failed(), 1);
+    assert_eq!(metrics.tasks_panicked(), 0);
+    assert_eq!(metrics.tasks_in_flight(), 0);
+    assert_eq!(metrics.ready_queue_depth(), 0);
+
+    let s = Scheduler::new(graph("panic").froze().unwrap());
+    let metrics = s.metrics();
+    let ctx = AtomicU32::new(0);
+    s.run_sequential(&ctx).unwrap_err();
+    assert_eq!(ctx.load(Ordering::Relaxed), 3);
+    assert_eq!(metrics.tasks_started(), 3);
+    assert_eq!(metrics.tasks_succeeded(), 2);
+    assert_eq!(metrics.tasks_failed(), 0);
+    assert_eq!(metrics.tasks_panicked(), 1);
+    assert_eq!(metrics.tasks_in_flight(), 0);
+    assert_eq!(metrics.ready_queue_depth(), 0);
+}
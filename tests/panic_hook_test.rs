@@ -0,0 +1,74 @@
+#![allow(clippy::needless_return)]
+
+// A single test, as panic hooks are shared by the whole process.
+
+use std::panic::{self, PanicHookInfo};
+use std::sync::Arc;
+use std::sync::atomic::{Ordering, AtomicU32};
+
+use dag_engine::{Error::*, TaskError, Graph, Scheduler};
+
+type Hook = Box<dyn Fn(&PanicHookInfo<'_>) + Send + Sync + 'static>;
+
+fn counting_hook(counter: &Arc<AtomicU32>) -> Hook {
+    let counter = counter.clone();
+    return Box::new(move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+}
+
+// tells whether the hook installed is the very same as before
+fn address_of(hook: &Hook) -> usize {
+    return &**hook as *const _ as *const () as usize;
+}
+
+fn run_panicking() {
+    let mut g = Graph::new();
+    g.add_node("A", |_: &()| -> Result<(), TaskError> {
+        panic!("A");
+    }).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let err = s.run(&()).unwrap_err();
+    assert!(matches!(err, RuntimePanicked{info: Some(_), ..}));
+}
+
+#[test]
+fn application_hooks() {
+    // installed before a run, still called, the panic captured, and back in
+    // place after the run
+    let n_before = Arc::new(AtomicU32::new(0));
+    let hook = counting_hook(&n_before);
+    let address = address_of(&hook);
+    panic::set_hook(hook);
+    run_panicking();
+    assert_eq!(n_before.load(Ordering::SeqCst), 1);
+    let hook = panic::take_hook();
+    assert_eq!(address_of(&hook), address);
+    panic::set_hook(hook);
+
+    // replaced between runs, the next run captures all the same
+    let n_replaced = Arc::new(AtomicU32::new(0));
+    let hook = counting_hook(&n_replaced);
+    let address = address_of(&hook);
+    panic::set_hook(hook);
+    run_panicking();
+    assert_eq!((n_before.load(Ordering::SeqCst), n_replaced.load(Ordering::SeqCst)), (1, 1));
+    let hook = panic::take_hook();
+    assert_eq!(address_of(&hook), address);
+    panic::set_hook(hook);
+
+    // installed while a run is going on, kept after it
+    let n_during = Arc::new(AtomicU32::new(0));
+    let mut g = Graph::new();
+    let counter = n_during.clone();
+    g.add_node("install", move |_: &()| -> Result<(), TaskError> {
+        panic::set_hook(counting_hook(&counter));
+        Ok(())
+    }).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    s.run(&()).unwrap();
+    let _ = panic::catch_unwind(|| panic!("after"));
+    assert_eq!(n_during.load(Ordering::SeqCst), 1);
+    assert_eq!(n_replaced.load(Ordering::SeqCst), 1);
+    let _ = panic::take_hook();
+}
@@ -0,0 +1,70 @@
+#![cfg(feature = "petgraph")]
+#![allow(clippy::needless_return)]
+
+use std::collections::HashMap;
+
+use petgraph::graph::DiGraph;
+
+use dag_engine::{Error::*, TaskError, Task, Graph, Scheduler};
+
+fn dummy_task() -> Task<()> {
+    Box::new(|_: &()| -> Result<(), TaskError> {
+        Ok(())
+    })
+}
+
+const NAMES: [&str; 9] = ["A1", "A2", "A3", "B1", "B2", "B3", "C1", "C2", "C3"];
+const EDGES: [(&str, &str); 12] = [
+    ("A1", "B1"), ("A1", "B2"), ("A1", "B3"), ("A2", "B1"), ("A2", "B3"), ("A3", "B3"),
+    ("B1", "C2"), ("B1", "C3"), ("B2", "C2"), ("B3", "C1"), ("B3", "C2"), ("B3", "C3"),
+];
+
+fn sleep_dag() -> Graph<()> {
+    let mut g = Graph::new();
+    for name in NAMES {
+        g.add_node(name, dummy_task()).unwrap();
+    }
+    for (from, to) in EDGES {
+        g.add_edge(from, to).unwrap();
+    }
+    return g;
+}
+
+fn registry() -> HashMap<String, Task<()>> {
+    return NAMES.iter().map(|name| (name.to_string(), dummy_task())).collect();
+}
+
+#[test]
+fn round_trip() {
+    let g = sleep_dag();
+    let pg = DiGraph::<String, ()>::from(&g);
+    assert_eq!(pg.node_count(), 9);
+    assert_eq!(pg.edge_count(), 12);
+
+    let order = petgraph::algo::toposort(&pg, None).unwrap();
+    let position: HashMap<&str, usize> = order.iter().enumerate()
+        .map(|(i, index)| (pg[*index].as_str(), i)).collect();
+    for (from, to) in EDGES {
+        assert!(position[from] < position[to]);
+    }
+
+    let back = Graph::from_petgraph(&pg, registry()).unwrap();
+    assert!(back.topology_eq(&g));
+    assert_eq!(back.fingerprint(), g.fingerprint());
+    Scheduler::new(back.froze().unwrap()).run(&()).unwrap();
+}
+
+#[test]
+fn rejected() {
+    let mut pg = DiGraph::<String, ()>::from(&sleep_dag());
+    pg.add_node("A1".to_string());
+    assert!(Graph::from_petgraph(&pg, registry()).is_err_and(
+        |e| if let DuplicatedNode{name} = e { name == "A1" } else { false }
+    ));
+
+    let mut pg = DiGraph::<String, ()>::from(&sleep_dag());
+    pg.add_node("D1".to_string());
+    assert!(Graph::from_petgraph(&pg, registry()).is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "D1" } else { false }
+    ));
+}
@@ -0,0 +1,77 @@
+#![allow(clippy::needless_return)]
+
+// A single test, so that no other test adds threads while they are counted.
+
+use std::sync::atomic::{Ordering, AtomicU32};
+use std::time::{Duration, Instant};
+
+use dag_engine::{TaskError, Graph, SchedulerBuilder, ThreadPoolSpawner};
+
+fn counter_task(ctx: &AtomicU32) -> Result<(), TaskError> {
+    ctx.fetch_add(1, Ordering::Relaxed);
+    return Ok(());
+}
+
+fn n_thread() -> usize {
+    return std::fs::read_dir("/proc/self/task").map_or(0, |tasks| tasks.count());
+}
+
+#[test]
+fn prestart_workers() {
+    let mut g = Graph::new();
+    let n_node: u32 = 30;
+    for i in 0..n_node {
+        g.add_node(&i.to_string(), counter_task).unwrap();
+        if i >= 3 {
+            g.add_edge(&(i / 3).to_string(), &i.to_string()).unwrap();
+        }
+    }
+    let frozen = g.froze().unwrap();
+    let n_before = n_thread();
+
+    let s = SchedulerBuilder::new(frozen.clone())
+        .spawner(ThreadPoolSpawner::new(4))
+        .prestart_workers(true)
+        .build();
+    if cfg!(target_os = "linux") {
+        assert_eq!(n_thread(), n_before + 4);
+    }
+    let ctx = AtomicU32::new(0);
+    let mut slowest = Duration::ZERO;
+    for round in 0..100 {
+        let t0 = Instant::now();
+        s.run(&ctx).unwrap();
+        if round > 0 {
+            slowest = slowest.max(t0.elapsed());
+        }
+    }
+    println!("prestart_workers slowest run: {} us", slowest.as_micros());
+    assert!(slowest < Duration::from_millis(50));
+    assert_eq!(ctx.load(Ordering::Relaxed), 100 * n_node);
+    drop(s);
+    if cfg!(target_os = "linux") {
+        assert_eq!(n_thread(), n_before);
+    }
+
+    // started by the first run, or explicitly
+    let s = SchedulerBuilder::new(frozen)
+        .spawner(ThreadPoolSpawner::new(2))
+        .build();
+    if cfg!(target_os = "linux") {
+        assert_eq!(n_thread(), n_before);
+    }
+    s.warm_up();
+    s.warm_up();
+    if cfg!(target_os = "linux") {
+        assert_eq!(n_thread(), n_before + 2);
+    }
+    s.run(&ctx).unwrap();
+    drop(s);
+    if cfg!(target_os = "linux") {
+        assert_eq!(n_thread(), n_before);
+    }
+
+    let spawner = ThreadPoolSpawner::new(0);
+    assert!(spawner.n_worker() >= 1);
+    assert!(!spawner.is_started());
+}
@@ -0,0 +1,91 @@
+#![cfg(feature = "rayon")]
+#![allow(clippy::needless_return)]
+
+use std::sync::atomic::{Ordering, AtomicU32};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use dag_engine::{Error::*, TaskError, Task, Graph, Scheduler};
+
+fn sleep_task(duration_ms: u64) -> Task<AtomicU32> {
+    Box::new(move |ctx: &AtomicU32| -> Result<(), TaskError> {
+        thread::sleep(Duration::from_millis(duration_ms));
+        ctx.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    })
+}
+
+fn pool(n_thread: usize) -> rayon::ThreadPool {
+    return rayon::ThreadPoolBuilder::new().num_threads(n_thread).build().unwrap();
+}
+
+#[test]
+fn sleep_dag() {
+    let mut g = Graph::new();
+    g.add_node("A1", sleep_task(20)).unwrap();
+    g.add_node("A2", sleep_task(40)).unwrap();
+    g.add_node("A3", sleep_task(60)).unwrap();
+    g.add_node("B1", sleep_task(40)).unwrap();
+    g.add_node("B2", sleep_task(60)).unwrap();
+    g.add_node("B3", sleep_task(20)).unwrap();
+    g.add_node("C1", sleep_task(60)).unwrap();
+    g.add_node("C2", sleep_task(20)).unwrap();
+    g.add_node("C3", sleep_task(40)).unwrap();
+    g.add_edge("A1", "B1").unwrap();
+    g.add_edge("A1", "B2").unwrap();
+    g.add_edge("A1", "B3").unwrap();
+    g.add_edge("A2", "B1").unwrap();
+    g.add_edge("A2", "B3").unwrap();
+    g.add_edge("A3", "B3").unwrap();
+    g.add_edge("B1", "C2").unwrap();
+    g.add_edge("B1", "C3").unwrap();
+    g.add_edge("B2", "C2").unwrap();
+    g.add_edge("B3", "C1").unwrap();
+    g.add_edge("B3", "C2").unwrap();
+    g.add_edge("B3", "C3").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+
+    let ctx = AtomicU32::new(0);
+    s.run_on_pool(&pool(2), &ctx).unwrap();
+    assert_eq!(ctx.load(Ordering::Relaxed), 9);
+
+    let ctx = AtomicU32::new(0);
+    s.run_on_rayon(&ctx).unwrap();
+    assert_eq!(ctx.load(Ordering::Relaxed), 9);
+}
+
+#[test]
+fn sleep_layer() {
+    let mut g = Graph::new();
+    for i in 0..8 {
+        g.add_node(&i.to_string(), sleep_task(50)).unwrap();
+    }
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = AtomicU32::new(0);
+    let t0 = Instant::now();
+    s.run_on_pool(&pool(2), &ctx).unwrap();
+    let cost = t0.elapsed();
+    println!("sleep_layer cost: {} ms", cost.as_millis());
+    assert_eq!(ctx.load(Ordering::Relaxed), 8);
+    // two at a time, however slow the machine is otherwise
+    assert!(cost >= Duration::from_millis(200));
+    assert!(cost < Duration::from_secs(5));
+}
+
+#[test]
+fn failed() {
+    let mut g = Graph::new();
+    g.add_node("A", sleep_task(0)).unwrap();
+    g.add_node("B", |_: &AtomicU32| -> Result<(), TaskError> {
+        Err(Box::new(std::fmt::Error))
+    }).unwrap();
+    g.add_node("C", sleep_task(0)).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = AtomicU32::new(0);
+    assert!(s.run_on_pool(&pool(2), &ctx).is_err_and(
+        |e| if let RuntimeFailed{node, ..} = e { node == "B" } else { false }
+    ));
+    assert_eq!(ctx.load(Ordering::Relaxed), 1);
+}
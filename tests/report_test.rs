@@ -0,0 +1,356 @@
+#![allow(clippy::needless_return)]
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use dag_engine::{Error::*, TaskError, TaskCtx, Task, Graph, Scheduler, SchedulerBuilder, FailurePolicy, NodeStatus, Event, ManualClock};
+
+fn failed_task() -> Task<()> {
+    Box::new(|_: &()| -> Result<(), TaskError> {
+        Err(Box::new(std::fmt::Error))
+    })
+}
+
+fn sleep_task(duration_ms: u64) -> Task<()> {
+    Box::new(move |_: &()| -> Result<(), TaskError> {
+        thread::sleep(Duration::from_millis(duration_ms));
+        Ok(())
+    })
+}
+
+#[test]
+fn chain() {
+    let mut g = Graph::new();
+    g.add_node("A", sleep_task(20)).unwrap();
+    g.add_node("B", sleep_task(20)).unwrap();
+    g.add_node("C", sleep_task(20)).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let (result, report) = s.run_with_report(&());
+    result.unwrap();
+
+    assert_eq!(report.max_parallelism_observed(), 1);
+    assert_eq!(report.n_worker(), 1);
+    assert!(report.duration() >= Duration::from_millis(60));
+    for node in report.nodes() {
+        assert!(matches!(node.status, NodeStatus::Succeeded{duration, over_budget: false} if duration >= Duration::from_millis(20)));
+        assert_eq!(node.worker, Some(0));
+        assert!(node.finished_at.unwrap() >= node.started_at.unwrap() + Duration::from_millis(20));
+    }
+    let a = report.node("A").unwrap();
+    let b = report.node("B").unwrap();
+    assert!(b.started_at.unwrap() >= a.finished_at.unwrap());
+}
+
+#[test]
+fn layer() {
+    let mut g = Graph::new();
+    g.add_node("A1", sleep_task(100)).unwrap();
+    g.add_node("A2", sleep_task(100)).unwrap();
+    g.add_node("A3", sleep_task(100)).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let (result, report) = s.run_with_report(&());
+    result.unwrap();
+
+    assert_eq!(report.max_parallelism_observed(), 3);
+    assert_eq!(report.n_worker(), 3);
+    let mut workers: Vec<usize> = report.nodes().iter().map(|node| node.worker.unwrap()).collect();
+    workers.sort();
+    assert_eq!(workers, [0, 1, 2]);
+
+    let utilization = report.utilization(10);
+    assert_eq!(utilization.len(), 10);
+    assert!(utilization[5] > 0.99);
+    for busy in utilization {
+        assert!((0.0..=1.0001).contains(&busy));
+    }
+}
+
+#[test]
+fn failed() {
+    let mut g = Graph::new();
+    g.add_node("A", sleep_task(0)).unwrap();
+    g.add_node("B", |_: &()| -> Result<(), TaskError> {
+        Err(Box::new(std::fmt::Error))
+    }).unwrap();
+    g.add_node("C", sleep_task(0)).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let (result, report) = s.run_with_report(&());
+    result.unwrap_err();
+
+    let statuses: Vec<NodeStatus> = report.nodes().iter().map(|node| node.status.clone()).collect();
+    assert!(matches!(statuses[0], NodeStatus::Succeeded{..}));
+    assert_eq!(statuses[1..], [NodeStatus::Failed, NodeStatus::NotRun]);
+    let c = report.node("C").unwrap();
+    assert!(c.worker.is_none() && c.started_at.is_none());
+}
+
+#[test]
+fn errors() {
+    let mut g = Graph::new();
+    g.add_node("failed", failed_task()).unwrap();
+    g.add_node("timed_out", dag_engine::task::with_timeout(sleep_task(50), Duration::from_millis(10))).unwrap();
+    g.add_node("panicked", |_: &()| -> Result<(), TaskError> {
+        panic!("boom");
+    }).unwrap();
+    g.add_node("ok", sleep_task(0)).unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .failure_policy(FailurePolicy::ContinueOnError)
+        .build();
+    let (result, report) = s.run_with_report(&());
+    result.unwrap_err();
+
+    let failed = report.node("failed").unwrap();
+    assert_eq!(failed.error.as_deref(), Some(std::fmt::Error.to_string().as_str()));
+    assert_eq!(failed.error_type, None);
+    let timed_out = report.node("timed_out").unwrap();
+    assert!(timed_out.error.as_deref().is_some_and(|err| err.starts_with("exceeded 10ms")));
+    assert_eq!(timed_out.error_type.as_deref(), Some("dag_engine::error::TimeoutError"));
+    let panicked = report.node("panicked").unwrap();
+    assert_eq!(panicked.error.as_deref(), Some("boom"));
+    assert_eq!(panicked.error_type.as_deref(), Some("&str"));
+    let ok = report.node("ok").unwrap();
+    assert!(ok.error.is_none() && ok.error_type.is_none());
+}
+
+#[test]
+fn skipped() {
+    let mut g = Graph::new();
+    g.add_node("F", failed_task()).unwrap();
+    g.add_node("B", sleep_task(0)).unwrap();
+    g.add_node("C", sleep_task(0)).unwrap();
+    g.add_node("D", sleep_task(0)).unwrap();
+    g.add_node("E", sleep_task(0)).unwrap();
+    g.add_edge("F", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.add_edge("D", "C").unwrap();
+    g.add_edge("D", "E").unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap()).failure_policy(FailurePolicy::ContinueOnError).build();
+    let (result, report) = s.run_with_report(&());
+    result.unwrap_err();
+
+    assert_eq!(report.node("F").unwrap().status, NodeStatus::Failed);
+    assert!(matches!(report.node("D").unwrap().status, NodeStatus::Succeeded{..}));
+    assert!(matches!(report.node("E").unwrap().status, NodeStatus::Succeeded{..}));
+    assert_eq!(report.node("B").unwrap().status, NodeStatus::Skipped{
+        because_of: "F".to_string(),
+        path: vec!["F".to_string(), "B".to_string()],
+    });
+    let c = report.node("C").unwrap();
+    assert!(c.worker.is_none() && c.started_at.is_none());
+    if let NodeStatus::Skipped{because_of, path} = &c.status {
+        assert_eq!(because_of, "F");
+        assert_eq!(path.len(), 3);
+        assert_eq!(path, &["F", "B", "C"]);
+    } else {
+        panic!("C not skipped: {:?}", c.status);
+    }
+}
+
+#[test]
+fn max_failures() {
+    let mut g = Graph::new();
+    for i in 0..10 {
+        g.add_node(&format!("F{}", i), failed_task()).unwrap();
+    }
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .failure_policy(FailurePolicy::ContinueOnError)
+        .max_failures(3)
+        .max_concurrency(2)
+        .build();
+    let (result, report) = s.run_with_report(&());
+    let n_failed = report.nodes().iter().filter(|node| node.status == NodeStatus::Failed).count();
+    let n_not_run = report.nodes().iter().filter(|node| node.status == NodeStatus::NotRun).count();
+    assert!((3..=3 + 2).contains(&n_failed));
+    assert_eq!(n_failed + n_not_run, 10);
+    assert!(report.max_parallelism_observed() <= 2);
+    assert!(result.is_err_and(
+        |e| if let MultipleFailures{failures, aborted} = e { aborted && failures.len() == n_failed } else { false }
+    ));
+}
+
+#[test]
+fn slow_nodes() {
+    fn clock_task(duration_ms: u64) -> impl Fn(&TaskCtx<'_, ()>) -> Result<(), TaskError> {
+        return move |ctx: &TaskCtx<'_, ()>| {
+            ctx.clock().sleep(Duration::from_millis(duration_ms));
+            Ok(())
+        };
+    }
+    let mut g = Graph::new();
+    g.add_node_with_ctx("slow", clock_task(100)).unwrap();
+    g.set_cost("slow", Duration::from_millis(10)).unwrap();
+    g.add_node_with_ctx("fine", clock_task(10)).unwrap();
+    g.set_cost("fine", Duration::from_millis(100)).unwrap();
+    g.add_node_with_ctx("unknown", clock_task(30)).unwrap();
+    let slow: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+    let recorded = slow.clone();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .slow_task_factor(2.0)
+        // one at a time, so that no node moves the clock while another runs
+        .clock(Arc::new(ManualClock::new()))
+        .max_concurrency(1)
+        .observer(move |event: &Event<'_>| {
+            if let Event::NodeSlow{name, duration, budget, ..} = event {
+                assert_eq!(*duration, Duration::from_millis(100));
+                assert_eq!(*budget, Duration::from_millis(20));
+                recorded.lock().unwrap().push(name.to_string());
+            }
+        })
+        .build();
+    let (result, report) = s.run_with_report(&());
+    result.unwrap();
+
+    assert!(matches!(report.node("slow").unwrap().status, NodeStatus::Succeeded{over_budget: true, ..}));
+    assert!(matches!(report.node("fine").unwrap().status, NodeStatus::Succeeded{over_budget: false, ..}));
+    assert!(matches!(report.node("unknown").unwrap().status, NodeStatus::Succeeded{over_budget: false, ..}));
+    assert_eq!(*slow.lock().unwrap(), ["slow"]);
+}
+
+#[test]
+fn queue_latency() {
+    fn siblings() -> Graph<()> {
+        let mut g = Graph::new();
+        g.add_node("root", |_: &()| Ok(())).unwrap();
+        for name in ["A", "B", "C"] {
+            g.add_node(name, sleep_task(30)).unwrap();
+            g.add_edge("root", name).unwrap();
+        }
+        return g;
+    }
+    let s = Scheduler::new(siblings().froze().unwrap());
+    let (result, report) = s.run_with_report(&());
+    result.unwrap();
+    for node in report.nodes() {
+        assert!(node.ready_at.is_some());
+        assert!(node.queue_latency().unwrap() < Duration::from_millis(20));
+    }
+
+    let events = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+    let s = SchedulerBuilder::new(siblings().froze().unwrap())
+        .max_concurrency(1)
+        .observer(move |event: &Event<'_>| {
+            if let Event::NodeReady{name, ..} = event {
+                recorded.lock().unwrap().push(name.to_string());
+            }
+        })
+        .build();
+    let (result, report) = s.run_with_report(&());
+    result.unwrap();
+    assert_eq!(*events.lock().unwrap(), ["root", "A", "B", "C"]);
+    let mut nodes: Vec<_> = report.nodes()[1..].iter().collect();
+    nodes.sort_by_key(|node| node.started_at);
+    let ran_before = nodes[0].finished_at.unwrap() - nodes[0].started_at.unwrap()
+        + nodes[1].finished_at.unwrap() - nodes[1].started_at.unwrap();
+    assert!(nodes[2].queue_latency().unwrap() >= ran_before);
+    assert!(nodes[0].queue_latency().unwrap() < Duration::from_millis(20));
+}
+
+#[test]
+fn to_dot() {
+    let mut g = Graph::new();
+    g.add_node("A", sleep_task(0)).unwrap();
+    g.add_node("B", sleep_task(0)).unwrap();
+    g.add_node("C", failed_task()).unwrap();
+    g.add_node("D", sleep_task(0)).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.add_edge("C", "D").unwrap();
+    let frozen = g.froze().unwrap();
+    let s = Scheduler::new(frozen.clone());
+    let (result, report) = s.run_with_report(&());
+    result.unwrap_err();
+
+    let dot = report.to_dot(&frozen);
+    assert!(dot.starts_with(&format!("digraph \"{}\" {{\n", report.run_id())));
+    let line_of = |id: &str| dot.lines().find(|line| line.trim_start().starts_with(&format!("{} [", id))).unwrap().to_string();
+    assert!(line_of("n0").contains("fillcolor=green"));
+    assert!(line_of("n0").starts_with("    n0 [label=\"A\\n"));
+    assert!(line_of("n2").contains("fillcolor=red, tooltip=\"failed in "));
+    assert_eq!(line_of("n3"), "    n3 [label=\"D\", fillcolor=grey, tooltip=\"not run\"];");
+    assert!(dot.contains("    n1 -> n2;\n    n2 -> n3;\n}\n"));
+
+    // skipped nodes, and nodes the report does not know
+    let s = SchedulerBuilder::new(frozen.clone())
+        .failure_policy(FailurePolicy::ContinueOnError)
+        .build();
+    let (_, report) = s.run_with_report(&());
+    let mut g = Graph::new();
+    g.add_node("C", sleep_task(0)).unwrap();
+    g.add_node("D", sleep_task(0)).unwrap();
+    g.add_node("E", sleep_task(0)).unwrap();
+    let dot = report.to_dot(&g.froze().unwrap());
+    assert!(dot.contains("n1 [label=\"D\", fillcolor=yellow, tooltip=\"skipped because of C\"];"));
+    assert!(dot.contains("n2 [label=\"E\", fillcolor=grey, tooltip=\"not run\"];"));
+}
+
+#[cfg(feature = "serde")]
+fn json_report() -> dag_engine::RunReport {
+    let mut g = Graph::new();
+    g.add_node("A", sleep_task(0)).unwrap();
+    g.add_node("F", failed_task()).unwrap();
+    g.add_node("C", sleep_task(0)).unwrap();
+    g.add_edge("A", "F").unwrap();
+    g.add_edge("F", "C").unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .failure_policy(FailurePolicy::ContinueOnError)
+        .build();
+    let (result, report) = s.run_with_report(&());
+    result.unwrap_err();
+    return report;
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn json() {
+    let report = json_report();
+    let json = report.to_json();
+    let restored = dag_engine::RunReport::from_json(&json).unwrap();
+    assert_eq!(restored.to_json(), json);
+    assert_eq!(restored.run_id(), report.run_id());
+    assert_eq!(restored.duration(), report.duration());
+    assert_eq!(restored.n_worker(), report.n_worker());
+    for (restored, node) in restored.nodes().iter().zip(report.nodes()) {
+        assert_eq!(restored.name, node.name);
+        assert_eq!(restored.status, node.status);
+        assert_eq!(restored.worker, node.worker);
+        assert_eq!(restored.started_at, node.started_at);
+        assert_eq!(restored.finished_at, node.finished_at);
+        assert_eq!(restored.error, node.error);
+        assert_eq!(restored.error_type, node.error_type);
+    }
+    assert!(dag_engine::RunReport::from_json("{").is_err());
+}
+
+// what dashboards read, renaming any of these breaks them
+#[cfg(feature = "serde")]
+#[test]
+fn json_schema() {
+    let report = json_report();
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()).unwrap();
+    let keys = |value: &serde_json::Value| -> Vec<String> {
+        let mut keys: Vec<String> = value.as_object().unwrap().keys().cloned().collect();
+        keys.sort();
+        return keys;
+    };
+    assert_eq!(keys(&json), ["duration", "n_worker", "nodes", "run_id"]);
+    assert_eq!(json["run_id"], report.run_id().as_str());
+    assert_eq!(keys(&json["duration"]), ["nanos", "secs"]);
+    let nodes = json["nodes"].as_array().unwrap();
+    assert_eq!(keys(&nodes[0]), ["barrier", "error", "error_type", "fallback_used", "finished_at", "name", "ready_at", "started_at", "status", "worker"]);
+    assert_eq!(nodes[0]["name"], "A");
+    assert_eq!(keys(&nodes[0]["status"]), ["Succeeded"]);
+    assert_eq!(keys(&nodes[0]["status"]["Succeeded"]), ["duration", "over_budget"]);
+    assert_eq!(nodes[1]["status"], "Failed");
+    assert_eq!(nodes[0]["error"], serde_json::Value::Null);
+    assert_eq!(nodes[1]["error"], std::fmt::Error.to_string());
+    assert_eq!(nodes[1]["error_type"], serde_json::Value::Null);
+    assert_eq!(keys(&nodes[2]["status"]["Skipped"]), ["because_of", "path"]);
+    assert_eq!(nodes[2]["started_at"], serde_json::Value::Null);
+    assert_eq!(keys(&nodes[1]["started_at"]), ["nanos", "secs"]);
+}
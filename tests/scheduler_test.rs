@@ -1,9 +1,13 @@
-use std::sync::Mutex;
-use std::sync::atomic::{Ordering, AtomicU32, AtomicU64};
+#![allow(clippy::needless_return, clippy::needless_range_loop, clippy::manual_range_contains)]
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{Ordering, AtomicBool, AtomicU32, AtomicU64};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use dag_engine::{Error::*, TaskError, Task, Graph, Scheduler};
+use dag_engine::{Error::*, TaskError, Task, TaskRun, TaskCtx, Graph, FrozenGraph, FrozeOptions, Scheduler, SchedulerBuilder};
+use dag_engine::{FailurePolicy, PanicPolicy, DispatchStrategy, RunOptions, RunHandle, StepStatus, RunId, Event, NodeStatus, DuplicateEdgePolicy, TaskClass, ManualClock, PanickedTaskError, PostTaskCheckError};
 use rand::{SeedableRng, Rng};
 
 struct SleepContext {
@@ -103,7 +107,38 @@ fn sleep_layer() {
 }
 
 #[test]
-fn sleep_dag() {
+fn sleep_layer_barriers() {
+    let mut g = Graph::new();
+    for (name, duration_ms) in [
+        ("A1", 20), ("A2", 40), ("A3", 60), ("B1", 40), ("B2", 60), ("B3", 20), ("C1", 60), ("C2", 20), ("C3", 40),
+    ] {
+        g.add_node(name, sleep_task(duration_ms)).unwrap();
+    }
+    g.add_barrier("AB", &["A1", "A2", "A3"], &["B1", "B2", "B3"]).unwrap();
+    g.add_barrier("BC", &["B1", "B2", "B3"], &["C1", "C2", "C3"]).unwrap();
+    assert!(g.add_barrier("CD", &["C1"], &["D1"]).is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "D1" } else { false }
+    ));
+    assert!(g.add_barrier("AB", &[], &[]).is_err());
+    assert_eq!(g.to_graphml().matches("<edge ").count(), 12);
+
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = SleepContext::new();
+    let (result, report) = s.run_with_report(&ctx);
+    result.unwrap();
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 9);
+    let nodes: Vec<_> = report.nodes().iter().filter(|node| !node.barrier).collect();
+    assert_eq!(nodes.len(), 9);
+    let layer = |prefix: &str| nodes.iter().filter(|node| node.name.starts_with(prefix)).collect::<Vec<_>>();
+    for (before, after) in [("A", "B"), ("B", "C")] {
+        let finished = layer(before).iter().map(|node| node.finished_at.unwrap()).max().unwrap();
+        let started = layer(after).iter().map(|node| node.started_at.unwrap()).min().unwrap();
+        assert!(finished <= started);
+    }
+    assert!(report.node("AB").unwrap().barrier);
+}
+
+fn sleep_dag_graph() -> Graph<SleepContext> {
     let mut g = Graph::new();
     g.add_node("A1", sleep_task(20)).unwrap();
     g.add_node("A2", sleep_task(40)).unwrap();
@@ -126,10 +161,108 @@ fn sleep_dag() {
     g.add_edge("B3", "C1").unwrap();
     g.add_edge("B3", "C2").unwrap();
     g.add_edge("B3", "C3").unwrap();
-    let ctx = run_sleep("sleep_dag", g);
+    return g;
+}
+
+#[test]
+fn sleep_dag() {
+    let ctx = run_sleep("sleep_dag", sleep_dag_graph());
     assert_eq!(ctx.n_run.load(Ordering::Relaxed), 9);
 }
 
+#[test]
+fn sleep_dag_subgraph() {
+    let g = sleep_dag_graph();
+    let sub = g.subgraph(|name| !name.starts_with('A'));
+    assert_eq!(sub.node_count(), 6);
+    assert_eq!(sub.edge_count(), 6);
+    assert_eq!(g.node_count(), 9);
+    let frozen = sub.froze().unwrap();
+    assert!(frozen.is_reachable("B1", "C3").unwrap());
+    assert!(frozen.is_reachable("B3", "C1").unwrap());
+    assert!(!frozen.is_reachable("B2", "C1").unwrap());
+    assert!(frozen.is_reachable("A1", "B1").is_err());
+    let s = Scheduler::new(frozen);
+    let ctx = SleepContext::new();
+    s.run(&ctx).unwrap();
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 6);
+}
+
+#[test]
+fn empty_graphs() {
+    let g: Graph<SleepContext> = Graph::new();
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = SleepContext::new();
+    s.run(&ctx).unwrap();
+    s.run_sequential(&ctx).unwrap();
+    let (result, report) = s.run_with_report(&ctx);
+    result.unwrap();
+    assert!(report.nodes().is_empty());
+    Arc::new(s).spawn_run(Arc::new(SleepContext::new())).join().unwrap();
+
+    let options = FrozeOptions{reject_empty: true, ..Default::default()};
+    let g: Graph<SleepContext> = Graph::new();
+    let err = g.froze_with(options).err().unwrap();
+    assert!(matches!(err, EmptyGraph) && err.is_build());
+    assert_eq!(err.to_string(), "graph has no nodes");
+
+    for options in [FrozeOptions::default(), options] {
+        let mut g = Graph::new();
+        g.add_node("A", sleep_task(1)).unwrap();
+        let s = Scheduler::new(g.froze_with(options).unwrap());
+        let ctx = SleepContext::new();
+        s.run(&ctx).unwrap();
+        s.run_sequential(&ctx).unwrap();
+        assert_eq!(ctx.n_run.load(Ordering::Relaxed), 2);
+
+        // without edges every node is a root, and they all run at once
+        let mut g = Graph::new();
+        for i in 0..8 {
+            g.add_node(&i.to_string(), sleep_task(50)).unwrap();
+        }
+        let s = Scheduler::new(g.froze_with(options).unwrap());
+        let ctx = SleepContext::new();
+        let (result, report) = s.run_with_report(&ctx);
+        result.unwrap();
+        assert_eq!(ctx.n_run.load(Ordering::Relaxed), 8);
+        assert_eq!(report.max_parallelism_observed(), 8);
+    }
+}
+
+#[test]
+fn sleep_wide() {
+    let mut g = Graph::new();
+    let n_node: usize = 1000;
+    g.add_node("S", sleep_task(0)).unwrap();
+    g.add_node("T", sleep_task(0)).unwrap();
+    for i in 0..n_node {
+        let si = i.to_string();
+        g.add_node(&si, move |ctx: &SleepContext| -> Result<(), TaskError> {
+            thread::sleep(Duration::from_micros(200));
+            ctx.n_run.fetch_add(1, Ordering::Relaxed);
+            Ok(())
+        }).unwrap();
+        g.add_edge("S", &si).unwrap();
+        g.add_edge(&si, "T").unwrap();
+    }
+    let s = Scheduler::new(g.froze().unwrap());
+
+    let ctx = SleepContext::new();
+    let t0 = Instant::now();
+    s.run_sequential(&ctx).unwrap();
+    let sequential_cost = t0.elapsed();
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed) as usize, n_node + 2);
+
+    let ctx = SleepContext::new();
+    let t0 = Instant::now();
+    s.run(&ctx).unwrap();
+    let cost = t0.elapsed();
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed) as usize, n_node + 2);
+    println!("sleep_wide cost: {} ms, sequential: {} ms",
+        cost.as_millis(), sequential_cost.as_millis());
+    assert!(cost < sequential_cost * 4);
+}
+
 struct ToposortContext {
     result: Mutex<Vec<String>>
 }
@@ -149,8 +282,19 @@ fn toposort_task(name: &'static str) -> Task<ToposortContext> {
     })
 }
 
-#[test]
-fn toposort() {
+fn check_toposort(result: &[String]) {
+    assert_eq!(result.len(), 9);
+    for (i, name) in result.iter().enumerate() {
+        assert_eq!(&name[0..1], match i {
+            0..=2 => "A",
+            3..=5 => "B",
+            6..=8 => "C",
+            _ => panic!("{}", i),
+        })
+    }
+}
+
+fn toposort_graph() -> Graph<ToposortContext> {
     let mut g = Graph::new();
     g.add_node("A1", toposort_task("A1")).unwrap();
     g.add_node("A2", toposort_task("A2")).unwrap();
@@ -180,20 +324,41 @@ fn toposort() {
     g.add_edge("B3", "C2").unwrap();
     g.add_edge("B3", "C3").unwrap();
 
+    return g;
+}
+
+#[test]
+fn toposort() {
+    let g = toposort_graph();
     let s = Scheduler::new(g.froze().unwrap());
     let ctx = ToposortContext::new();
     s.run(&ctx).unwrap();
 
     let result = ctx.result.into_inner().unwrap();
     // dbg!(result);
-    assert_eq!(result.len(), 9);
-    for (i, name) in result.iter().enumerate() {
-        assert_eq!(&name[0..1], match i {
-            0..=2 => "A",
-            3..=5 => "B",
-            6..=8 => "C",
-            _ => panic!("{}", i),
-        })
+    check_toposort(&result);
+}
+
+#[test]
+fn toposort_owned() {
+    let s = Scheduler::new(toposort_graph().froze().unwrap());
+    let ctx = s.run_owned(ToposortContext::new()).ok().unwrap();
+    check_toposort(&ctx.result.into_inner().unwrap());
+}
+
+#[test]
+fn toposort_sequential() {
+    let s = Scheduler::new(toposort_graph().froze().unwrap());
+    let mut orders = vec![];
+    for _ in 0..8 {
+        let ctx = ToposortContext::new();
+        s.run_sequential(&ctx).unwrap();
+        orders.push(ctx.result.into_inner().unwrap());
+    }
+    check_toposort(&orders[0]);
+    assert_eq!(orders[0], ["A1", "A2", "A3", "B1", "B2", "B3", "C1", "C2", "C3"]);
+    for order in orders.iter() {
+        assert_eq!(order, &orders[0]);
     }
 }
 
@@ -232,7 +397,7 @@ fn toposort_random() {
         if i > 1 {
             for j in 0..i-1 {
                 let k: u32 = rng.gen();
-                if k % 16 == 0 {
+                if k.is_multiple_of(16) {
                     g.add_edge(&j.to_string(), &si).unwrap();
                     n_edge += 1;
                 }
@@ -252,6 +417,49 @@ fn toposort_random() {
     for i in 0..n_node {
         assert_eq!(i, result[i]);
     }
+
+    let ctx = ToposortRandomContext::new();
+    s.run_sequential(&ctx).unwrap();
+    let result = ctx.result.into_inner().unwrap();
+    for i in 0..n_node {
+        assert_eq!(i, result[i]);
+    }
+}
+
+#[test]
+fn deterministic_dispatch() {
+    let mut g = Graph::new();
+    let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    let n_node: usize = 50;
+    let mut parents: Vec<Vec<usize>> = vec![];
+    for i in 0..n_node {
+        g.add_node(&i.to_string(), toposort_random_task(i)).unwrap();
+        parents.push((0..i).filter(|_| rng.gen_range(0..8) == 0).collect());
+        for j in parents[i].iter() {
+            g.add_edge(&j.to_string(), &i.to_string()).unwrap();
+        }
+    }
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .max_concurrency(1)
+        .deterministic_dispatch(true)
+        .build();
+    let mut orders = vec![];
+    for _ in 0..10 {
+        let ctx = ToposortRandomContext::new();
+        s.run(&ctx).unwrap();
+        orders.push(ctx.result.into_inner().unwrap());
+    }
+    let mut position = vec![0; n_node];
+    for (i, index) in orders[0].iter().enumerate() {
+        position[*index] = i;
+    }
+    assert_eq!(orders[0].len(), n_node);
+    for i in 0..n_node {
+        assert!(parents[i].iter().all(|j| position[*j] < position[i]));
+    }
+    for order in orders.iter() {
+        assert_eq!(order, &orders[0]);
+    }
 }
 
 struct FailedContext {
@@ -301,11 +509,12 @@ fn failed() {
     g.add_edge("A", "B").unwrap();
     g.add_edge("B", "C").unwrap();
     g.add_edge("C", "D").unwrap();
+    let frozen = g.froze().unwrap();
 
-    let s = Scheduler::new(g.froze().unwrap());
+    let s = Scheduler::new(frozen.clone());
     let ctx = FailedContext::new();
     assert!(s.run(&ctx).is_err_and(|e| -> bool {
-        if let RuntimeFailed{node, err} = e {
+        if let RuntimeFailed{node, err, ..} = e {
             if node == "C" {
                 if let Some(e) = err.downcast_ref::<FailedError>() {
                     return e.reason == node;
@@ -315,6 +524,156 @@ fn failed() {
         return false;
     }));
     assert_eq!(ctx.n_run.load(Ordering::Relaxed), 3);
+
+    let ctx = FailedContext::new();
+    let err = s.run_sequential(&ctx).unwrap_err();
+    assert!(err.is_runtime() && !err.is_build());
+    assert_eq!(err.node_name(), Some("C"));
+    assert_eq!(err.task_error().unwrap().to_string(), "C");
+    assert_eq!(err.panic_message(), None);
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 3);
+
+    // the same index in every run
+    assert_eq!(err.node_index(), frozen.index_of("C"));
+    assert_eq!(frozen.name_of(err.node_index().unwrap()), Some("C"));
+    assert!(matches!(s.run(&FailedContext::new()), Err(RuntimeFailed{index: 2, ..})));
+    assert_eq!(frozen.index_of("E"), None);
+    assert_eq!(frozen.name_of(4), None);
+}
+
+#[test]
+fn reversed() {
+    let mut g = Graph::new();
+    for name in ["A", "B", "C", "D"] {
+        g.add_node(name, toposort_task(name)).unwrap();
+    }
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.add_edge("C", "D").unwrap();
+    let s = Scheduler::new(g.reversed().froze().unwrap());
+    let ctx = ToposortContext::new();
+    s.run_sequential(&ctx).unwrap();
+    assert_eq!(*ctx.result.lock().unwrap(), ["D", "C", "B", "A"]);
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = ToposortContext::new();
+    s.run_sequential(&ctx).unwrap();
+    assert_eq!(*ctx.result.lock().unwrap(), ["A", "B", "C", "D"]);
+
+    let mut g = Graph::new();
+    g.add_node("A", failed_task("")).unwrap();
+    g.add_node("B", failed_task("")).unwrap();
+    g.add_node("C", failed_task("C")).unwrap();
+    g.add_node("D", failed_task("")).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.add_edge("C", "D").unwrap();
+    let s = Scheduler::new(g.reversed().froze().unwrap());
+    let ctx = FailedContext::new();
+    assert_eq!(s.run_sequential(&ctx).unwrap_err().node_name(), Some("C"));
+    // D and C
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 2);
+
+    g.add_edge("D", "A").unwrap();
+    assert!(g.reversed().froze().is_err_and(
+        |e| if let CyclicGraphFound{ring, ..} = e { ring == "[A, B, C, D]" } else { false }
+    ));
+    assert!(g.froze().is_err());
+}
+
+#[test]
+fn failed_owned() {
+    let mut g = Graph::new();
+    g.add_node("A", failed_task("")).unwrap();
+    g.add_node("B", failed_task("B")).unwrap();
+    g.add_node("C", failed_task("")).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+
+    let s = Scheduler::new(g.froze().unwrap());
+    let (ctx, err) = s.run_owned(FailedContext::new()).err().unwrap();
+    assert!(matches!(err, RuntimeFailed{node, ..} if node == "B"));
+    assert_eq!(ctx.n_run.into_inner(), 2);
+}
+
+#[test]
+fn cancelled() {
+    let err = Cancelled{
+        completed: vec![String::from("A")],
+        pending: vec![String::from("B"), String::from("C")],
+    };
+    assert!(err.is_runtime() && !err.is_build());
+    assert_eq!(err.node_name(), None);
+    assert_eq!(err.to_string(), "run cancelled after 1 nodes, 2 pending");
+}
+
+#[test]
+fn continue_on_error() {
+    let mut g = Graph::new();
+    g.add_node("A", failed_task("")).unwrap();
+    g.add_node("B", failed_task("B")).unwrap();
+    g.add_node("C", failed_task("")).unwrap();
+    g.add_node("D", failed_task("")).unwrap();
+    g.add_node("E", failed_task("E")).unwrap();
+    g.add_node("F", failed_task("")).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.add_edge("A", "D").unwrap();
+    g.add_edge("D", "C").unwrap();
+    g.add_edge("D", "E").unwrap();
+
+    let s = SchedulerBuilder::new(g.froze().unwrap()).failure_policy(FailurePolicy::ContinueOnError).build();
+    let check = |e: &dag_engine::Error| -> bool {
+        if let MultipleFailures{failures, aborted: false} = e {
+            let mut nodes: Vec<&str> = failures.iter().map(|e| match e {
+                RuntimeFailed{node, ..} => node.as_str(),
+                _ => "",
+            }).collect();
+            nodes.sort();
+            return nodes == ["B", "E"];
+        }
+        return false;
+    };
+    let ctx = FailedContext::new();
+    assert!(s.run(&ctx).is_err_and(|e| check(&e)));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 5);
+
+    let ctx = FailedContext::new();
+    assert!(s.run_sequential(&ctx).is_err_and(|e| check(&e)));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 5);
+}
+
+#[test]
+fn in_flight_on_error() {
+    let mut g = Graph::new();
+    g.add_node("A", panicked_task("")).unwrap();
+    g.add_node("B", panicked_task("")).unwrap();
+    g.add_node("C1", |_: &PanickedContext| -> Result<(), TaskError> {
+        thread::sleep(Duration::from_millis(10));
+        panic!("C1");
+    }).unwrap();
+    g.add_node("C2", |ctx: &PanickedContext| -> Result<(), TaskError> {
+        ctx.n_run.fetch_add(1, Ordering::Relaxed);
+        thread::sleep(Duration::from_millis(100));
+        Ok(())
+    }).unwrap();
+    g.add_node("D", panicked_task("")).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C1").unwrap();
+    g.add_edge("B", "C2").unwrap();
+    g.add_edge("C1", "D").unwrap();
+    g.add_edge("C2", "D").unwrap();
+
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = PanickedContext::new();
+    let err = s.run(&ctx).unwrap_err();
+    assert_eq!(err.node_name(), Some("C1"));
+    assert_eq!(err.in_flight(), ["C2"]);
+    assert!(matches!(err, RuntimePanicked{in_flight, ..} if in_flight == ["C2"]));
+    // C2 ran to its end anyway
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 3);
+
+    let ctx = PanickedContext::new();
+    assert!(s.run_sequential(&ctx).unwrap_err().in_flight().is_empty());
 }
 
 struct PanickedContext {
@@ -356,7 +715,7 @@ fn panicked() {
     let s = Scheduler::new(g.froze().unwrap());
     let ctx = PanickedContext::new();
     assert!(s.run(&ctx).is_err_and(|e| -> bool {
-        if let RuntimePanicked{node, err} = e {
+        if let RuntimePanicked{node, err, ..} = e {
             if node == "C1" {
                 if let Some(s) = err.downcast_ref::<String>() {
                     return s == &node;
@@ -370,6 +729,14 @@ fn panicked() {
     assert!(3 <= n_run && n_run <= 4);
     // dbg!(n_run);
 
+    let ctx = PanickedContext::new();
+    let err = s.run_sequential(&ctx).unwrap_err();
+    assert!(matches!(err, RuntimePanicked{..}));
+    assert_eq!(err.node_name(), Some("C1"));
+    assert_eq!(err.panic_message().as_deref(), Some("C1"));
+    assert!(err.task_error().is_none());
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 3);
+
     let mut g = Graph::new();
     g.add_node("A", panicked_task("")).unwrap();
     g.add_node("B", panicked_task("")).unwrap();
@@ -387,18 +754,1958 @@ fn panicked() {
 
     let s = Scheduler::new(g.froze().unwrap());
     let ctx = PanickedContext::new();
-    assert!(s.run(&ctx).is_err_and(|e| -> bool {
-        if let RuntimePanicked{node, err} = e {
-            if node.starts_with("C") {
-                if let Some(s) = err.downcast_ref::<String>() {
-                    return s == &node;
-                }
-            }
-            // dbg!(&node, &err);
-        }
-        return false;
-    }));
+    let err = s.run(&ctx).unwrap_err();
+    assert!(matches!(err, RuntimePanicked{..}));
+    let node = err.node_name().unwrap();
+    assert!(node.starts_with("C"));
+    assert_eq!(err.panic_message().as_deref(), Some(node));
     let n_run = ctx.n_run.load(Ordering::Relaxed);
     assert!(3 <= n_run && n_run <= 5);
     // dbg!(n_run);
 }
+
+#[test]
+fn panics_as_errors() {
+    let mut g = Graph::new();
+    g.add_node("A", panicked_task("")).unwrap();
+    g.add_node("B", panicked_task("B")).unwrap();
+    g.add_node("C", panicked_task("C")).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("A", "C").unwrap();
+    g.set_fallback("C", |_: &PanickedContext| Ok(())).unwrap();
+    let frozen = g.froze().unwrap();
+
+    let s = SchedulerBuilder::new(frozen.clone())
+        .panics_as_errors(true)
+        .build();
+    let ctx = PanickedContext::new();
+    let err = s.run(&ctx).unwrap_err();
+    assert!(matches!(err, RuntimeFailed{..}));
+    assert_eq!(err.node_name(), Some("B"));
+    assert!(err.panic_message().is_none());
+    let panicked = err.task_error().unwrap().downcast_ref::<PanickedTaskError>().unwrap();
+    assert_eq!(panicked.message.as_deref(), Some("B"));
+    assert_eq!(panicked.payload.downcast_ref::<String>().map(|s| s.as_str()), Some("B"));
+    assert!(err.to_string().starts_with("run B failed: task panicked: B at "));
+    let n_run = ctx.n_run.load(Ordering::Relaxed);
+    assert!(2 <= n_run && n_run <= 3);
+
+    // the panic of C is caught by its fallback, as without the option
+    let s = SchedulerBuilder::new(frozen)
+        .panics_as_errors(true)
+        .failure_policy(FailurePolicy::ContinueOnError)
+        .build();
+    let err = s.run(&PanickedContext::new()).unwrap_err();
+    assert!(matches!(&err, MultipleFailures{failures, ..} if matches!(&failures[..], [RuntimeFailed{node, ..}] if node == "B")));
+}
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn scheduler_is_send_sync() {
+    assert_send_sync::<Scheduler<SleepContext>>();
+    assert_send_sync::<Scheduler<ToposortContext>>();
+    assert_send_sync::<FrozenGraph<SleepContext>>();
+    assert_send_sync::<Graph<SleepContext>>();
+}
+
+static PIPELINE: OnceLock<Scheduler<AtomicU32>> = OnceLock::new();
+
+#[test]
+fn static_scheduler() {
+    let n_node: usize = 32;
+    let shared = AtomicU32::new(0);
+    thread::scope(|scope| {
+        for _ in 0..4 {
+            scope.spawn(|| {
+                let s = PIPELINE.get_or_init(|| Scheduler::new(counter_graph(n_node, false).froze().unwrap()));
+                let ctx = AtomicU32::new(0);
+                for _ in 0..10 {
+                    s.run(&ctx).unwrap();
+                    s.run(&shared).unwrap();
+                }
+                assert_eq!(ctx.load(Ordering::Relaxed), 10 * n_node as u32);
+            });
+        }
+    });
+    assert_eq!(shared.load(Ordering::Relaxed), 4 * 10 * n_node as u32);
+}
+
+struct CountingContext {
+    n_run: Vec<AtomicU32>,
+}
+
+impl CountingContext {
+    fn new(n_node: usize) -> CountingContext {
+        CountingContext{
+            n_run: (0..n_node).map(|_| AtomicU32::new(0)).collect(),
+        }
+    }
+}
+
+fn counting_task(id: usize) -> Task<CountingContext> {
+    Box::new(move |ctx: &CountingContext| -> Result<(), TaskError> {
+        ctx.n_run[id].fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    })
+}
+
+#[test]
+fn concurrent_runs() {
+    let mut g = Graph::new();
+    let n_node: usize = 16;
+    for i in 0..n_node {
+        g.add_node(&i.to_string(), counting_task(i)).unwrap();
+        if i >= 2 {
+            g.add_edge(&(i - 2).to_string(), &i.to_string()).unwrap();
+            g.add_edge(&(i - 1).to_string(), &i.to_string()).unwrap();
+        }
+    }
+    let s = Scheduler::new(g.froze().unwrap());
+    let n_thread: usize = 8;
+    let n_round: u32 = 20;
+
+    thread::scope(|scope| {
+        for _ in 0..n_thread {
+            scope.spawn(|| {
+                let ctx = CountingContext::new(n_node);
+                for _ in 0..n_round {
+                    s.run(&ctx).unwrap();
+                }
+                for n_run in ctx.n_run.iter() {
+                    assert_eq!(n_run.load(Ordering::Relaxed), n_round);
+                }
+            });
+        }
+    });
+}
+
+struct ArcContext {
+    n_run: AtomicU32,
+    released: AtomicBool,
+}
+
+fn gated_task(ctx: &ArcContext) -> Result<(), TaskError> {
+    ctx.n_run.fetch_add(1, Ordering::SeqCst);
+    while !ctx.released.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(1));
+    }
+    Ok(())
+}
+
+fn arc_task(ctx: &ArcContext) -> Result<(), TaskError> {
+    ctx.n_run.fetch_add(1, Ordering::SeqCst);
+    Ok(())
+}
+
+#[test]
+fn run_arc() {
+    let mut g = Graph::new();
+    g.add_node("A", gated_task).unwrap();
+    g.add_node("B", arc_task).unwrap();
+    g.add_edge("A", "B").unwrap();
+    let s = Arc::new(Scheduler::new(g.froze().unwrap()));
+
+    let ctx = Arc::new(ArcContext{
+        n_run: AtomicU32::new(0),
+        released: AtomicBool::new(false),
+    });
+    let handle = {
+        let s = s.clone();
+        let ctx = ctx.clone();
+        thread::spawn(move || s.run_arc(ctx))
+    };
+    while ctx.n_run.load(Ordering::SeqCst) == 0 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    assert_eq!(ctx.n_run.load(Ordering::SeqCst), 1);
+    assert!(!handle.is_finished());
+    ctx.released.store(true, Ordering::SeqCst);
+    handle.join().unwrap().unwrap();
+    assert_eq!(ctx.n_run.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn run_arc_failed() {
+    let mut g = Graph::new();
+    g.add_node("A", failed_task("")).unwrap();
+    g.add_node("B", failed_task("B")).unwrap();
+    g.add_node("C", failed_task("")).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = Arc::new(FailedContext::new());
+    assert!(s.run_arc(ctx.clone()).is_err_and(
+        |e| if let RuntimeFailed{node, ..} = e { node == "B" } else { false }
+    ));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn abandon_in_flight_on_error() {
+    let mut g = Graph::new();
+    g.add_node("slow", |ctx: &ArcContext| -> Result<(), TaskError> {
+        thread::sleep(Duration::from_millis(500));
+        ctx.n_run.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }).unwrap();
+    g.add_node("failed", |_: &ArcContext| -> Result<(), TaskError> {
+        thread::sleep(Duration::from_millis(10));
+        Err(Box::new(std::fmt::Error))
+    }).unwrap();
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let sender = Mutex::new(sender);
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .abandon_in_flight_on_error(true)
+        .on_abandoned(move |name, status| sender.lock().unwrap().send((name.to_string(), status)).unwrap())
+        .build();
+    let ctx = Arc::new(ArcContext{
+        n_run: AtomicU32::new(0),
+        released: AtomicBool::new(false),
+    });
+    let t0 = Instant::now();
+    let err = s.run_arc(ctx.clone()).unwrap_err();
+    assert!(t0.elapsed() < Duration::from_millis(400));
+    assert_eq!(err.node_name(), Some("failed"));
+    assert_eq!(err.in_flight(), ["slow"]);
+    assert_eq!(ctx.n_run.load(Ordering::SeqCst), 0);
+    let (name, status) = receiver.recv_timeout(Duration::from_secs(10)).unwrap();
+    assert_eq!(name, "slow");
+    assert!(matches!(status, NodeStatus::Succeeded{..}));
+    assert_eq!(ctx.n_run.load(Ordering::SeqCst), 1);
+
+    // a run borrowing its context still waits
+    let t0 = Instant::now();
+    assert!(s.run(&*ctx).is_err());
+    assert!(t0.elapsed() >= Duration::from_millis(500));
+}
+
+fn assert_send<T: Send>() {}
+
+#[test]
+fn spawn_run() {
+    assert_send::<RunHandle>();
+
+    let mut g = Graph::new();
+    g.add_node("A", gated_task).unwrap();
+    g.add_node("B", arc_task).unwrap();
+    g.add_edge("A", "B").unwrap();
+    let s = Arc::new(Scheduler::new(g.froze().unwrap()));
+
+    let ctx = Arc::new(ArcContext{
+        n_run: AtomicU32::new(0),
+        released: AtomicBool::new(false),
+    });
+    let handle = s.clone().spawn_run(ctx.clone());
+    assert!(!handle.is_finished());
+    ctx.released.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+    assert_eq!(ctx.n_run.load(Ordering::SeqCst), 2);
+
+    // dropping the handle must not abort the run
+    let ctx = Arc::new(ArcContext{
+        n_run: AtomicU32::new(0),
+        released: AtomicBool::new(false),
+    });
+    drop(s.clone().spawn_run(ctx.clone()));
+    ctx.released.store(true, Ordering::SeqCst);
+    let t0 = Instant::now();
+    while ctx.n_run.load(Ordering::SeqCst) < 2 {
+        assert!(t0.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+fn wait_for_runs(ctx: &ArcContext, n_run: u32) {
+    let t0 = Instant::now();
+    while ctx.n_run.load(Ordering::SeqCst) < n_run {
+        assert!(t0.elapsed() < Duration::from_secs(10));
+        thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn drain() {
+    let mut g = Graph::new();
+    for name in ["A1", "A2"] {
+        g.add_node(name, gated_task).unwrap();
+    }
+    for name in ["B1", "B2", "C1"] {
+        g.add_node(name, arc_task).unwrap();
+    }
+    for (from, to) in [("A1", "B1"), ("A2", "B2"), ("B1", "C1"), ("B2", "C1")] {
+        g.add_edge(from, to).unwrap();
+    }
+    let frozen = g.froze().unwrap();
+    for policy in [FailurePolicy::FailFast, FailurePolicy::ContinueOnError] {
+        let s = Arc::new(SchedulerBuilder::new(frozen.clone()).failure_policy(policy).build());
+        let ctx = Arc::new(ArcContext{
+            n_run: AtomicU32::new(0),
+            released: AtomicBool::new(false),
+        });
+        let handle = s.spawn_run(ctx.clone());
+        wait_for_runs(&ctx, 2);
+        handle.drain();
+        ctx.released.store(true, Ordering::SeqCst);
+        let err = handle.join().unwrap_err();
+        assert!(err.is_runtime());
+        assert_eq!(err.to_string(), "run drained after 2 nodes, 3 not run");
+        assert!(matches!(err, Drained{completed, not_run}
+            if completed == ["A1", "A2"] && not_run == ["B1", "B2", "C1"]));
+        assert_eq!(ctx.n_run.load(Ordering::SeqCst), 2);
+    }
+
+    // everything was dispatched already
+    let mut g = Graph::new();
+    g.add_node("A1", gated_task).unwrap();
+    g.add_node("A2", gated_task).unwrap();
+    let s = Arc::new(Scheduler::new(g.froze().unwrap()));
+    let ctx = Arc::new(ArcContext{
+        n_run: AtomicU32::new(0),
+        released: AtomicBool::new(false),
+    });
+    let handle = s.spawn_run(ctx.clone());
+    wait_for_runs(&ctx, 2);
+    handle.drain();
+    ctx.released.store(true, Ordering::SeqCst);
+    handle.join().unwrap();
+}
+
+#[test]
+fn cancel() {
+    let mut g = Graph::new();
+    g.add_node("A", arc_task).unwrap();
+    g.add_node("B", gated_task).unwrap();
+    g.add_node("C", arc_task).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    let s = Arc::new(Scheduler::new(g.froze().unwrap()));
+    let ctx = Arc::new(ArcContext{
+        n_run: AtomicU32::new(0),
+        released: AtomicBool::new(false),
+    });
+    let handle = s.spawn_run(ctx.clone());
+    wait_for_runs(&ctx, 2);
+    handle.cancel();
+    // B is still running, and not waited for
+    let err = handle.join().unwrap_err();
+    ctx.released.store(true, Ordering::SeqCst);
+    assert!(err.is_runtime());
+    assert_eq!(err.to_string(), "run cancelled after 1 nodes, 2 pending");
+    assert!(matches!(err, Cancelled{completed, pending} if completed == ["A"] && pending == ["B", "C"]));
+}
+
+fn noop_task(_: &()) -> Result<(), TaskError> {
+    Ok(())
+}
+
+#[test]
+fn noop_overhead() {
+    let mut g = Graph::new();
+    let n_node: usize = 5000;
+    for i in 0..n_node {
+        let si = i.to_string();
+        g.add_node(&si, noop_task).unwrap();
+        if i >= 1 {
+            g.add_edge(&(i / 2).to_string(), &si).unwrap();
+        }
+    }
+    let s = Scheduler::new(g.froze().unwrap());
+
+    let n_round: u32 = 20;
+    let t0 = Instant::now();
+    for _ in 0..n_round {
+        s.run_sequential(&()).unwrap();
+    }
+    let sequential_cost = t0.elapsed() / n_round;
+
+    let t0 = Instant::now();
+    s.run(&()).unwrap();
+    let cost = t0.elapsed();
+    println!("noop_overhead cost: {} ms, sequential: {} us",
+        cost.as_millis(), sequential_cost.as_micros());
+    assert!(sequential_cost < Duration::from_millis(100));
+    assert!(cost < Duration::from_secs(10));
+}
+
+struct ThreadNameContext {
+    names: Mutex<Vec<Option<String>>>,
+}
+
+fn thread_name_task(ctx: &ThreadNameContext) -> Result<(), TaskError> {
+    let name = thread::current().name().map(|s| s.to_string());
+    ctx.names.lock().unwrap().push(name);
+    Ok(())
+}
+
+#[test]
+fn thread_names() {
+    let long_names: Vec<String> = ["A", "B", "C"].iter()
+        .map(|s| s.repeat(200)).collect();
+    let mut g = Graph::new();
+    for name in long_names.iter() {
+        g.add_node(name, thread_name_task).unwrap();
+    }
+    g.add_edge(&long_names[0], &long_names[1]).unwrap();
+    g.add_edge(&long_names[1], &long_names[2]).unwrap();
+    g.add_node(&"\u{2603}".repeat(8), thread_name_task).unwrap();
+    g.add_edge(&long_names[2], &"\u{2603}".repeat(8)).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = ThreadNameContext{names: Mutex::new(vec![])};
+    s.run(&ctx).unwrap();
+    let names = ctx.names.into_inner().unwrap();
+    assert_eq!(names.len(), 4);
+    for (i, name) in names.iter().enumerate() {
+        let name = name.as_ref().unwrap();
+        assert!(name.len() <= 63);
+        match i {
+            0..=2 => assert!(long_names[i].starts_with(name.as_str())),
+            _ => assert!("\u{2603}".repeat(8).starts_with(name.as_str())),
+        }
+    }
+
+    let mut g = Graph::new();
+    for name in long_names.iter() {
+        g.add_node(name, thread_name_task).unwrap();
+    }
+    let s = SchedulerBuilder::new(g.froze().unwrap()).name_threads(false).build();
+    let ctx = ThreadNameContext{names: Mutex::new(vec![])};
+    s.run(&ctx).unwrap();
+    let names = ctx.names.into_inner().unwrap();
+    assert_eq!(names, [None, None, None]);
+}
+
+#[inline(never)]
+fn recurse(depth: usize) -> usize {
+    let buf = std::hint::black_box([depth as u8; 1024]);
+    if depth == 0 {
+        return buf[0] as usize;
+    }
+    return recurse(depth - 1) + buf[1023] as usize;
+}
+
+#[test]
+fn stack_size() {
+    let mut g = Graph::new();
+    g.add_node("A", |_: &()| -> Result<(), TaskError> {
+        recurse(16 * 1024);
+        Ok(())
+    }).unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap()).stack_size(64 << 20).build();
+    s.run(&()).unwrap();
+}
+
+fn counter_task(ctx: &AtomicU32) -> Result<(), TaskError> {
+    ctx.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+fn counter_graph(n_node: usize, inline: bool) -> Graph<AtomicU32> {
+    let mut g = Graph::new();
+    for i in 0..n_node {
+        let si = i.to_string();
+        g.add_node(&si, counter_task).unwrap();
+        g.set_inline(&si, inline).unwrap();
+        if i >= 1 {
+            g.add_edge(&(i / 2).to_string(), &si).unwrap();
+        }
+    }
+    return g;
+}
+
+#[test]
+fn inline_tasks() {
+    let n_node: usize = 1000;
+
+    let s = Scheduler::new(counter_graph(n_node, false).froze().unwrap());
+    let ctx = AtomicU32::new(0);
+    let t0 = Instant::now();
+    s.run(&ctx).unwrap();
+    let spawn_cost = t0.elapsed();
+    assert_eq!(ctx.load(Ordering::Relaxed) as usize, n_node);
+
+    let s = Scheduler::new(counter_graph(n_node, true).froze().unwrap());
+    let ctx = AtomicU32::new(0);
+    let t0 = Instant::now();
+    s.run(&ctx).unwrap();
+    let inline_cost = t0.elapsed();
+    assert_eq!(ctx.load(Ordering::Relaxed) as usize, n_node);
+
+    println!("inline_tasks cost: {} us, spawning: {} us",
+        inline_cost.as_micros(), spawn_cost.as_micros());
+    assert!(inline_cost < Duration::from_millis(100));
+    assert!(inline_cost * 4 < spawn_cost);
+
+    let mut g = Graph::new();
+    g.set_inline("A", true).unwrap_err();
+    g.add_node("A", counter_task).unwrap();
+    assert!(g.set_inline("B", true).is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "B" } else { false }
+    ));
+}
+
+#[test]
+fn inline_failed() {
+    let mut g = Graph::new();
+    g.add_node("A", failed_task("")).unwrap();
+    g.add_node("B", failed_task("")).unwrap();
+    g.add_node("C", failed_task("C")).unwrap();
+    g.add_node("D", failed_task("")).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.add_edge("C", "D").unwrap();
+    g.set_inline("B", true).unwrap();
+    g.set_inline("C", true).unwrap();
+
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = FailedContext::new();
+    assert!(s.run(&ctx).is_err_and(
+        |e| if let RuntimeFailed{node, ..} = e { node == "C" } else { false }
+    ));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 3);
+
+    let mut g = Graph::new();
+    g.add_node("A", panicked_task("")).unwrap();
+    g.add_node("B", panicked_task("B")).unwrap();
+    g.add_node("C", panicked_task("")).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.set_inline("B", true).unwrap();
+
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = PanickedContext::new();
+    assert!(s.run(&ctx).is_err_and(|e| -> bool {
+        if let RuntimePanicked{node, err, ..} = e {
+            return node == "B" && err.downcast_ref::<String>() == Some(&node);
+        }
+        return false;
+    }));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 2);
+}
+
+#[test]
+fn fallback() {
+    let mut g = Graph::new();
+    g.add_node("A", |_: &AtomicU32| -> Result<(), TaskError> {
+        Err(Box::new(std::fmt::Error))
+    }).unwrap();
+    g.add_node("B", |_: &AtomicU32| -> Result<(), TaskError> {
+        panic!("B");
+    }).unwrap();
+    g.add_node("C", counter_task).unwrap();
+    g.add_edge("A", "C").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.set_fallback("A", counter_task).unwrap();
+    g.set_fallback("B", counter_task).unwrap();
+    assert!(g.set_fallback("D", counter_task).is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "D" } else { false }
+    ));
+
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = AtomicU32::new(0);
+    let (result, report) = s.run_with_report(&ctx);
+    result.unwrap();
+    assert_eq!(ctx.load(Ordering::Relaxed), 3);
+    assert!(report.node("A").unwrap().fallback_used);
+    assert!(report.node("B").unwrap().fallback_used);
+    assert!(!report.node("C").unwrap().fallback_used);
+    assert_eq!(s.metrics().tasks_succeeded(), 3);
+
+    let mut g = Graph::new();
+    g.add_node("A", failed_task("A")).unwrap();
+    g.set_fallback("A", failed_task("fallback")).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = FailedContext::new();
+    assert!(s.run_sequential(&ctx).is_err_and(|e| -> bool {
+        if let RuntimeFailed{node, err, ..} = e {
+            return node == "A" && err.to_string() == "fallback";
+        }
+        return false;
+    }));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 2);
+}
+
+fn hooked_graph() -> Graph<Mutex<Vec<String>>> {
+    let mut g: Graph<Mutex<Vec<String>>> = Graph::new();
+    g.add_node("A", |ctx: &Mutex<Vec<String>>| -> Result<(), TaskError> {
+        ctx.lock().unwrap().push("A".to_string());
+        Ok(())
+    }).unwrap();
+    g.add_node("B", |ctx: &Mutex<Vec<String>>| -> Result<(), TaskError> {
+        ctx.lock().unwrap().push("B".to_string());
+        panic!("B");
+    }).unwrap();
+    g.add_edge("A", "B").unwrap();
+    for name in ["A", "B"] {
+        g.set_hooks(name, move |ctx: &Mutex<Vec<String>>| -> Result<(), TaskError> {
+            ctx.lock().unwrap().push(format!("before {}", name));
+            Ok(())
+        }, move |ctx: &Mutex<Vec<String>>, result: &Result<(), TaskError>| {
+            let result = match result {
+                Ok(_) => "ok".to_string(),
+                Err(err) => err.to_string(),
+            };
+            ctx.lock().unwrap().push(format!("after {} {}", name, result));
+        }).unwrap();
+    }
+    return g;
+}
+
+#[test]
+fn hooks() {
+    let s = Scheduler::new(hooked_graph().froze().unwrap());
+    let ctx = Mutex::new(vec![]);
+    assert!(s.run(&ctx).is_err_and(
+        |e| if let RuntimePanicked{node, ..} = e { node == "B" } else { false }
+    ));
+    assert_eq!(ctx.into_inner().unwrap(), [
+        "before A", "A", "after A ok", "before B", "B", "after B task panicked: B",
+    ]);
+
+    let mut g = Graph::new();
+    g.add_node("A", failed_task("")).unwrap();
+    g.set_hooks("A", |_: &FailedContext| -> Result<(), TaskError> {
+        Err(Box::new(FailedError{reason: "before".to_string()}))
+    }, |ctx: &FailedContext, _: &Result<(), TaskError>| {
+        ctx.n_run.fetch_add(10, Ordering::Relaxed);
+    }).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = FailedContext::new();
+    assert!(s.run(&ctx).is_err_and(
+        |e| if let RuntimeFailed{node, err, ..} = e { node == "A" && err.to_string() == "before" } else { false }
+    ));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 0);
+}
+
+#[test]
+fn each_hooks() {
+    let n_node: usize = 20;
+    let calls: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+    let (before_calls, after_calls) = (calls.clone(), calls.clone());
+    let s = SchedulerBuilder::new(counter_graph(n_node, false).froze().unwrap())
+        .on_before_each(move |name: &str, _: &AtomicU32| {
+            assert_eq!(thread::current().name(), Some(name));
+            before_calls.lock().unwrap().push(format!("before {}", name));
+        })
+        .on_after_each(move |name: &str, ctx: &AtomicU32, result: &Result<(), TaskError>| {
+            assert_eq!(thread::current().name(), Some(name));
+            assert!(result.is_ok() && ctx.load(Ordering::Relaxed) > 0);
+            after_calls.lock().unwrap().push(format!("after {}", name));
+        })
+        .build();
+    let ctx = AtomicU32::new(0);
+    s.run(&ctx).unwrap();
+    assert_eq!(ctx.load(Ordering::Relaxed) as usize, n_node);
+
+    let calls = calls.lock().unwrap();
+    assert_eq!(calls.len(), n_node * 2);
+    for i in 0..n_node {
+        let before = calls.iter().position(|call| *call == format!("before {}", i)).unwrap();
+        let after = calls.iter().position(|call| *call == format!("after {}", i)).unwrap();
+        assert!(before < after);
+        assert_eq!(calls.iter().filter(|call| call.ends_with(&format!(" {}", i))).count(), 2);
+    }
+}
+
+#[test]
+fn before_each_panics() {
+    let calls: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+    let mut g = Graph::new();
+    let task_calls = calls.clone();
+    g.add_node("A", move |_: &()| -> Result<(), TaskError> {
+        task_calls.lock().unwrap().push(String::from("task"));
+        return Ok(());
+    }).unwrap();
+    let (check_calls, after_calls) = (calls.clone(), calls.clone());
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .on_before_each(|_: &str, _: &()| panic!("before"))
+        .post_task_check(move |_: &()| -> Result<(), TaskError> {
+            check_calls.lock().unwrap().push(String::from("check"));
+            return Ok(());
+        })
+        .on_after_each(move |_: &str, _: &(), result: &Result<(), TaskError>| {
+            assert!(result.is_ok());
+            after_calls.lock().unwrap().push(String::from("after"));
+        })
+        .build();
+    assert!(s.run(&()).is_err_and(
+        |e| if let RuntimePanicked{node, err, ..} = e { node == "A" && err.downcast_ref::<&str>() == Some(&"before") } else { false }
+    ));
+    assert_eq!(*calls.lock().unwrap(), ["task", "check", "after"]);
+}
+
+#[test]
+fn post_task_check() {
+    let mut g = Graph::new();
+    g.add_node("A", |_: &Mutex<u32>| Ok(())).unwrap();
+    // returns Ok, after a panic it caught while holding the lock
+    g.add_node("B", |ctx: &Mutex<u32>| -> Result<(), TaskError> {
+        let _ = std::panic::catch_unwind(|| {
+            let _guard = ctx.lock().unwrap();
+            panic!("poisoning");
+        });
+        return Ok(());
+    }).unwrap();
+    g.add_node("C", |_: &Mutex<u32>| Ok(())).unwrap();
+    g.add_node("D", |_: &Mutex<u32>| Ok(())).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.add_edge("A", "D").unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .post_task_check(|ctx: &Mutex<u32>| -> Result<(), TaskError> {
+            if ctx.is_poisoned() {
+                ctx.clear_poison();
+                return Err(Box::new(std::fmt::Error));
+            }
+            return Ok(());
+        })
+        .failure_policy(FailurePolicy::ContinueOnError)
+        // so that the check after D cannot see the lock poisoned by B
+        .max_concurrency(1)
+        .build();
+    let (result, report) = s.run_with_report(&Mutex::new(0));
+    let Err(MultipleFailures{failures, ..}) = result else {
+        panic!("B did not fail");
+    };
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].node_name(), Some("B"));
+    let err = failures[0].task_error().unwrap().downcast_ref::<PostTaskCheckError>().unwrap();
+    assert_eq!(err.node, "B");
+    assert_eq!(failures[0].to_string(), "run B failed: post-task check failed after B: an error occurred when formatting an argument");
+    assert!(matches!(report.node("C").unwrap().status, NodeStatus::Skipped{..}));
+    assert!(matches!(report.node("D").unwrap().status, NodeStatus::Succeeded{..}));
+}
+
+#[test]
+fn infallible_and_once_nodes() {
+    let mut g = Graph::new();
+    g.add_infallible_node("A", |ctx: &AtomicU32| {
+        ctx.fetch_add(1, Ordering::Relaxed);
+    }).unwrap();
+    let owned = vec![1, 2, 3];
+    g.add_once_node("B", move |ctx: &AtomicU32| -> Result<(), TaskError> {
+        ctx.fetch_add(owned.into_iter().sum(), Ordering::Relaxed);
+        Ok(())
+    }).unwrap();
+    g.add_edge("A", "B").unwrap();
+    assert!(g.add_infallible_node("A", |_: &AtomicU32| {}).is_err_and(
+        |e| if let DuplicatedNode{name} = e { name == "A" } else { false }
+    ));
+
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = AtomicU32::new(0);
+    s.run(&ctx).unwrap();
+    assert_eq!(ctx.load(Ordering::Relaxed), 7);
+
+    let ctx = AtomicU32::new(0);
+    assert!(s.run(&ctx).is_err_and(
+        |e| if let RuntimeFailed{node, err, ..} = e { node == "B" && err.to_string() == "once task already ran" } else { false }
+    ));
+    assert_eq!(ctx.load(Ordering::Relaxed), 1);
+}
+
+struct AddTask {
+    amount: u32,
+}
+
+impl AddTask {
+    fn amount(&self) -> u32 {
+        return self.amount;
+    }
+}
+
+impl TaskRun<AtomicU32> for AddTask {
+    fn run(&self, ctx: &AtomicU32) -> Result<(), TaskError> {
+        ctx.fetch_add(self.amount(), Ordering::Relaxed);
+        return Ok(());
+    }
+
+    fn name_hint(&self) -> Option<&str> {
+        return Some("add");
+    }
+}
+
+#[test]
+fn struct_tasks() {
+    let task = AddTask{amount: 10};
+    assert_eq!(task.name_hint(), Some("add"));
+    let mut g = Graph::new();
+    g.add_task("A", task).unwrap();
+    g.add_node("B", counter_task).unwrap();
+    g.add_edge("A", "B").unwrap();
+    assert!(g.add_task("A", AddTask{amount: 1}).is_err_and(
+        |e| if let DuplicatedNode{name} = e { name == "A" } else { false }
+    ));
+    g.replace_task("B", AddTask{amount: 100}).unwrap();
+    assert!(g.replace_task("C", AddTask{amount: 1}).is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "C" } else { false }
+    ));
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = AtomicU32::new(0);
+    s.run(&ctx).unwrap();
+    assert_eq!(ctx.load(Ordering::Relaxed), 110);
+}
+
+static PANIC_LINES: [AtomicU32; 2] = [AtomicU32::new(0), AtomicU32::new(0)];
+
+fn panic_at_first(_: &()) -> Result<(), TaskError> {
+    thread::sleep(Duration::from_millis(20));
+    PANIC_LINES[0].store(line!(), Ordering::Relaxed); panic!("first");
+}
+
+fn panic_at_second(_: &()) -> Result<(), TaskError> {
+    thread::sleep(Duration::from_millis(20));
+    PANIC_LINES[1].store(line!(), Ordering::Relaxed); panic!("second");
+}
+
+#[test]
+fn panic_location() {
+    let mut g = Graph::new();
+    g.add_node("A", panic_at_first).unwrap();
+    g.add_node("B", panic_at_second).unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap()).failure_policy(FailurePolicy::ContinueOnError).build();
+    let err = s.run(&()).unwrap_err();
+    let MultipleFailures{failures, ..} = err else {
+        panic!("unexpected error: {}", err);
+    };
+    assert_eq!(failures.len(), 2);
+    for err in failures {
+        let RuntimePanicked{node, info, ..} = &err else {
+            panic!("unexpected error: {}", err);
+        };
+        let info = info.as_ref().unwrap();
+        let i = if node == "A" { 0 } else { 1 };
+        assert_eq!(info.file, file!());
+        assert_eq!(info.line, PANIC_LINES[i].load(Ordering::Relaxed));
+        assert!(err.to_string().ends_with(&format!(" at {}:{}:{}", file!(), info.line, info.column)));
+        assert_eq!(info.backtrace.is_some(), cfg!(feature = "backtrace"));
+    }
+}
+
+#[test]
+fn run_dirty() {
+    let mut g = Graph::new();
+    for name in ["A1", "A2", "A3", "B1", "B2", "B3", "C1", "C2", "C3"] {
+        g.add_node(name, toposort_task(name)).unwrap();
+    }
+    for (from, to) in [
+        ("A1", "B1"), ("A1", "B2"), ("A1", "B3"), ("A2", "B1"), ("A2", "B3"), ("A3", "B3"),
+        ("B1", "C2"), ("B1", "C3"), ("B2", "C2"), ("B3", "C1"), ("B3", "C2"), ("B3", "C3"),
+    ] {
+        g.add_edge(from, to).unwrap();
+    }
+    let s = Scheduler::new(g.froze().unwrap());
+
+    let ctx = ToposortContext::new();
+    s.run_dirty(&ctx, &["B1"]).unwrap();
+    let result = ctx.result.into_inner().unwrap();
+    assert_eq!(result.len(), 3);
+    assert_eq!(result[0], "B1");
+    assert!(result.contains(&"C2".to_string()) && result.contains(&"C3".to_string()));
+
+    let ctx = ToposortContext::new();
+    s.run_dirty(&ctx, &["C1", "A3"]).unwrap();
+    let result = ctx.result.into_inner().unwrap();
+    assert_eq!(result.len(), 5);
+    assert_eq!(result[0], "A3");
+    assert_eq!(result[1], "B3");
+
+    let ctx = ToposortContext::new();
+    s.run_dirty(&ctx, &[]).unwrap();
+    assert!(ctx.result.lock().unwrap().is_empty());
+    assert!(s.run_dirty(&ctx, &["B1", "X"]).is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "X" } else { false }
+    ));
+}
+
+#[test]
+fn stuck_warnings() {
+    let mut g = Graph::new();
+    g.add_node("slow", sleep_task(300)).unwrap();
+    g.add_node("fast", sleep_task(10)).unwrap();
+    let warnings = Arc::new(Mutex::new(vec![]));
+    let recorded = warnings.clone();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .stuck_warning_after(Duration::from_millis(100), move |name, elapsed| {
+            recorded.lock().unwrap().push((name.to_string(), elapsed));
+        })
+        .build();
+    s.run(&SleepContext::new()).unwrap();
+    let warnings = warnings.lock().unwrap();
+    println!("stuck warnings: {:?}", warnings);
+    assert!(warnings.len() >= 2);
+    for (i, (name, elapsed)) in warnings.iter().enumerate() {
+        assert_eq!(name, "slow");
+        assert!(*elapsed >= Duration::from_millis(100 * (i as u64 + 1)));
+    }
+}
+
+fn ticking_task(ctx: &TaskCtx<'_, SleepContext>) -> Result<(), TaskError> {
+    for i in 0..=10 {
+        ctx.set_progress(i as f32 / 10.0);
+        thread::sleep(Duration::from_millis(30));
+    }
+    ctx.n_run.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+#[test]
+fn heartbeats() {
+    let mut g = Graph::new();
+    g.add_node_with_ctx("tick", ticking_task).unwrap();
+    g.add_node("silent", sleep_task(330)).unwrap();
+    let warned = Arc::new(Mutex::new(vec![]));
+    let recorded = warned.clone();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .stuck_warning_after(Duration::from_millis(100), move |name, _| {
+            recorded.lock().unwrap().push(name.to_string());
+        })
+        .build();
+    let metrics = s.metrics();
+    assert_eq!(metrics.progress("tick"), None);
+    let ctx = SleepContext::new();
+    let sampled = thread::scope(|scope| {
+        let observer = scope.spawn(|| {
+            thread::sleep(Duration::from_millis(150));
+            return (metrics.progress("tick"), metrics.last_heartbeat("tick"));
+        });
+        s.run(&ctx).unwrap();
+        return observer.join().unwrap();
+    });
+    let (progress, heartbeat) = sampled;
+    let progress = progress.unwrap();
+    assert!(progress > 0.0 && progress < 1.0);
+    assert!(heartbeat.unwrap().elapsed() > Duration::from_millis(150));
+    assert_eq!(metrics.progress("tick"), Some(1.0));
+    assert_eq!(metrics.progress("silent"), None);
+    assert_eq!(metrics.progress("X"), None);
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 2);
+
+    let warned = warned.lock().unwrap();
+    assert!(!warned.is_empty());
+    assert!(warned.iter().all(|name| name == "silent"));
+}
+
+#[test]
+fn observers() {
+    let events: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+    let s = SchedulerBuilder::new(toposort_graph().froze().unwrap())
+        .max_concurrency(1)
+        .observer(move |event: &Event<'_>| {
+            recorded.lock().unwrap().push(match event {
+                Event::RunStarted{..} => "run started".to_string(),
+                Event::NodeReady{name, ..} => format!("ready {}", name),
+                Event::NodeStarted{name, ..} => format!("started {}", name),
+                Event::NodeFinished{name, status, ..} => {
+                    assert!(matches!(status, NodeStatus::Succeeded{over_budget: false, ..}));
+                    format!("finished {}", name)
+                },
+                Event::NodeSlow{name, ..} => format!("slow {}", name),
+                Event::QueueSaturated{..} => unreachable!("the ready queue is not bounded"),
+                Event::RunFinished{succeeded, ..} => format!("run finished {}", succeeded),
+            });
+        })
+        .build();
+    let ctx = ToposortContext::new();
+    s.run(&ctx).unwrap();
+    let events = events.lock().unwrap();
+    // every node is ready before it is started
+    for (i, event) in events.iter().enumerate() {
+        if let Some(name) = event.strip_prefix("started ") {
+            assert!(events[..i].contains(&format!("ready {}", name)));
+        }
+    }
+    let events: Vec<&String> = events.iter().filter(|event| !event.starts_with("ready ")).collect();
+    assert_eq!(events.len(), 2 + 9 * 2);
+    assert_eq!(events[0], "run started");
+    assert_eq!(events[events.len() - 1], "run finished true");
+    // one node at a time, each finishing before the next starts
+    for (i, name) in ctx.result.lock().unwrap().iter().enumerate() {
+        assert_eq!(*events[1 + i * 2], format!("started {}", name));
+        assert_eq!(*events[2 + i * 2], format!("finished {}", name));
+    }
+}
+
+#[test]
+fn run_options() {
+    let s = Scheduler::new(toposort_graph().froze().unwrap());
+    let ctx = ToposortContext::new();
+    s.run_with(&ctx, RunOptions{
+        targets: Some(vec!["B2".to_string()]),
+        skip: vec!["A1".to_string()],
+        ..RunOptions::default()
+    }).unwrap();
+    let mut result = ctx.result.into_inner().unwrap();
+    result.sort();
+    assert_eq!(result, ["A2", "A3", "B2"]);
+
+    let ctx = ToposortContext::new();
+    s.run_with(&ctx, RunOptions::default()).unwrap();
+    check_toposort(&ctx.result.into_inner().unwrap());
+
+    let mut g = Graph::new();
+    g.add_node("A", failed_task("A")).unwrap();
+    g.add_node("B", failed_task("B")).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = FailedContext::new();
+    assert!(s.run(&ctx).is_err_and(|e| if let RuntimeFailed{node, ..} = e { node == "A" } else { false }));
+    assert!(s.run_with(&ctx, RunOptions{
+        failure_policy: Some(FailurePolicy::ContinueOnError),
+        ..RunOptions::default()
+    }).is_err_and(|e| if let MultipleFailures{failures, ..} = e { failures.len() == 2 } else { false }));
+    assert!(s.run_with(&ctx, RunOptions{
+        targets: Some(vec!["X".to_string()]),
+        ..RunOptions::default()
+    }).is_err_and(|e| if let NodeNotFound{name} = e { name == "X" } else { false }));
+}
+
+#[test]
+fn run_summary() {
+    let s = Scheduler::new(toposort_graph().froze().unwrap());
+    let summary = s.run_with(&ToposortContext::new(), RunOptions{
+        targets: Some(vec!["B2".to_string()]),
+        skip: vec!["A1".to_string()],
+        ..RunOptions::default()
+    }).unwrap();
+    assert_eq!((summary.executed, summary.skipped, summary.cached), (3, 6, 0));
+    let summary = s.run_with(&ToposortContext::new(), RunOptions::default()).unwrap();
+    assert_eq!((summary.executed, summary.skipped), (9, 0));
+    let summary = s.run_full(&ToposortContext::new()).unwrap();
+    assert_eq!((summary.executed, summary.skipped), (9, 0));
+    let summary = s.run_dirty(&ToposortContext::new(), &["B2"]).unwrap();
+    assert!(summary.executed > 0 && summary.executed + summary.skipped == 9);
+}
+
+#[test]
+fn node_named_root() {
+    let mut g = Graph::new();
+    for name in ["$ROOT", "A", "B"] {
+        g.add_node(name, toposort_task(name)).unwrap();
+    }
+    g.add_edge("A", "$ROOT").unwrap();
+    g.add_edge("$ROOT", "B").unwrap();
+    let frozen = g.froze().unwrap();
+    assert_eq!(frozen.shortest_path("A", "B").unwrap().unwrap(), ["A", "$ROOT", "B"]);
+    let s = Scheduler::new(frozen);
+    let ctx = ToposortContext::new();
+    s.run(&ctx).unwrap();
+    assert_eq!(*ctx.result.lock().unwrap(), ["A", "$ROOT", "B"]);
+    let (result, report) = s.run_with_report(&ctx);
+    result.unwrap();
+    assert_eq!(report.nodes().len(), 3);
+    assert!(matches!(report.node("$ROOT").unwrap().status, NodeStatus::Succeeded{..}));
+}
+
+#[test]
+fn duplicate_edges() {
+    let mut g = Graph::new();
+    g.add_node("A", toposort_task("A")).unwrap();
+    g.add_node("B", toposort_task("B")).unwrap();
+    assert!(g.add_edge_idempotent("A", "B").unwrap());
+    assert!(!g.add_edge_idempotent("A", "B").unwrap());
+    assert!(g.add_edge("A", "B").is_err_and(
+        |e| if let DuplicatedEdge{from_node, to_node} = e { from_node == "A" && to_node == "B" } else { false }
+    ));
+    g.set_duplicate_edge_policy(DuplicateEdgePolicy::Ignore);
+    g.add_edge("A", "B").unwrap();
+    assert!(g.add_edge("A", "X").is_err());
+    assert!(g.add_edge_idempotent("B", "B").is_err());
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = ToposortContext::new();
+    s.run(&ctx).unwrap();
+    assert_eq!(*ctx.result.lock().unwrap(), ["A", "B"]);
+}
+
+#[test]
+fn override_node() {
+    let mut g = Graph::new();
+    for name in ["A", "B", "C"] {
+        assert!(!g.add_or_replace_node(name, toposort_task(name)).unwrap());
+    }
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.set_inline("B", true).unwrap();
+    let overridden = Arc::new(AtomicBool::new(false));
+    let flag = overridden.clone();
+    assert!(g.add_or_replace_node("B", move |ctx: &ToposortContext| -> Result<(), TaskError> {
+        flag.store(true, Ordering::Relaxed);
+        ctx.result.lock().unwrap().push("B'".to_string());
+        Ok(())
+    }).unwrap());
+    assert!(g.add_or_replace_node("", toposort_task("")).is_err_and(
+        |e| if let InvalidNode{name, ..} = e { name.is_empty() } else { false }
+    ));
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = ToposortContext::new();
+    s.run(&ctx).unwrap();
+    assert!(overridden.load(Ordering::Relaxed));
+    assert_eq!(*ctx.result.lock().unwrap(), ["A", "B'", "C"]);
+}
+
+#[test]
+fn rename_node() {
+    let mut g = Graph::new();
+    g.add_node("A", failed_task("")).unwrap();
+    g.add_node("B", failed_task("failed")).unwrap();
+    g.add_node("C", failed_task("")).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.rename_node("B", "renamed").unwrap();
+    g.add_edge("renamed", "C").unwrap();
+    assert!(g.add_edge("B", "C").is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "B" } else { false }
+    ));
+    assert!(g.rename_node("B", "D").is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "B" } else { false }
+    ));
+    assert!(g.rename_node("A", "C").is_err_and(
+        |e| if let DuplicatedNode{name} = e { name == "C" } else { false }
+    ));
+    assert!(g.rename_node("A", "").is_err_and(
+        |e| if let InvalidNode{name, ..} = e { name.is_empty() } else { false }
+    ));
+    g.rename_node("A", "A").unwrap();
+    g.add_node("B", failed_task("")).unwrap();
+
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = FailedContext::new();
+    assert!(s.run(&ctx).is_err_and(
+        |e| if let RuntimeFailed{node, ..} = e { node == "renamed" } else { false }
+    ));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 3);
+}
+
+#[derive(Default)]
+struct GaugeContext {
+    running: AtomicU32,
+    max_running: AtomicU32,
+}
+
+fn measure(gauges: &[&GaugeContext], duration_ms: u64) {
+    for gauge in gauges {
+        let running = gauge.running.fetch_add(1, Ordering::SeqCst) + 1;
+        gauge.max_running.fetch_max(running, Ordering::SeqCst);
+    }
+    thread::sleep(Duration::from_millis(duration_ms));
+    for gauge in gauges {
+        gauge.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+fn gauge_task(duration_ms: u64) -> Task<GaugeContext> {
+    Box::new(move |ctx: &GaugeContext| -> Result<(), TaskError> {
+        measure(&[ctx], duration_ms);
+        Ok(())
+    })
+}
+
+#[test]
+fn max_total_weight() {
+    let mut g = Graph::new();
+    for name in ["A", "B", "C"] {
+        g.add_node(name, gauge_task(20)).unwrap();
+        g.set_weight(name, 60).unwrap();
+    }
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .max_total_weight(100)
+        .build();
+    let ctx = GaugeContext::default();
+    s.run(&ctx).unwrap();
+    assert_eq!(ctx.max_running.load(Ordering::SeqCst), 1);
+
+    // the weightless ones are never held back, and a node heavier than the
+    // budget still runs on its own
+    let mut g = Graph::new();
+    for i in 0..4 {
+        g.add_node(&i.to_string(), gauge_task(20)).unwrap();
+    }
+    g.add_node("heavy", gauge_task(20)).unwrap();
+    g.set_weight("heavy", 1000).unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .max_total_weight(100)
+        .build();
+    let ctx = GaugeContext::default();
+    s.run(&ctx).unwrap();
+    assert_eq!(ctx.max_running.load(Ordering::SeqCst), 5);
+}
+
+#[test]
+fn class_limits() {
+    // gauges of the io nodes, the cpu nodes and all of them
+    let mut g: Graph<[GaugeContext; 3]> = Graph::new();
+    for i in 0..8 {
+        let name = format!("io-{}", i);
+        g.add_node(&name, |ctx: &[GaugeContext; 3]| -> Result<(), TaskError> {
+            measure(&[&ctx[0], &ctx[2]], 30);
+            Ok(())
+        }).unwrap();
+        g.set_class(&name, TaskClass::Io).unwrap();
+    }
+    for i in 0..4 {
+        let name = format!("cpu-{}", i);
+        g.add_node(&name, |ctx: &[GaugeContext; 3]| -> Result<(), TaskError> {
+            measure(&[&ctx[1], &ctx[2]], 30);
+            Ok(())
+        }).unwrap();
+        g.set_class(&name, TaskClass::Cpu).unwrap();
+    }
+    g.add_node("unclassified", |_: &[GaugeContext; 3]| -> Result<(), TaskError> {
+        Ok(())
+    }).unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .limit_class(TaskClass::Io, 1)
+        .limit_class(TaskClass::Io, 2)
+        .limit_class(TaskClass::Cpu, 4)
+        .limit_class(TaskClass::Custom(String::from("gpu")), 1)
+        .build();
+    let ctx = <[GaugeContext; 3]>::default();
+    let (result, report) = s.run_with_report(&ctx);
+    result.unwrap();
+    assert_eq!(ctx[0].max_running.load(Ordering::SeqCst), 2);
+    assert_eq!(ctx[1].max_running.load(Ordering::SeqCst), 4);
+    // io nodes waiting do not hold back the cpu ones
+    assert_eq!(ctx[2].max_running.load(Ordering::SeqCst), 6);
+    assert_eq!(report.nodes().len(), 13);
+}
+
+#[test]
+fn strict_classes() {
+    let graph = || {
+        let mut g: Graph<()> = Graph::new();
+        g.add_infallible_node("fetch", |_| {}).unwrap();
+        g.set_class("fetch", TaskClass::Io).unwrap();
+        g.add_infallible_node("train", |_| {}).unwrap();
+        g.set_class("train", TaskClass::Custom(String::from("gpu"))).unwrap();
+        g.add_infallible_node("plain", |_| {}).unwrap();
+        return g.froze().unwrap();
+    };
+    let result = SchedulerBuilder::new(graph())
+        .limit_class(TaskClass::Io, 1)
+        .strict_classes(true)
+        .try_build();
+    assert!(result.is_err_and(|e| if let UnsatisfiableNode{name, ..} = e { name == "train" } else { false }));
+
+    let s = SchedulerBuilder::new(graph())
+        .limit_class(TaskClass::Io, 1)
+        .limit_class(TaskClass::Custom(String::from("gpu")), 1)
+        .strict_classes(true)
+        .try_build()
+        .unwrap();
+    s.run(&()).unwrap();
+    assert!(SchedulerBuilder::new(graph()).limit_class(TaskClass::Io, 1).try_build().is_ok());
+}
+
+#[test]
+fn send_not_sync_context() {
+    // a cache filled and read through shared references, so not Sync
+    #[derive(Debug)]
+    struct CachedContext {
+        fail: std::cell::Cell<bool>,
+        cache: std::cell::RefCell<Vec<String>>,
+        worker: std::cell::Cell<Option<thread::ThreadId>>,
+    }
+
+    let mut g: Graph<CachedContext> = Graph::new();
+    for name in ["a", "b", "c"] {
+        g.add_node(name, move |ctx: &CachedContext| -> Result<(), TaskError> {
+            ctx.cache.borrow_mut().push(name.to_string());
+            ctx.worker.set(Some(thread::current().id()));
+            Ok(())
+        }).unwrap();
+    }
+    g.add_node("broken", |ctx: &CachedContext| -> Result<(), TaskError> {
+        if ctx.fail.get() {
+            assert_eq!(ctx.cache.borrow().len(), 3);
+            return Err(Box::new(std::fmt::Error));
+        }
+        Ok(())
+    }).unwrap();
+    g.add_edge("b", "a").unwrap();
+    g.add_edge("a", "c").unwrap();
+    g.add_edge("c", "broken").unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap()).build();
+
+    let ctx = CachedContext{fail: std::cell::Cell::new(true), cache: Default::default(), worker: Default::default()};
+    let (ctx, err) = s.run_sequential_send(ctx).unwrap_err();
+    assert!(matches!(err, RuntimeFailed{node, ..} if node == "broken"));
+    assert_eq!(*ctx.cache.borrow(), vec!["b", "a", "c"]);
+    assert_ne!(ctx.worker.get(), Some(thread::current().id()));
+
+    ctx.cache.borrow_mut().clear();
+    ctx.fail.set(false);
+    let ctx = s.run_sequential_send(ctx).unwrap();
+    assert_eq!(ctx.cache.borrow().len(), 3);
+}
+
+#[test]
+fn run_ids() {
+    let events: Arc<Mutex<Vec<(RunId, String)>>> = Arc::new(Mutex::new(vec![]));
+    let recorded = events.clone();
+    let both_started = Arc::new(std::sync::Barrier::new(2));
+    let mut g: Graph<()> = Graph::new();
+    let barrier = both_started.clone();
+    g.add_node("first", move |_: &()| -> Result<(), TaskError> {
+        barrier.wait();
+        Ok(())
+    }).unwrap();
+    g.add_node("second", |_: &()| -> Result<(), TaskError> {
+        Err(Box::new(std::fmt::Error))
+    }).unwrap();
+    g.add_edge("first", "second").unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .observer(move |event: &Event<'_>| {
+            let name = match event {
+                Event::NodeStarted{name, ..} => name,
+                Event::NodeReady{..} => return,
+                _ => "",
+            };
+            recorded.lock().unwrap().push((event.run_id().clone(), name.to_string()));
+        })
+        .build();
+
+    // the first nodes of both runs wait for each other, so the runs overlap
+    let (left, right) = thread::scope(|scope| {
+        let left = scope.spawn(|| s.run_with(&(), RunOptions::default().run_id("left")));
+        let right = scope.spawn(|| s.run_with(&(), RunOptions::default().run_id(String::from("right"))));
+        return (left.join().unwrap(), right.join().unwrap());
+    });
+    assert!(left.is_err_and(|e| e.run_id() == Some(&RunId::new("left"))));
+    assert!(right.is_err_and(|e| if let RuntimeFailed{run_id, ..} = e { run_id.as_str() == "right" } else { false }));
+
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 2 * (2 + 2 * 2));
+    for run in ["left", "right"] {
+        let of_run: Vec<_> = events.iter().filter(|(run_id, _)| run_id.as_str() == run).map(|(_, name)| name.as_str()).collect();
+        assert_eq!(of_run, vec!["", "first", "", "second", "", ""]);
+    }
+    drop(events);
+
+    let s = Scheduler::new(toposort_graph().froze().unwrap());
+    let (_, first) = s.run_with_report(&ToposortContext::new());
+    let (_, second) = s.run_with_report(&ToposortContext::new());
+    assert_ne!(first.run_id(), second.run_id());
+}
+
+#[test]
+fn run_collect() {
+    let mut g: Graph<AtomicU32> = Graph::new();
+    g.add_output_node("root", |ctx: &AtomicU32| Ok(ctx.fetch_add(1, Ordering::SeqCst))).unwrap();
+    for (name, value) in [("a", 1usize), ("b", 2), ("c", 3)] {
+        g.add_output_node(name, move |_: &AtomicU32| Ok(value * 10)).unwrap();
+        g.add_edge("root", name).unwrap();
+    }
+    g.add_node_with_ctx("d", |ctx: &TaskCtx<'_, AtomicU32>| {
+        ctx.set_output(1usize);
+        ctx.set_output(40usize);
+        Ok(())
+    }).unwrap();
+    g.add_edge("root", "d").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let outputs = s.run_collect::<usize>(&AtomicU32::new(0)).unwrap();
+    assert_eq!(outputs.len(), 4);
+    for (name, value) in [("a", 10), ("b", 20), ("c", 30), ("d", 40)] {
+        assert_eq!(outputs[name], value);
+    }
+    assert!(s.run_collect::<u32>(&AtomicU32::new(0)).is_err_and(|e| {
+        if let OutputTypeMismatch{node, expected, ..} = e { node != "root" && expected == "u32" } else { false }
+    }));
+
+    let mut g: Graph<()> = Graph::new();
+    g.add_output_node("a", |_: &()| Ok(1usize)).unwrap();
+    g.add_node("b", |_: &()| Ok(())).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    assert!(s.run_collect::<usize>(&()).is_err_and(|e| if let MissingOutput{node, ..} = e { node == "b" } else { false }));
+}
+
+#[test]
+fn hopeless_nodes() {
+    let mut g: Graph<Mutex<Vec<&'static str>>> = Graph::new();
+    for name in ["slow", "after_slow", "unhinted", "quick"] {
+        g.add_node(name, move |ctx: &Mutex<Vec<&'static str>>| {
+            ctx.lock().unwrap().push(name);
+            Ok(())
+        }).unwrap();
+    }
+    g.add_edge("slow", "after_slow").unwrap();
+    g.set_cost("slow", Duration::from_millis(500)).unwrap();
+    g.set_cost("quick", Duration::from_millis(10)).unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .clock(Arc::new(ManualClock::new()))
+        .deadline(Duration::from_millis(100))
+        .skip_hopeless_nodes(true)
+        .build();
+    let ctx = Mutex::new(vec![]);
+    let (result, report) = s.run_with_report(&ctx);
+    assert!(result.is_err_and(|e| {
+        if let DeadlineExceeded{deadline, mut completed, not_run} = e {
+            completed.sort();
+            deadline == Duration::from_millis(100) && completed == ["quick", "unhinted"] && not_run == ["slow", "after_slow"]
+        } else { false }
+    }));
+    let mut ran = ctx.into_inner().unwrap();
+    ran.sort();
+    assert_eq!(ran, ["quick", "unhinted"]);
+    assert!(matches!(report.node("slow").unwrap().status, NodeStatus::SkippedForDeadline{estimate, ..} if estimate == Duration::from_millis(500)));
+    assert_eq!(report.node("after_slow").unwrap().status, NodeStatus::NotRun);
+}
+
+#[test]
+fn deadline() {
+    fn late_task(ctx: &TaskCtx<'_, ()>) -> Result<(), TaskError> {
+        ctx.clock().sleep(Duration::from_secs(50));
+        Ok(())
+    }
+    let clock = Arc::new(ManualClock::new());
+    let mut g: Graph<()> = Graph::new();
+    g.add_node_with_ctx("a", late_task).unwrap();
+    g.add_node("b", |_: &()| Ok(())).unwrap();
+    g.add_edge("a", "b").unwrap();
+    let frozen = g.froze().unwrap();
+    let s = SchedulerBuilder::new(frozen.clone()).clock(clock.clone()).deadline(Duration::from_secs(20)).build();
+    assert!(s.run(&()).is_err_and(|e| {
+        if let DeadlineExceeded{completed, not_run, ..} = e { completed == ["a"] && not_run == ["b"] } else { false }
+    }));
+    assert_eq!(clock.elapsed(), Duration::from_secs(50));
+    let s = SchedulerBuilder::new(frozen).clock(clock.clone()).deadline(Duration::from_secs(60)).build();
+    assert!(s.run(&()).is_ok());
+
+    // running late on the last node is no failure, nothing was left out
+    let mut g: Graph<()> = Graph::new();
+    g.add_node_with_ctx("a", late_task).unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap()).clock(clock).deadline(Duration::from_secs(20)).build();
+    assert!(s.run(&()).is_ok());
+}
+
+#[test]
+fn node_params() {
+    fn record(ctx: &TaskCtx<'_, Mutex<Vec<String>>>) -> Result<(), TaskError> {
+        let value = format!("{}:{}:{}", ctx.name(), ctx.param("table").unwrap_or("-"), ctx.param("batch").unwrap_or("-"));
+        ctx.lock().unwrap().push(value);
+        Ok(())
+    }
+    let mut g = Graph::new();
+    g.add_node_with_ctx("load", record).unwrap();
+    g.add_node_with_ctx("store", record).unwrap();
+    g.add_edge("load", "store").unwrap();
+    g.set_param("load", "table", "events").unwrap();
+    g.set_param("load", "batch", "100").unwrap();
+    assert!(g.set_param("missing", "batch", "1").is_err_and(|e| matches!(e, NodeNotFound{..})));
+    let s = Scheduler::new(g.froze().unwrap());
+    let params = |node: &str, pairs: &[(&str, &str)]| -> (String, HashMap<String, String>) {
+        return (node.to_string(), pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect());
+    };
+
+    let ctx = Mutex::new(vec![]);
+    s.run_with(&ctx, RunOptions::default()).unwrap();
+    s.run_with(&ctx, RunOptions::default().node_params(HashMap::from([
+        params("load", &[("batch", "500")]),
+        params("store", &[("table", "events_staging")]),
+    ]))).unwrap();
+    assert_eq!(*ctx.lock().unwrap(), [
+        "load:events:100", "store:-:-",
+        "load:events:500", "store:events_staging:-",
+    ]);
+
+    let options = RunOptions::default().node_params(HashMap::from([params("laod", &[("batch", "1")])]));
+    assert!(s.run_with(&ctx, options).is_err_and(|e| if let NodeNotFound{name} = e { name == "laod" } else { false }));
+    assert_eq!(ctx.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn override_task() {
+    let expensive_ran = Arc::new(AtomicBool::new(false));
+    let mut g = Graph::new();
+    let push = |name: &'static str| move |ctx: &Mutex<Vec<&str>>| -> Result<(), TaskError> {
+        ctx.lock().unwrap().push(name);
+        return Ok(());
+    };
+    g.add_node("fetch", push("fetch")).unwrap();
+    let flag = expensive_ran.clone();
+    g.add_node("expensive", move |_: &Mutex<Vec<&str>>| -> Result<(), TaskError> {
+        flag.store(true, Ordering::SeqCst);
+        return Ok(());
+    }).unwrap();
+    g.add_node("store", push("store")).unwrap();
+    g.add_edge("fetch", "expensive").unwrap();
+    g.add_edge("expensive", "store").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+
+    let ctx = Mutex::new(vec![]);
+    s.run_with(&ctx, RunOptions::default().override_task("expensive", push("stub"))).unwrap();
+    assert_eq!(*ctx.lock().unwrap(), ["fetch", "stub", "store"]);
+    assert!(!expensive_ran.load(Ordering::SeqCst));
+
+    // only for that run
+    ctx.lock().unwrap().clear();
+    s.run(&ctx).unwrap();
+    assert_eq!(*ctx.lock().unwrap(), ["fetch", "store"]);
+    assert!(expensive_ran.load(Ordering::SeqCst));
+
+    let options = RunOptions::default().override_task("expnsive", push("stub"));
+    assert!(s.run_with(&ctx, options).is_err_and(|e| if let NodeNotFound{name} = e { name == "expnsive" } else { false }));
+    assert_eq!(ctx.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn queue_saturated() {
+    let mut g: Graph<AtomicU32> = Graph::new();
+    let count = |ctx: &AtomicU32| {
+        ctx.fetch_add(1, Ordering::Relaxed);
+        Ok(())
+    };
+    g.add_node("root", count).unwrap();
+    g.add_node("join", count).unwrap();
+    for i in 0..500 {
+        let (first, second) = (format!("first{}", i), format!("second{}", i));
+        g.add_node(&first, count).unwrap();
+        g.add_node(&second, count).unwrap();
+        g.add_edge("root", &first).unwrap();
+        g.add_edge(&first, "join").unwrap();
+        g.add_edge("join", &second).unwrap();
+    }
+    let depths = Arc::new(Mutex::new(vec![]));
+    let recorded = depths.clone();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .max_concurrency(4)
+        .max_ready_queue(100)
+        .observer(move |event: &Event<'_>| {
+            if let Event::QueueSaturated{depth, ..} = event {
+                recorded.lock().unwrap().push(*depth);
+            }
+        })
+        .build();
+    let ctx = AtomicU32::new(0);
+    s.run(&ctx).unwrap();
+    assert_eq!(ctx.load(Ordering::Relaxed), 1002);
+    // once per layer, each all ready at the same time
+    assert_eq!(*depths.lock().unwrap(), [500, 500]);
+    assert_eq!(s.metrics().ready_queue_depth(), 0);
+}
+
+#[test]
+fn gates() {
+    let started: Arc<Mutex<Vec<(&str, Instant)>>> = Arc::new(Mutex::new(vec![]));
+    let record = |name: &'static str| {
+        let started = started.clone();
+        return move |_: &()| -> Result<(), TaskError> {
+            started.lock().unwrap().push((name, Instant::now()));
+            return Ok(());
+        };
+    };
+    let mut g = Graph::new();
+    g.add_node("A", record("A")).unwrap();
+    g.add_node("gated", record("gated")).unwrap();
+    g.add_node("B", record("B")).unwrap();
+    g.add_node("C", record("C")).unwrap();
+    g.add_edge("A", "gated").unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    let gate = Arc::new(AtomicBool::new(false));
+    g.set_gate("gated", gate.clone()).unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .gate_poll_interval(Duration::from_millis(5))
+        .build();
+
+    let t0 = Instant::now();
+    thread::scope(|scope| {
+        scope.spawn(|| {
+            thread::sleep(Duration::from_millis(100));
+            gate.store(true, Ordering::Release);
+        });
+        s.run(&()).unwrap();
+    });
+    let started = started.lock().unwrap();
+    let after = |name: &str| started.iter().find(|(n, _)| *n == name).unwrap().1 - t0;
+    assert!(after("C") < Duration::from_millis(50));
+    assert!(after("gated") >= Duration::from_millis(100));
+    assert_eq!(started.last().unwrap().0, "gated");
+}
+
+#[test]
+fn gate_timeout() {
+    let mut g = Graph::new();
+    g.add_node("A", |_: &()| Ok(())).unwrap();
+    g.add_node("gated", |_: &()| Ok(())).unwrap();
+    g.set_gate("gated", Arc::new(AtomicBool::new(false))).unwrap();
+    let clock = Arc::new(ManualClock::new());
+    let s = SchedulerBuilder::new(g.froze().unwrap())
+        .clock(clock.clone())
+        .gate_timeout(Duration::from_secs(1))
+        .build();
+    let err = s.run(&()).unwrap_err();
+    assert_eq!(err.node_name(), Some("gated"));
+    assert!(matches!(err, RuntimeTimedOut{limit, elapsed, ..}
+        if limit == Duration::from_secs(1) && elapsed >= limit));
+    assert!(clock.elapsed() >= Duration::from_secs(1));
+}
+
+#[test]
+fn fuse_linear_chains() {
+    let chain = |fail_at: Option<usize>| {
+        let mut g = Graph::new();
+        for i in 0..100 {
+            g.add_node(&i.to_string(), move |_: &()| {
+                if Some(i) == fail_at {
+                    return Err(Box::new(std::fmt::Error));
+                }
+                return Ok(());
+            }).unwrap();
+            if i > 0 {
+                g.add_edge(&(i - 1).to_string(), &i.to_string()).unwrap();
+            }
+        }
+        return g;
+    };
+    let fused = FrozeOptions::default().fuse_linear_chains(true);
+    let best_of = |s: &Scheduler<()>| {
+        return (0..5).map(|_| {
+            let start = Instant::now();
+            s.run(&()).unwrap();
+            return start.elapsed();
+        }).min().unwrap();
+    };
+    let unfused_time = best_of(&Scheduler::new(chain(None).froze().unwrap()));
+    let fused_time = best_of(&Scheduler::new(chain(None).froze_with(fused).unwrap()));
+    assert!(fused_time < unfused_time, "{:?} fused, {:?} unfused", fused_time, unfused_time);
+
+    let s = Scheduler::new(chain(Some(57)).froze_with(fused).unwrap());
+    let (result, report) = s.run_with_report(&());
+    assert!(result.is_err_and(|e| matches!(&e, RuntimeFailed{..}) && e.node_name() == Some("57")));
+    for (i, node) in report.nodes().iter().enumerate() {
+        match i {
+            0..57 => assert!(matches!(node.status, NodeStatus::Succeeded{..}), "{}", node.name),
+            57 => assert!(matches!(node.status, NodeStatus::Failed)),
+            _ => assert!(matches!(node.status, NodeStatus::NotRun), "{}", node.name),
+        }
+    }
+    assert!(report.nodes()[56].started_at.is_some() && report.nodes()[56].finished_at.is_some());
+}
+
+#[test]
+fn fused_weighted_node() {
+    // B would be fused to A, and run next to X on A's worker
+    let mut g = Graph::new();
+    g.add_node("X", gauge_task(50)).unwrap();
+    g.add_node("A", |_: &GaugeContext| Ok(())).unwrap();
+    g.add_node("B", gauge_task(20)).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.set_weight("X", 60).unwrap();
+    g.set_weight("B", 60).unwrap();
+    let s = SchedulerBuilder::new(g.froze_with(FrozeOptions::default().fuse_linear_chains(true)).unwrap())
+        .max_total_weight(100)
+        .build();
+    let ctx = GaugeContext::default();
+    s.run(&ctx).unwrap();
+    assert_eq!(ctx.max_running.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn fused_chain_concurrency() {
+    // the chain counts once, X starts right away next to it
+    let events: Arc<Mutex<Vec<String>>> = Arc::new(Mutex::new(vec![]));
+    let mut g = Graph::new();
+    for name in ["A", "B", "C", "X"] {
+        let events = events.clone();
+        g.add_node(name, move |_: &()| -> Result<(), TaskError> {
+            events.lock().unwrap().push(format!("{} started", name));
+            thread::sleep(Duration::from_millis(20));
+            events.lock().unwrap().push(format!("{} finished", name));
+            return Ok(());
+        }).unwrap();
+    }
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    let s = SchedulerBuilder::new(g.froze_with(FrozeOptions::default().fuse_linear_chains(true)).unwrap())
+        .dispatch_strategy(DispatchStrategy::InsertionOrder)
+        .max_concurrency(2)
+        .build();
+    s.run(&()).unwrap();
+    let events = events.lock().unwrap();
+    let position = |event: &str| events.iter().position(|e| e == event).unwrap();
+    assert!(position("X started") < position("A finished"), "{:?}", events);
+}
+
+#[test]
+fn fused_past_deadline() {
+    fn record(ctx: &TaskCtx<'_, Mutex<Vec<String>>>) -> Result<(), TaskError> {
+        if ctx.name() == "a" {
+            ctx.clock().sleep(Duration::from_secs(50));
+        }
+        ctx.lock().unwrap().push(ctx.name().to_string());
+        Ok(())
+    }
+    let mut g = Graph::new();
+    for name in ["a", "b", "c"] {
+        g.add_node_with_ctx(name, record).unwrap();
+    }
+    g.add_edge("a", "b").unwrap();
+    g.add_edge("b", "c").unwrap();
+    let s = SchedulerBuilder::new(g.froze_with(FrozeOptions::default().fuse_linear_chains(true)).unwrap())
+        .clock(Arc::new(ManualClock::new()))
+        .deadline(Duration::from_secs(20))
+        .build();
+    let ctx = Mutex::new(vec![]);
+    assert!(s.run(&ctx).is_err_and(|e| {
+        if let DeadlineExceeded{completed, not_run, ..} = e { completed == ["a"] && not_run == ["b", "c"] } else { false }
+    }));
+    assert_eq!(ctx.into_inner().unwrap(), ["a"]);
+}
+
+#[test]
+fn fused_hopeless_node() {
+    fn record(ctx: &TaskCtx<'_, Mutex<Vec<String>>>) -> Result<(), TaskError> {
+        if ctx.name() == "a" {
+            ctx.clock().sleep(Duration::from_millis(10));
+        }
+        ctx.lock().unwrap().push(ctx.name().to_string());
+        Ok(())
+    }
+    // b fits the deadline when the run starts, not once a is done
+    let mut g = Graph::new();
+    g.add_node_with_ctx("a", record).unwrap();
+    g.add_node_with_ctx("b", record).unwrap();
+    g.add_edge("a", "b").unwrap();
+    g.set_cost("b", Duration::from_millis(95)).unwrap();
+    let s = SchedulerBuilder::new(g.froze_with(FrozeOptions::default().fuse_linear_chains(true)).unwrap())
+        .clock(Arc::new(ManualClock::new()))
+        .deadline(Duration::from_millis(100))
+        .skip_hopeless_nodes(true)
+        .build();
+    let ctx = Mutex::new(vec![]);
+    let (result, report) = s.run_with_report(&ctx);
+    assert!(matches!(result, Err(DeadlineExceeded{..})));
+    assert_eq!(ctx.into_inner().unwrap(), ["a"]);
+    assert!(matches!(report.node("b").unwrap().status, NodeStatus::SkippedForDeadline{..}));
+}
+
+#[test]
+fn failures() {
+    let mut g = Graph::new();
+    for (name, sleep_ms) in [("a", 60), ("b", 0), ("c", 30)] {
+        g.add_node(name, move |_: &()| {
+            thread::sleep(Duration::from_millis(sleep_ms));
+            return Err(Box::new(std::fmt::Error));
+        }).unwrap();
+    }
+    g.add_node("d", |_: &()| Ok(())).unwrap();
+    let s = SchedulerBuilder::new(g.froze().unwrap()).failure_policy(FailurePolicy::ContinueOnError).build();
+    let err = s.run(&()).err().unwrap();
+
+    let failures = err.failures();
+    let nodes: Vec<&str> = failures.iter().map(|(node, _)| node).collect();
+    assert_eq!(nodes, vec!["b", "c", "a"]);
+    assert!(failures.first().is_some_and(|(node, err)| node == "b" && err.is::<std::fmt::Error>()));
+    assert!(failures.contains_node("a") && !failures.contains_node("d"));
+    let mut n = 0;
+    for (_, err) in err.failures() {
+        assert!(err.downcast_ref::<std::fmt::Error>().is_some());
+        n += 1;
+    }
+    assert_eq!(n, 3);
+
+    let message = err.to_string();
+    assert!(message.starts_with("3 nodes failed:\n  - "), "{}", message);
+    assert_eq!(message.lines().count(), 4);
+    assert!(!message.contains("more"));
+    let listed = failures.max_listed(1).to_string();
+    assert_eq!(listed.lines().filter(|line| line.starts_with("  - ")).count(), 1);
+    assert!(listed.ends_with("\n  …and 2 more"), "{}", listed);
+
+    let single = Scheduler::new({
+        let mut g = Graph::new();
+        g.add_node("x", |_: &()| Err(Box::new(std::fmt::Error))).unwrap();
+        g.froze().unwrap()
+    }).run(&()).err().unwrap();
+    assert_eq!(single.failures().len(), 1);
+    assert!(single.failures().first().is_some_and(|(node, _)| node == "x"));
+}
+
+#[test]
+fn pinned_to_caller() {
+    let threads = Arc::new(Mutex::new(HashMap::new()));
+    let mut g = Graph::new();
+    for name in ["pinned", "unpinned", "also_pinned"] {
+        let threads = threads.clone();
+        g.add_node(name, move |_: &()| {
+            threads.lock().unwrap().insert(name, thread::current().id());
+            return Ok(());
+        }).unwrap();
+    }
+    g.add_edge("unpinned", "also_pinned").unwrap();
+    g.set_pinned_to_caller("pinned", true).unwrap();
+    g.set_pinned_to_caller("also_pinned", true).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    s.run(&()).unwrap();
+
+    let threads = threads.lock().unwrap();
+    assert_eq!(threads["pinned"], thread::current().id());
+    assert_eq!(threads["also_pinned"], thread::current().id());
+    assert_ne!(threads["unpinned"], thread::current().id());
+}
+
+#[test]
+fn panic_policy() {
+    let graph = |policy: PanicPolicy| {
+        let mut g = Graph::new();
+        g.add_node("critical", |_: &()| -> Result<(), TaskError> { panic!("corrupted") }).unwrap();
+        g.add_node("other", |_: &()| Ok(())).unwrap();
+        g.set_panic_policy("critical", policy).unwrap();
+        return g.froze().unwrap();
+    };
+    let s = Scheduler::new(graph(PanicPolicy::Capture));
+    assert!(s.run(&()).is_err_and(|e| matches!(e, RuntimePanicked{..})));
+
+    let s = Scheduler::new(graph(PanicPolicy::Abort));
+    let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| s.run(&()))).err().unwrap();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"corrupted"));
+}
+
+#[test]
+fn panic_policy_waits_for_siblings() {
+    let n_running = Arc::new(AtomicU32::new(0));
+    let mut g = Graph::new();
+    g.add_node("critical", |_: &()| -> Result<(), TaskError> {
+        thread::sleep(Duration::from_millis(20));
+        panic!("corrupted");
+    }).unwrap();
+    for name in ["slow1", "slow2"] {
+        let n_running = n_running.clone();
+        g.add_node(name, move |_: &()| -> Result<(), TaskError> {
+            n_running.fetch_add(1, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(200));
+            n_running.fetch_sub(1, Ordering::SeqCst);
+            return Ok(());
+        }).unwrap();
+    }
+    g.set_panic_policy("critical", PanicPolicy::Abort).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+
+    let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| s.run_arc(Arc::new(())))).err().unwrap();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"corrupted"));
+    assert_eq!(n_running.load(Ordering::SeqCst), 0);
+
+    let payload = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        let mut execution = s.start(Arc::new(()));
+        while let StepStatus::Running = execution.step(Duration::from_millis(5)) {}
+    })).err().unwrap();
+    assert_eq!(payload.downcast_ref::<&str>(), Some(&"corrupted"));
+    assert_eq!(n_running.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn run_with_scratch() {
+    struct RunState {
+        results: Mutex<HashMap<&'static str, u32>>,
+        dropped: Arc<AtomicU32>,
+    }
+    impl Drop for RunState {
+        fn drop(&mut self) {
+            self.dropped.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let mut g = Graph::new();
+    g.add_node_scratch("produce", |base: &u32, state: &RunState| {
+        let mut results = state.results.lock().unwrap();
+        assert!(results.is_empty());
+        results.insert("produce", *base + 1);
+        return Ok(());
+    }).unwrap();
+    g.add_node_scratch("consume", |_: &u32, state: &RunState| {
+        let mut results = state.results.lock().unwrap();
+        let produced = results["produce"];
+        results.insert("consume", produced * 2);
+        return Ok(());
+    }).unwrap();
+    g.add_edge("produce", "consume").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+
+    let dropped = Arc::new(AtomicU32::new(0));
+    for n in 1..=3 {
+        s.run_with_scratch(&41, || RunState{results: Mutex::new(HashMap::new()), dropped: dropped.clone()}).unwrap();
+        assert_eq!(dropped.load(Ordering::SeqCst), n);
+    }
+    assert!(s.run(&41).is_err_and(|e| matches!(e, RuntimeFailed{..})));
+    assert!(s.run_with_scratch(&41, || 0u8).is_err_and(|e| matches!(e, RuntimeFailed{..})));
+}
+
+#[test]
+fn run_many() {
+    struct BatchContext {
+        fail: bool,
+        n_run: AtomicU32,
+    }
+    let task = |fails: bool| move |ctx: &BatchContext| -> Result<(), TaskError> {
+        ctx.n_run.fetch_add(1, Ordering::Relaxed);
+        if fails && ctx.fail {
+            return Err(Box::new(FailedError{reason: String::from("C")}));
+        }
+        return Ok(());
+    };
+    let mut g = Graph::new();
+    for name in ["A", "B", "C", "D"] {
+        g.add_node(name, task(name == "C")).unwrap();
+    }
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("B", "C").unwrap();
+    g.add_edge("C", "D").unwrap();
+    let frozen = g.froze().unwrap();
+
+    for s in [Scheduler::new(frozen.clone()), SchedulerBuilder::new(frozen).max_concurrency(2).build()] {
+        let ctxs: Vec<BatchContext> = (0..50).map(|i| BatchContext{fail: i % 5 == 3, n_run: AtomicU32::new(0)}).collect();
+        let results = s.run_many(&ctxs);
+        assert_eq!(results.len(), 50);
+        for (ctx, result) in ctxs.iter().zip(results.iter()) {
+            if ctx.fail {
+                assert!(result.as_ref().is_err_and(|e| e.node_name() == Some("C")));
+                assert_eq!(ctx.n_run.load(Ordering::Relaxed), 3);
+            } else {
+                assert!(result.is_ok());
+                assert_eq!(ctx.n_run.load(Ordering::Relaxed), 4);
+            }
+        }
+        assert_eq!(results.iter().filter(|result| result.is_err()).count(), 10);
+    }
+    assert!(Scheduler::new(Graph::<BatchContext>::new().froze().unwrap()).run_many(&[]).is_empty());
+}
+
+#[test]
+fn step() {
+    let mut g = Graph::new();
+    g.add_node("A", sleep_task(100)).unwrap();
+    g.add_node("B", sleep_task(30)).unwrap();
+    g.add_node("C", sleep_task(10)).unwrap();
+    g.add_node("D", sleep_task(20)).unwrap();
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("A", "C").unwrap();
+    g.add_edge("B", "D").unwrap();
+    g.add_edge("C", "D").unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = Arc::new(SleepContext::new());
+
+    let mut exec = s.start(ctx.clone());
+    // back long before A is done
+    assert!(matches!(exec.step(Duration::from_millis(2)), StepStatus::Running));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 0);
+    let mut n_step = 1;
+    let result = loop {
+        let status = exec.step(Duration::from_millis(2));
+        n_step += 1;
+        if let StepStatus::Finished(result) = status {
+            break result;
+        }
+    };
+    result.unwrap();
+    assert!(exec.is_finished());
+    assert!(n_step > 10, "{}", n_step);
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 4);
+
+    // dropped halfway, the node running is left to finish
+    let ctx = Arc::new(SleepContext::new());
+    let mut exec = s.start(ctx.clone());
+    assert!(matches!(exec.step(Duration::ZERO), StepStatus::Running));
+    drop(exec);
+    thread::sleep(Duration::from_millis(150));
+    assert_eq!(ctx.n_run.load(Ordering::Relaxed), 1);
+}
@@ -0,0 +1,57 @@
+#![allow(clippy::needless_return)]
+
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use dag_engine::{Error, ScopedGraph};
+
+#[test]
+fn borrowed_tasks() {
+    let mut local_vec = vec![1, 2, 3, 4];
+    let sums = Mutex::new(vec![]);
+    let counter = AtomicU32::new(0);
+
+    let mut g = ScopedGraph::new();
+    g.add_node("first_half", |_: &()| {
+        sums.lock().unwrap().push(local_vec[..2].iter().sum::<i32>());
+        Ok(())
+    }).unwrap();
+    g.add_node("second_half", |_: &()| {
+        sums.lock().unwrap().push(local_vec[2..].iter().sum::<i32>());
+        Ok(())
+    }).unwrap();
+    g.add_node("count", |_: &()| {
+        assert_eq!(sums.lock().unwrap().len() % 2, 0);
+        counter.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }).unwrap();
+    g.add_edge("first_half", "count").unwrap();
+    g.add_edge("second_half", "count").unwrap();
+    assert!(g.add_edge("count", "missing").is_err_and(|e| if let Error::NodeNotFound{name} = e { name == "missing" } else { false }));
+
+    g.run(&()).unwrap();
+    g.run_sequential(&()).unwrap();
+    drop(g);
+
+    let mut sums = sums.into_inner().unwrap();
+    sums.sort();
+    assert_eq!(sums, vec![3, 3, 7, 7]);
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    local_vec.push(5);
+    assert_eq!(local_vec.len(), 5);
+}
+
+#[test]
+fn cyclic() {
+    let names = [String::from("a"), String::from("b")];
+    let mut g: ScopedGraph<Mutex<Vec<&str>>> = ScopedGraph::new();
+    for name in names.iter() {
+        g.add_node(name, move |ctx: &Mutex<Vec<&str>>| {
+            ctx.lock().unwrap().push(name);
+            Ok(())
+        }).unwrap();
+    }
+    g.add_edge("a", "b").unwrap();
+    g.add_edge("b", "a").unwrap();
+    assert!(g.run(&Mutex::new(vec![])).is_err_and(|e| if let Error::CyclicGraphFound{ring, ..} = e { ring.contains("a") } else { false }));
+}
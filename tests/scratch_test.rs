@@ -0,0 +1,102 @@
+#![allow(clippy::needless_return)]
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use dag_engine::{TaskError, TaskCtx, Graph, Scheduler, SchedulerBuilder, RunOptions, FailurePolicy};
+
+type Dirs = Mutex<Vec<PathBuf>>;
+
+fn base_dir(name: &str) -> PathBuf {
+    let base = std::env::temp_dir().join(format!("dag_engine_{}_{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&base);
+    return base;
+}
+
+fn use_scratch(ctx: &TaskCtx<'_, Dirs>) -> PathBuf {
+    let dir = ctx.scratch_dir();
+    assert!(dir.is_dir());
+    fs::write(dir.join("out.txt"), ctx.name()).unwrap();
+    ctx.lock().unwrap().push(dir.to_path_buf());
+    return dir.to_path_buf();
+}
+
+fn fine(ctx: &TaskCtx<'_, Dirs>) -> Result<(), TaskError> {
+    use_scratch(ctx);
+    Ok(())
+}
+
+fn broken(ctx: &TaskCtx<'_, Dirs>) -> Result<(), TaskError> {
+    use_scratch(ctx);
+    Err(Box::new(std::fmt::Error))
+}
+
+fn graph() -> Graph<Dirs> {
+    let mut g = Graph::new();
+    g.add_node_with_ctx("fine", fine).unwrap();
+    g.add_node_with_ctx("ns/rescued", broken).unwrap();
+    g.set_fallback("ns/rescued", |ctx: &Dirs| {
+        ctx.lock().unwrap().push(PathBuf::from("fallback"));
+        Ok(())
+    }).unwrap();
+    g.add_node_with_ctx("broken", broken).unwrap();
+    g.add_node("unused", |_: &Dirs| Ok(())).unwrap();
+    g.add_edge("fine", "broken").unwrap();
+    return g;
+}
+
+#[test]
+fn removed_after_run() {
+    let base = base_dir("removed");
+    let s = SchedulerBuilder::new(graph().froze().unwrap())
+        .scratch_base(&base)
+        .failure_policy(FailurePolicy::ContinueOnError)
+        .build();
+    let dirs = Mutex::new(vec![]);
+    assert!(s.run_with(&dirs, RunOptions::default().run_id("first")).is_err());
+
+    let mut dirs = dirs.into_inner().unwrap();
+    dirs.sort();
+    assert_eq!(dirs, [
+        base.join("first/broken/attempt_1"),
+        base.join("first/fine/attempt_1"),
+        base.join("first/ns_rescued-1/attempt_1"),
+        PathBuf::from("fallback"),
+    ]);
+    // nothing is left, the run directory included
+    assert!(base.is_dir());
+    assert!(fs::read_dir(&base).unwrap().next().is_none());
+    fs::remove_dir(&base).unwrap();
+}
+
+#[test]
+fn kept_on_failure() {
+    let base = base_dir("kept");
+    let s = SchedulerBuilder::new(graph().froze().unwrap())
+        .scratch_base(&base)
+        .keep_scratch_on_failure(true)
+        .failure_policy(FailurePolicy::ContinueOnError)
+        .build();
+    let dirs = Mutex::new(vec![]);
+    assert!(s.run_with(&dirs, RunOptions::default().run_id("r/1")).is_err());
+
+    let run_dir = base.join("r_1");
+    let kept = |node: &str| fs::read_to_string(run_dir.join(node).join("attempt_1/out.txt")).ok();
+    assert_eq!(kept("broken").as_deref(), Some("broken"));
+    // rescued by its fallback, so not a failure
+    assert!(!run_dir.join("ns_rescued-1").exists());
+    assert!(!run_dir.join("fine").exists());
+    fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn not_enabled() {
+    let mut g: Graph<()> = Graph::new();
+    g.add_node_with_ctx("a", |ctx: &TaskCtx<'_, ()>| {
+        let _: &Path = ctx.scratch_dir();
+        Ok(())
+    }).unwrap();
+    let result = Scheduler::new(g.froze().unwrap()).run(&());
+    assert!(result.is_err_and(|e| e.to_string().contains("scratch directories are not enabled")));
+}
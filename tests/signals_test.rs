@@ -0,0 +1,40 @@
+#![cfg(all(feature = "signals", unix))]
+#![allow(clippy::needless_return)]
+
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook::low_level::raise;
+
+use dag_engine::{Error::*, TaskError, Graph, Scheduler};
+
+fn interrupting_task(signal: i32) -> impl Fn(&()) -> Result<(), TaskError> {
+    return move |_: &()| -> Result<(), TaskError> {
+        raise(signal).unwrap();
+        return Ok(());
+    };
+}
+
+fn noop_task(_: &()) -> Result<(), TaskError> {
+    return Ok(());
+}
+
+#[test]
+fn drain_on_signal() {
+    for signal in [SIGINT, SIGTERM] {
+        let mut g = Graph::new();
+        g.add_node("A", interrupting_task(signal)).unwrap();
+        g.add_node("B", noop_task).unwrap();
+        g.add_node("C", noop_task).unwrap();
+        g.add_edge("A", "B").unwrap();
+        g.add_edge("B", "C").unwrap();
+        let s = Scheduler::new(g.froze().unwrap());
+        assert!(s.run_with_signal_drain(&()).is_err_and(
+            |e| if let Drained{completed, not_run} = e { completed == ["A"] && not_run == ["B", "C"] } else { false }
+        ));
+    }
+
+    let mut g = Graph::new();
+    g.add_node("A", noop_task).unwrap();
+    g.add_node("B", noop_task).unwrap();
+    g.add_edge("A", "B").unwrap();
+    Scheduler::new(g.froze().unwrap()).run_with_signal_drain(&()).unwrap();
+}
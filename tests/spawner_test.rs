@@ -0,0 +1,129 @@
+#![allow(clippy::needless_return)]
+
+use std::sync::Mutex;
+use std::sync::atomic::{Ordering, AtomicU32};
+
+use dag_engine::{Error::*, TaskError, Task, Graph, Scheduler};
+use dag_engine::{Job, Spawner, SpawnScope, SpawnError, ThreadSpawner, ThreadPoolSpawner, InlineSpawner};
+
+struct OrderContext {
+    order: Mutex<Vec<&'static str>>,
+}
+
+impl OrderContext {
+    fn new() -> OrderContext {
+        OrderContext{
+            order: Mutex::new(vec![]),
+        }
+    }
+}
+
+fn order_task(name: &'static str, fail: bool) -> Task<OrderContext> {
+    Box::new(move |ctx: &OrderContext| -> Result<(), TaskError> {
+        ctx.order.lock().unwrap().push(name);
+        if fail {
+            return Err(Box::new(SpawnError::new(name)));
+        }
+        Ok(())
+    })
+}
+
+fn diamond(fail: &str) -> Graph<OrderContext> {
+    let mut g = Graph::new();
+    for name in ["A", "B", "C", "D"] {
+        g.add_node(name, order_task(name, name == fail)).unwrap();
+    }
+    g.add_edge("A", "B").unwrap();
+    g.add_edge("A", "C").unwrap();
+    g.add_edge("B", "D").unwrap();
+    g.add_edge("C", "D").unwrap();
+    return g;
+}
+
+#[test]
+fn inline_spawner() {
+    let s = Scheduler::with_spawner(diamond("").froze().unwrap(), InlineSpawner);
+    for _ in 0..8 {
+        let ctx = OrderContext::new();
+        s.run(&ctx).unwrap();
+        assert_eq!(ctx.order.into_inner().unwrap(), ["A", "B", "C", "D"]);
+    }
+
+    let s = Scheduler::with_spawner(diamond("B").froze().unwrap(), InlineSpawner);
+    let ctx = OrderContext::new();
+    assert!(s.run(&ctx).is_err_and(
+        |e| if let RuntimeFailed{node, ..} = e { node == "B" } else { false }
+    ));
+    // C was dispatched together with B, the inline spawner runs it at once
+    assert_eq!(ctx.order.into_inner().unwrap(), ["A", "B", "C"]);
+}
+
+#[test]
+fn thread_spawner() {
+    let spawner = ThreadSpawner{name_threads: false, stack_size: Some(1 << 20)};
+    let s = Scheduler::with_spawner(diamond("").froze().unwrap(), spawner);
+    let ctx = OrderContext::new();
+    s.run(&ctx).unwrap();
+    let order = ctx.order.into_inner().unwrap();
+    assert_eq!(order.len(), 4);
+    assert_eq!(order[0], "A");
+    assert_eq!(order[3], "D");
+}
+
+#[test]
+fn pool_job_panicked() {
+    let spawner = ThreadPoolSpawner::new(2);
+    let n_done = AtomicU32::new(0);
+    let err = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        spawner.scope(&mut |scope| {
+            scope.spawn("boom", Box::new(|| panic!("boom"))).unwrap();
+            scope.spawn("done", Box::new(|| {
+                n_done.fetch_add(1, Ordering::Relaxed);
+            })).unwrap();
+        });
+    })).unwrap_err();
+    assert_eq!(err.downcast_ref::<&str>(), Some(&"boom"));
+    assert_eq!(n_done.load(Ordering::Relaxed), 1);
+
+    // the pool is still usable afterwards
+    spawner.scope(&mut |scope| {
+        scope.spawn("done", Box::new(|| {
+            n_done.fetch_add(1, Ordering::Relaxed);
+        })).unwrap();
+    });
+    assert_eq!(n_done.load(Ordering::Relaxed), 2);
+}
+
+// Spawns inline, but refuses to spawn one given node.
+struct FailingSpawner {
+    refuse: &'static str,
+    n_spawn: AtomicU32,
+}
+
+impl Spawner for FailingSpawner {
+    fn scope<'env>(&self, body: &mut dyn FnMut(&dyn SpawnScope<'env>)) {
+        body(self);
+    }
+}
+
+impl<'env> SpawnScope<'env> for FailingSpawner {
+    fn spawn(&self, name: &str, job: Job<'env>) -> Result<(), SpawnError> {
+        if name == self.refuse {
+            return Err(SpawnError::new("no more workers"));
+        }
+        self.n_spawn.fetch_add(1, Ordering::Relaxed);
+        job();
+        return Ok(());
+    }
+}
+
+#[test]
+fn spawn_failed() {
+    let spawner = FailingSpawner{refuse: "C", n_spawn: AtomicU32::new(0)};
+    let s = Scheduler::with_spawner(diamond("").froze().unwrap(), spawner);
+    let ctx = OrderContext::new();
+    let err = s.run(&ctx).unwrap_err();
+    assert_eq!(err.to_string(), "spawn C failed: no more workers");
+    assert!(matches!(err, SpawnFailed{node, ..} if node == "C"));
+    assert_eq!(ctx.order.into_inner().unwrap(), ["A", "B"]);
+}
@@ -0,0 +1,125 @@
+#![allow(clippy::needless_return)]
+
+use std::sync::Arc;
+use std::sync::atomic::{Ordering, AtomicU32};
+use std::time::Duration;
+
+use dag_engine::{Error::*, TaskError, Task, Graph, Scheduler, Clock, ManualClock, RunId};
+use dag_engine::task::{with_retry, with_backoff, with_timeout, with_timeout_on, map_err, with_name};
+
+// takes 30ms on `clock` and fails the first `n_fail` attempts
+fn flaky_task(n_fail: u32, clock: &Arc<ManualClock>) -> Task<AtomicU32> {
+    let clock = clock.clone();
+    Box::new(move |ctx: &AtomicU32| -> Result<(), TaskError> {
+        clock.sleep(Duration::from_millis(30));
+        if ctx.fetch_add(1, Ordering::Relaxed) < n_fail {
+            return Err(Box::new(std::fmt::Error));
+        }
+        return Ok(());
+    })
+}
+
+fn run_task(task: Task<AtomicU32>) -> (Result<(), dag_engine::Error>, u32) {
+    let mut g = Graph::new();
+    g.add_node("A", task).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = AtomicU32::new(0);
+    let result = s.run(&ctx);
+    return (result, ctx.into_inner());
+}
+
+#[test]
+fn retry() {
+    let clock = Arc::new(ManualClock::new());
+    let (result, n_run) = run_task(with_retry(flaky_task(2, &clock), 3));
+    result.unwrap();
+    assert_eq!(n_run, 3);
+
+    let (result, n_run) = run_task(with_retry(flaky_task(10, &clock), 3));
+    assert!(result.is_err_and(|e| matches!(e, RuntimeFailed{..})));
+    assert_eq!(n_run, 4);
+    assert_eq!(clock.elapsed(), Duration::from_millis(7 * 30));
+}
+
+#[test]
+fn backoff() {
+    let clock = Arc::new(ManualClock::new());
+    let (result, n_run) = run_task(with_backoff(flaky_task(2, &clock), 3, Duration::from_secs(1), clock.clone()));
+    result.unwrap();
+    assert_eq!(n_run, 3);
+    // 3 attempts, waiting 1s then 2s in between
+    assert_eq!(clock.elapsed(), Duration::from_millis(3 * 30 + 3000));
+
+    let clock = Arc::new(ManualClock::new());
+    let (result, n_run) = run_task(with_backoff(flaky_task(10, &clock), 3, Duration::from_secs(1), clock.clone()));
+    assert!(result.is_err_and(|e| matches!(e, RuntimeFailed{..})));
+    assert_eq!(n_run, 4);
+    assert_eq!(clock.elapsed(), Duration::from_millis(4 * 30 + 7000));
+}
+
+#[test]
+fn timeout_per_attempt() {
+    // every attempt is well within the limit, all of them together are not
+    let clock = Arc::new(ManualClock::new());
+    let limit = Duration::from_millis(60);
+    let (result, n_run) = run_task(with_retry(with_timeout_on(flaky_task(2, &clock), limit, clock.clone()), 3));
+    result.unwrap();
+    assert_eq!(n_run, 3);
+
+    let (result, n_run) = run_task(with_timeout_on(with_retry(flaky_task(2, &clock), 3), limit, clock.clone()));
+    let err = result.unwrap_err();
+    assert!(err.is_runtime() && err.task_error().is_none());
+    assert_eq!(err.node_name(), Some("A"));
+    assert!(matches!(err, RuntimeTimedOut{limit: l, elapsed, ..}
+        if l == limit && elapsed == Duration::from_millis(90)));
+    assert_eq!(n_run, 3);
+
+    let err = RuntimeTimedOut{
+        node: String::from("fetch"),
+        index: 0,
+        limit: Duration::from_secs(30),
+        elapsed: Duration::from_millis(31200),
+        in_flight: vec![],
+        run_id: RunId::new("1"),
+    };
+    assert_eq!(err.to_string(), "run fetch timed out: exceeded 30s (ran 31.2s)");
+}
+
+#[test]
+fn timeout_in_flight() {
+    let mut g = Graph::new();
+    let clock = Arc::new(ManualClock::new());
+    g.add_node("A", with_timeout_on(flaky_task(0, &clock), Duration::from_millis(10), clock.clone())).unwrap();
+    g.add_node("B", |_: &AtomicU32| -> Result<(), TaskError> {
+        std::thread::sleep(Duration::from_millis(100));
+        Ok(())
+    }).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let err = s.run(&AtomicU32::new(0)).unwrap_err();
+    assert_eq!(err.node_name(), Some("A"));
+    assert_eq!(err.node_index(), Some(0));
+    assert_eq!(err.in_flight(), ["B"]);
+    assert!(err.run_id().is_some());
+}
+
+#[test]
+fn system_clock() {
+    let task: Task<AtomicU32> = Box::new(|_: &AtomicU32| {
+        std::thread::sleep(Duration::from_millis(20));
+        Ok(())
+    });
+    let (result, _) = run_task(with_timeout(task, Duration::from_millis(10)));
+    assert!(result.is_err_and(|e| if let RuntimeTimedOut{limit, elapsed, ..} = e { limit == Duration::from_millis(10) && elapsed >= limit } else { false }));
+}
+
+#[test]
+fn error_mapping() {
+    let task = map_err(flaky_task(1, &Arc::new(ManualClock::new())), |err| -> TaskError {
+        let err: Box<dyn std::error::Error + Send + Sync> = format!("mapped {}", err).into();
+        return err;
+    });
+    let (result, _) = run_task(with_name(task, "fetch"));
+    assert!(result.is_err_and(
+        |e| if let RuntimeFailed{err, ..} = e { err.to_string() == "fetch: mapped an error occurred when formatting an argument" } else { false }
+    ));
+}
@@ -0,0 +1,71 @@
+#![cfg(feature = "testing")]
+#![allow(clippy::needless_return)]
+
+use std::sync::Mutex;
+
+use rand::{SeedableRng, Rng};
+
+use dag_engine::{TaskError, Graph, Scheduler, SchedulerBuilder};
+use dag_engine::testing::{random_dag, assert_valid_execution};
+
+fn recording_task(id: usize) -> impl Fn(&Mutex<Vec<String>>) -> Result<(), TaskError> {
+    return move |ctx: &Mutex<Vec<String>>| -> Result<(), TaskError> {
+        ctx.lock().unwrap().push(id.to_string());
+        return Ok(());
+    };
+}
+
+fn random_graph(rng: &mut rand::rngs::StdRng) -> Graph<Mutex<Vec<String>>> {
+    let n_node = rng.gen_range(0..40);
+    let edge_prob = rng.gen_range(0.0..0.5);
+    return random_dag(rng, n_node, edge_prob, recording_task);
+}
+
+#[test]
+fn random_sequential() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+    for _ in 0..300 {
+        let g = random_graph(&mut rng);
+        let n_node = g.node_count();
+        let s = Scheduler::new(g.clone().froze().unwrap());
+        let ctx = Mutex::new(vec![]);
+        s.run_sequential(&ctx).unwrap();
+        let order = ctx.into_inner().unwrap();
+        assert_eq!(order.len(), n_node);
+        assert_valid_execution(&order, &g);
+    }
+}
+
+#[test]
+fn random_parallel() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(2);
+    for i in 0..300 {
+        let g = random_graph(&mut rng);
+        let n_node = g.node_count();
+        let s = SchedulerBuilder::new(g.clone().froze().unwrap())
+            .max_concurrency(1 + i % 4)
+            .build();
+        let ctx = Mutex::new(vec![]);
+        s.run(&ctx).unwrap();
+        let order = ctx.into_inner().unwrap();
+        assert_eq!(order.len(), n_node);
+        assert_valid_execution(&order, &g);
+    }
+}
+
+#[test]
+fn invalid_executions() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+    let g = random_dag(&mut rng, 3, 1.0, recording_task);
+    assert_eq!(g.edge_count(), 3);
+    let order = |names: &[&str]| -> Vec<String> {
+        return names.iter().map(|name| name.to_string()).collect();
+    };
+    assert_valid_execution(&order(&["0", "1", "2"]), &g);
+    assert_valid_execution(&order(&["0", "1"]), &g);
+    for names in [&["0", "2", "1"][..], &["1"], &["0", "0"], &["0", "x"]] {
+        let names = order(names);
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| assert_valid_execution(&names, &g)));
+        assert!(result.is_err());
+    }
+}
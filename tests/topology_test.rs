@@ -0,0 +1,263 @@
+#![allow(clippy::needless_return)]
+
+use std::collections::{BTreeSet, HashMap};
+use std::time::Duration;
+
+use dag_engine::{Error::*, TaskError, Graph, TopologyDiff};
+use rand::{SeedableRng, Rng};
+
+fn dummy_task(_: &()) -> Result<(), TaskError> {
+    Ok(())
+}
+
+fn diamond(order: &[&str]) -> Graph<()> {
+    let mut g = Graph::new();
+    for name in order {
+        g.add_node(name, dummy_task).unwrap();
+    }
+    for (from, to) in [("A", "B"), ("A", "C"), ("B", "D"), ("C", "D")] {
+        g.add_edge(from, to).unwrap();
+    }
+    return g;
+}
+
+#[test]
+fn fingerprint() {
+    let g = diamond(&["A", "B", "C", "D"]);
+    let mut permuted = Graph::new();
+    for name in ["D", "B", "A", "C"] {
+        permuted.add_node(name, dummy_task).unwrap();
+    }
+    for (from, to) in [("C", "D"), ("B", "D"), ("A", "C"), ("A", "B")] {
+        permuted.add_edge(from, to).unwrap();
+    }
+    assert_eq!(g.fingerprint(), permuted.fingerprint());
+
+    let mut extra = diamond(&["A", "B", "C", "D"]);
+    extra.add_edge("A", "D").unwrap();
+    assert_ne!(g.fingerprint(), extra.fingerprint());
+
+    let mut inline = diamond(&["A", "B", "C", "D"]);
+    inline.set_inline("B", true).unwrap();
+    assert_ne!(g.fingerprint(), inline.fingerprint());
+
+    let mut cost = diamond(&["A", "B", "C", "D"]);
+    cost.set_cost("B", Duration::from_millis(5)).unwrap();
+    assert_ne!(g.fingerprint(), cost.fingerprint());
+    let mut other_cost = diamond(&["A", "B", "C", "D"]);
+    other_cost.set_cost("B", Duration::from_millis(6)).unwrap();
+    assert_ne!(cost.fingerprint(), other_cost.fingerprint());
+
+    let mut param = diamond(&["A", "B", "C", "D"]);
+    param.set_param("C", "retries", "3").unwrap();
+    assert_ne!(g.fingerprint(), param.fingerprint());
+    let mut other_param = diamond(&["A", "B", "C", "D"]);
+    other_param.set_param("C", "retries", "4").unwrap();
+    assert_ne!(param.fingerprint(), other_param.fingerprint());
+    // params are hashed by key, whatever order they were set in
+    param.set_param("C", "timeout", "1s").unwrap();
+    other_param.set_param("C", "timeout", "1s").unwrap();
+    other_param.set_param("C", "retries", "3").unwrap();
+    assert_eq!(param.fingerprint(), other_param.fingerprint());
+
+    // the same in every process
+    let fingerprint = g.fingerprint();
+    assert_eq!(g.froze().unwrap().fingerprint(), fingerprint);
+    assert_eq!(fingerprint, 10559769794887527202);
+}
+
+#[test]
+fn topology_diff() {
+    let g = diamond(&["A", "B", "C", "D"]);
+    let permuted = diamond(&["C", "A", "D", "B"]);
+    assert!(g.topology_eq(&permuted));
+    assert!(g.topology_diff(&permuted).is_empty());
+
+    let mut reversed = Graph::new();
+    for name in ["A", "B", "C", "D", "E"] {
+        reversed.add_node(name, dummy_task).unwrap();
+    }
+    for (from, to) in [("A", "B"), ("A", "C"), ("D", "B"), ("C", "D")] {
+        reversed.add_edge(from, to).unwrap();
+    }
+    assert!(!g.topology_eq(&reversed));
+    let edge = |from: &str, to: &str| (from.to_string(), to.to_string());
+    assert_eq!(g.topology_diff(&reversed), TopologyDiff{
+        missing_nodes: vec![],
+        extra_nodes: vec!["E".to_string()],
+        missing_edges: vec![edge("B", "D")],
+        extra_edges: vec![edge("D", "B")],
+    });
+    assert_eq!(reversed.topology_diff(&g).missing_nodes, ["E"]);
+}
+
+fn descendants(n_node: usize, edges: &[(usize, usize)]) -> Vec<BTreeSet<usize>> {
+    let mut descendants = vec![BTreeSet::new(); n_node];
+    // every edge goes from a lower to a higher index
+    for i in (0..n_node).rev() {
+        for (from, to) in edges.iter() {
+            if *from == i {
+                let below = descendants[*to].clone();
+                descendants[i].insert(*to);
+                descendants[i].extend(below);
+            }
+        }
+    }
+    return descendants;
+}
+
+fn edge_graph(n_node: usize, edges: &[(usize, usize)]) -> Graph<()> {
+    let mut g = Graph::new();
+    for i in 0..n_node {
+        g.add_node(&i.to_string(), dummy_task).unwrap();
+    }
+    for (from, to) in edges.iter() {
+        g.add_edge(&from.to_string(), &to.to_string()).unwrap();
+    }
+    return g;
+}
+
+#[test]
+fn transitive_reduction() {
+    let mut rng = rand::rngs::StdRng::seed_from_u64(42);
+    let n_node: usize = 96;
+    let mut edges = vec![];
+    for i in 1..n_node {
+        for j in 0..i {
+            let k: u32 = rng.gen();
+            if k.is_multiple_of(12) {
+                edges.push((j, i));
+            }
+        }
+    }
+    let mut g = edge_graph(n_node, &edges);
+
+    let redundant: Vec<(usize, usize)> = g.redundant_edges().iter()
+        .map(|(from, to)| (from.parse().unwrap(), to.parse().unwrap()))
+        .collect();
+    assert!(!redundant.is_empty());
+    assert_eq!(g.transitive_reduction(), redundant.len());
+    assert!(g.redundant_edges().is_empty());
+    assert_eq!(g.transitive_reduction(), 0);
+
+    let reduced: Vec<(usize, usize)> = edges.iter()
+        .filter(|edge| !redundant.contains(edge)).copied().collect();
+    assert!(g.topology_eq(&edge_graph(n_node, &reduced)));
+    assert_eq!(descendants(n_node, &edges), descendants(n_node, &reduced));
+    // nothing left is implied by the rest
+    for edge in reduced.iter() {
+        let others: Vec<(usize, usize)> = reduced.iter().filter(|other| *other != edge).copied().collect();
+        assert!(!descendants(n_node, &others)[edge.0].contains(&edge.1));
+    }
+    g.froze().unwrap();
+
+    let mut g = diamond(&["A", "B", "C", "D"]);
+    g.add_edge("A", "D").unwrap();
+    assert_eq!(g.redundant_edges(), [("A".to_string(), "D".to_string())]);
+}
+
+#[test]
+fn critical_path() {
+    let mut g = diamond(&["A", "B", "C", "D"]);
+    for (name, cost) in [("A", 10), ("B", 20), ("C", 30), ("D", 40)] {
+        g.set_cost(name, Duration::from_millis(cost)).unwrap();
+    }
+    assert!(g.set_cost("E", Duration::ZERO).is_err());
+    let frozen = g.froze().unwrap();
+    let (length, path) = frozen.critical_path();
+    assert_eq!(length, Duration::from_millis(80));
+    assert_eq!(path, ["A", "C", "D"]);
+
+    assert_eq!(frozen.estimate_makespan(1), Duration::from_millis(100));
+    assert_eq!(frozen.estimate_makespan(2), Duration::from_millis(80));
+    assert_eq!(frozen.estimate_makespan(0), Duration::from_millis(80));
+
+    // B and C share one worker: A, then B and C one after the other, then D
+    let mut g = diamond(&["A", "B", "C", "D"]);
+    g.add_node("E", dummy_task).unwrap();
+    g.set_default_cost(Duration::from_millis(5));
+    g.set_cost("A", Duration::from_millis(10)).unwrap();
+    let frozen = g.froze().unwrap();
+    assert_eq!(frozen.critical_path(), (Duration::from_millis(20), vec!["A".to_string(), "B".to_string(), "D".to_string()]));
+    assert_eq!(frozen.estimate_makespan(1), Duration::from_millis(30));
+    assert_eq!(frozen.estimate_makespan(2), Duration::from_millis(20));
+
+    let g: Graph<()> = Graph::new();
+    assert_eq!(g.froze().unwrap().critical_path(), (Duration::ZERO, vec![]));
+}
+
+#[test]
+fn simulate() {
+    let mut g = Graph::new();
+    for name in ["A", "B", "C", "D"] {
+        g.add_node(name, dummy_task).unwrap();
+    }
+    let frozen = g.froze().unwrap();
+    let ms = Duration::from_millis;
+    let costs: HashMap<String, Duration> = ["A", "B", "C"].iter().map(|name| (name.to_string(), ms(100))).collect();
+    for (workers, makespan) in [(1, 300), (2, 200), (3, 100), (0, 100)] {
+        let result = frozen.simulate(&costs, workers);
+        assert_eq!(result.makespan, ms(makespan));
+        assert_eq!(result.missing_costs, ["D"]);
+    }
+    let result = frozen.simulate(&costs, 2);
+    assert_eq!(result.start_times["A"], ms(0));
+    assert_eq!(result.start_times["B"], ms(0));
+    assert_eq!(result.start_times["C"], ms(100));
+    assert_eq!(result.start_times["D"], ms(100));
+    assert_eq!(result.utilization, 0.75);
+    assert_eq!(frozen.simulate(&costs, 3).utilization, 1.0);
+    assert_eq!(frozen.simulate(&costs, 0).utilization, 1.0);
+    assert_eq!(frozen.simulate(&HashMap::new(), 1).makespan, Duration::ZERO);
+
+    let mut costs = HashMap::new();
+    for (name, cost) in [("A", 40), ("B", 20), ("C", 30), ("D", 10)] {
+        costs.insert(name.to_string(), ms(cost));
+    }
+    let frozen = diamond(&["A", "B", "C", "D"]).froze().unwrap();
+    let result = frozen.simulate(&costs, 0);
+    assert_eq!(result.makespan, ms(80));
+    assert_eq!(result.start_times["D"], ms(70));
+    assert!(result.missing_costs.is_empty());
+}
+
+fn sleep_dag() -> Graph<()> {
+    let mut g = Graph::new();
+    for name in ["A1", "A2", "A3", "B1", "B2", "B3", "C1", "C2", "C3"] {
+        g.add_node(name, dummy_task).unwrap();
+    }
+    for (from, to) in [
+        ("A1", "B1"), ("A1", "B2"), ("A1", "B3"), ("A2", "B1"), ("A2", "B3"), ("A3", "B3"),
+        ("B1", "C2"), ("B1", "C3"), ("B2", "C2"), ("B3", "C1"), ("B3", "C2"), ("B3", "C3"),
+    ] {
+        g.add_edge(from, to).unwrap();
+    }
+    return g;
+}
+
+#[test]
+fn paths() {
+    let frozen = sleep_dag().froze().unwrap();
+    assert!(frozen.is_reachable("A1", "C1").unwrap());
+    assert!(frozen.is_reachable("A1", "A1").unwrap());
+    assert!(!frozen.is_reachable("C1", "A1").unwrap());
+    assert!(!frozen.is_reachable("A2", "B2").unwrap());
+    assert_eq!(frozen.shortest_path("A1", "C1").unwrap().unwrap(), ["A1", "B3", "C1"]);
+    assert_eq!(frozen.longest_path("A1", "C1").unwrap().unwrap(), ["A1", "B3", "C1"]);
+    assert_eq!(frozen.shortest_path("A3", "A3").unwrap().unwrap(), ["A3"]);
+    assert_eq!(frozen.shortest_path("B2", "C3").unwrap(), None);
+    assert_eq!(frozen.longest_path("B2", "C3").unwrap(), None);
+    assert!(frozen.is_reachable("A1", "X").is_err_and(
+        |e| if let NodeNotFound{name} = e { name == "X" } else { false }
+    ));
+    assert!(frozen.longest_path("X", "A1").is_err());
+
+    let mut g = diamond(&["A", "B", "C", "D"]);
+    g.add_node("E", dummy_task).unwrap();
+    g.add_edge("A", "E").unwrap();
+    g.add_edge("E", "C").unwrap();
+    g.add_edge("A", "D").unwrap();
+    let frozen = g.froze().unwrap();
+    assert_eq!(frozen.shortest_path("A", "D").unwrap().unwrap(), ["A", "D"]);
+    assert_eq!(frozen.longest_path("A", "D").unwrap().unwrap(), ["A", "E", "C", "D"]);
+}
@@ -0,0 +1,76 @@
+#![cfg(target_arch = "wasm32")]
+#![allow(clippy::needless_return)]
+
+// Run with `cargo test --target wasm32-unknown-unknown --test wasm_test`,
+// which needs `wasm-bindgen-test-runner` from wasm-bindgen-cli and node.
+
+use std::cell::RefCell;
+use std::sync::{Arc, Mutex};
+
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use dag_engine::{Error::*, TaskError, Graph, Scheduler, SchedulerBuilder, NodeStatus};
+
+fn diamond(failing: &'static str) -> Graph<Mutex<Vec<&'static str>>> {
+    let mut g = Graph::new();
+    for name in ["a", "b", "c", "d"] {
+        g.add_node(name, move |ctx: &Mutex<Vec<&str>>| -> Result<(), TaskError> {
+            ctx.lock().unwrap().push(name);
+            if name == failing {
+                return Err(Box::new(std::fmt::Error));
+            }
+            Ok(())
+        }).unwrap();
+    }
+    g.add_edge("a", "b").unwrap();
+    g.add_edge("a", "c").unwrap();
+    g.add_edge("b", "d").unwrap();
+    g.add_edge("c", "d").unwrap();
+    return g;
+}
+
+#[wasm_bindgen_test]
+fn run_inline() {
+    let s = Scheduler::new(diamond("").froze().unwrap());
+    let ctx = Mutex::new(vec![]);
+    s.run(&ctx).unwrap();
+    let order = ctx.into_inner().unwrap();
+    assert_eq!(order.len(), 4);
+    assert_eq!(order[0], "a");
+    assert_eq!(order[3], "d");
+
+    let (result, report) = s.run_with_report(&Mutex::new(vec![]));
+    result.unwrap();
+    assert!(report.nodes().iter().all(|node| matches!(node.status, NodeStatus::Succeeded{..})));
+}
+
+#[wasm_bindgen_test]
+fn failure() {
+    let s = SchedulerBuilder::new(diamond("b").froze().unwrap()).build();
+    let ctx = Mutex::new(vec![]);
+    assert!(s.run(&ctx).is_err_and(|e| if let RuntimeFailed{node, ..} = e { node == "b" } else { false }));
+    assert!(!ctx.into_inner().unwrap().contains(&"d"));
+}
+
+#[wasm_bindgen_test]
+fn send_not_sync_context() {
+    let mut g: Graph<RefCell<Vec<String>>> = Graph::new();
+    g.add_node("a", |ctx: &RefCell<Vec<String>>| -> Result<(), TaskError> {
+        ctx.borrow_mut().push(String::from("a"));
+        Ok(())
+    }).unwrap();
+    let s = Scheduler::new(g.froze().unwrap());
+    let ctx = s.run_sequential_send(RefCell::new(vec![])).unwrap();
+    assert_eq!(ctx.into_inner(), vec!["a"]);
+}
+
+#[wasm_bindgen_test]
+fn run_arc() {
+    let s = Scheduler::new(diamond("").froze().unwrap());
+    let ctx = Arc::new(Mutex::new(vec![]));
+    s.run_arc(ctx.clone()).unwrap();
+    let order = ctx.lock().unwrap();
+    assert_eq!(order.len(), 4);
+    assert_eq!(order[0], "a");
+    assert_eq!(order[3], "d");
+}